@@ -0,0 +1,243 @@
+//! What organisms are doing from tick to tick, and how many different things
+//! each one does over its life, to see whether flexible organisms live
+//! longer. Strategies followed by few organisms can get more out of the food
+//! they find, so that no single one takes over, and organisms crowded by
+//! others following their strategy get less.
+
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::collision::circle_circle;
+use crate::config::SimulationConfig;
+use crate::genes::{Traits, N_INPUTS};
+use crate::organism::{DeathEvent, InteractionRadius, Organism, SensoryInputs, Speed};
+use crate::plugin::LogTimer;
+use crate::spatial::SpatialGrid;
+use crate::topology::{self, displacement};
+use crate::SimTick;
+
+/// Fraction of its top speed under which an organism is resting.
+const RESTING_SPEED: f32 = 0.1;
+
+/// The movement strategy an organism follows on a tick, classified from its
+/// speed and what it sensed on the last sensory tick.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum BehaviorArchetype {
+    /// Barely moving.
+    Resting,
+    /// Moving with food in sight.
+    Foraging,
+    /// Moving with pheromones but no food in sight.
+    Trailing,
+    /// Moving with nothing in sight.
+    Exploring,
+}
+
+impl BehaviorArchetype {
+    pub fn classify(speed: f32, max_speed: f32, inputs: &[f32; N_INPUTS]) -> Self {
+        // food in the left, front and right sectors, then pheromones
+        let food = inputs[5..8].iter().any(|&f| f > 0.0);
+        let scent = inputs[15..18].iter().any(|&s| s > 0.0);
+        if speed < RESTING_SPEED * max_speed {
+            Self::Resting
+        } else if food {
+            Self::Foraging
+        } else if scent {
+            Self::Trailing
+        } else {
+            Self::Exploring
+        }
+    }
+}
+
+/// Every [`BehaviorArchetype`] the organism followed so far.
+#[derive(Component, Clone, Debug, Default)]
+pub struct BehaviorRepertoire {
+    pub strategies_used: HashSet<BehaviorArchetype>,
+}
+
+/// Fraction of the organisms that followed each [`BehaviorArchetype`] on
+/// the last tick.
+#[derive(Resource, Default)]
+pub struct StrategyFrequency(pub HashMap<BehaviorArchetype, f32>);
+
+impl StrategyFrequency {
+    pub fn of(&self, archetype: BehaviorArchetype) -> f32 {
+        self.0.get(&archetype).copied().unwrap_or(0.0)
+    }
+
+    /// Extra energy for eating while following `archetype`, up to
+    /// `advantage` the rarer it is, see `rare_advantage`.
+    pub fn rare_advantage(&self, advantage: f32, archetype: BehaviorArchetype) -> f32 {
+        advantage * (1.0 - self.of(archetype))
+    }
+}
+
+/// Fraction of their food gain the organisms lose to others following the
+/// same strategy they overlap with, see `competition_strength`.
+#[derive(Resource, Default)]
+pub struct StrategyCompetition {
+    losses: HashMap<Entity, f32>,
+    /// Mean of the fraction lost over all organisms on the last tick.
+    pub mean_intensity: f32,
+}
+
+impl StrategyCompetition {
+    /// What is left of the food gain of `organism`.
+    pub fn food_factor(&self, organism: Entity) -> f32 {
+        1.0 - self.losses.get(&organism).copied().unwrap_or(0.0)
+    }
+}
+
+/// Running sums to correlate the repertoire size of the organisms with
+/// their lifespan at death.
+#[derive(Resource, Default)]
+pub struct RepertoireLifespan {
+    n: f64,
+    sum_x: f64,
+    sum_y: f64,
+    sum_xx: f64,
+    sum_yy: f64,
+    sum_xy: f64,
+}
+
+impl RepertoireLifespan {
+    pub fn add(&mut self, repertoire: usize, lifespan: usize) {
+        let (x, y) = (repertoire as f64, lifespan as f64);
+        self.n += 1.0;
+        self.sum_x += x;
+        self.sum_y += y;
+        self.sum_xx += x * x;
+        self.sum_yy += y * y;
+        self.sum_xy += x * y;
+    }
+
+    /// Pearson correlation of repertoire size and lifespan over the deaths
+    /// so far, none until both have varied.
+    pub fn correlation(&self) -> Option<f64> {
+        let cov = self.n * self.sum_xy - self.sum_x * self.sum_y;
+        let var_x = self.n * self.sum_xx - self.sum_x * self.sum_x;
+        let var_y = self.n * self.sum_yy - self.sum_y * self.sum_y;
+        (var_x > 0.0 && var_y > 0.0).then(|| cov / (var_x * var_y).sqrt())
+    }
+}
+
+pub(crate) fn record_behavior(
+    mut frequency: ResMut<StrategyFrequency>,
+    mut query: Query<(&Speed, &Traits, &SensoryInputs, &mut BehaviorRepertoire), With<Organism>>,
+) {
+    let mut counts: HashMap<BehaviorArchetype, usize> = HashMap::new();
+    let mut organisms = 0;
+    for (speed, traits, inputs, mut repertoire) in &mut query {
+        let archetype = BehaviorArchetype::classify(speed.0, traits.max_speed, &inputs.0);
+        *counts.entry(archetype).or_default() += 1;
+        organisms += 1;
+        // only touch the component when the set grows
+        if !repertoire.strategies_used.contains(&archetype) {
+            repertoire.strategies_used.insert(archetype);
+        }
+    }
+    frequency.0 = counts
+        .into_iter()
+        .map(|(archetype, count)| (archetype, count as f32 / organisms as f32))
+        .collect();
+}
+
+pub(crate) fn correlate_repertoire(
+    mut correlation: ResMut<RepertoireLifespan>,
+    mut death_events: EventReader<DeathEvent>,
+) {
+    for event in death_events.iter() {
+        correlation.add(event.repertoire, event.age);
+    }
+}
+
+/// Measures how far every organism overlaps others following the same
+/// strategy, as a fraction of its own diameter up to 1, and how much of its
+/// food gain that costs it.
+#[allow(clippy::type_complexity)]
+pub(crate) fn measure_competition(
+    config: Res<SimulationConfig>,
+    grid: Res<SpatialGrid>,
+    mut competition: ResMut<StrategyCompetition>,
+    query: Query<
+        (
+            Entity,
+            &Transform,
+            &InteractionRadius,
+            &Speed,
+            &Traits,
+            &SensoryInputs,
+        ),
+        With<Organism>,
+    >,
+) {
+    competition.losses.clear();
+    competition.mean_intensity = 0.0;
+    if config.competition_strength <= 0.0 {
+        return;
+    }
+    let largest = query
+        .iter()
+        .map(|(_, _, radius, ..)| radius.0)
+        .fold(0.0, f32::max);
+    let mut total = 0.0;
+    let mut organisms = 0;
+    for (organism, transform, radius, speed, traits, inputs) in &query {
+        organisms += 1;
+        let archetype = BehaviorArchetype::classify(speed.0, traits.max_speed, &inputs.0);
+        let position = transform.translation;
+        let nearby =
+            topology::query_circle(&config, &grid, position.truncate(), radius.0 + largest);
+        let mut overlap = 0.0;
+        for (other, other_transform, other_radius, other_speed, other_traits, other_inputs) in
+            query.iter_many(&nearby)
+        {
+            if other == organism
+                || BehaviorArchetype::classify(
+                    other_speed.0,
+                    other_traits.max_speed,
+                    &other_inputs.0,
+                ) != archetype
+            {
+                continue;
+            }
+            let offset = displacement(&config, other_transform.translation, position);
+            if let Some(contact) =
+                circle_circle(offset.truncate(), radius.0, Vec2::ZERO, other_radius.0)
+            {
+                overlap += contact.depth / (2.0 * radius.0);
+            }
+        }
+        let loss = config.competition_strength * f32::min(overlap, 1.0);
+        if loss > 0.0 {
+            competition.losses.insert(organism, loss);
+            total += loss;
+        }
+    }
+    competition.mean_intensity = total / organisms.max(1) as f32;
+}
+
+/// Writes the mean competition intensity to competition.csv on every log
+/// tick, when there is competition between strategies.
+pub(crate) fn log_competition(
+    config: Res<SimulationConfig>,
+    tick: Res<SimTick>,
+    timer: Res<LogTimer>,
+    competition: Res<StrategyCompetition>,
+    mut log: Local<Option<std::io::BufWriter<std::fs::File>>>,
+) {
+    if config.competition_strength <= 0.0 || !timer.0.just_finished() {
+        return;
+    }
+    let log = log.get_or_insert_with(|| {
+        let file = std::fs::File::create("competition.csv").unwrap();
+        let mut file = std::io::BufWriter::new(file);
+        file.write_all(b"tick,mean_intensity\n").unwrap();
+        file
+    });
+    writeln!(log, "{},{}", tick.0, competition.mean_intensity).unwrap();
+}
@@ -0,0 +1,82 @@
+//! The bottleneck test: culling most of the population and timing how long
+//! its genetic diversity takes to recover, to see how fast the population
+//! diversifies again from a few survivors.
+
+use bevy::prelude::*;
+use rand::seq::SliceRandom;
+
+use crate::diversity::shannon_index;
+use crate::genes::GeneInfo;
+use crate::organism::Organism;
+use crate::{Dead, DeathCause, SimRng, SimTick};
+
+/// Tick the population is culled at.
+pub const BOTTLENECK_TICK: u64 = 1000;
+/// Fraction of the organisms killed by the cull.
+pub const CULL_FRACTION: f32 = 0.9;
+/// Fraction of the Shannon index before the cull that counts as recovered.
+pub const RECOVERY_FRACTION: f32 = 0.9;
+/// Ticks after the cull a replicate gives up waiting for the recovery.
+pub const MAX_RECOVERY_TICKS: u64 = 10_000;
+
+pub struct BottleneckOutcome {
+    /// Shannon index right before the cull.
+    pub shannon_before: f32,
+    /// Organisms left by the cull.
+    pub survivors: usize,
+    /// Ticks from the cull until the Shannon index was back to
+    /// [`RECOVERY_FRACTION`] of what it was, none if it never was.
+    pub recovery_ticks: Option<u64>,
+}
+
+/// Marks `fraction` of the organisms dead, picked with the simulation rng,
+/// and returns how many are left.
+pub fn cull(world: &mut World, fraction: f32) -> usize {
+    let mut query = world.query_filtered::<Entity, With<Organism>>();
+    let mut organisms: Vec<Entity> = query.iter(world).collect();
+    organisms.shuffle(&mut world.resource_mut::<SimRng>().0);
+    let survivors = organisms.len() - (organisms.len() as f32 * fraction).round() as usize;
+    for organism in organisms.split_off(survivors) {
+        world.entity_mut(organism).insert(Dead(DeathCause::Culled));
+    }
+    survivors
+}
+
+fn shannon(world: &mut World) -> f32 {
+    let mut query = world.query_filtered::<&GeneInfo, With<Organism>>();
+    shannon_index(query.iter(world))
+}
+
+/// Runs `app`, already set up, to [`BOTTLENECK_TICK`], culls it, then runs
+/// it until its diversity recovers or the population dies out.
+pub fn run_bottleneck(app: &mut App) -> BottleneckOutcome {
+    let tick = |app: &App| app.world.resource::<SimTick>().0;
+    while tick(app) < BOTTLENECK_TICK {
+        app.update();
+    }
+    let shannon_before = shannon(&mut app.world);
+    let survivors = cull(&mut app.world, CULL_FRACTION);
+    let mut recovery_ticks = None;
+    let mut last_tick = tick(app);
+    while last_tick < BOTTLENECK_TICK + MAX_RECOVERY_TICKS {
+        app.update();
+        // the culled organisms are only gone once a tick has run
+        if tick(app) == last_tick {
+            continue;
+        }
+        last_tick = tick(app);
+        let mut organisms = app.world.query_filtered::<(), With<Organism>>();
+        if organisms.iter(&app.world).next().is_none() {
+            break;
+        }
+        if shannon(&mut app.world) >= RECOVERY_FRACTION * shannon_before {
+            recovery_ticks = Some(last_tick - BOTTLENECK_TICK);
+            break;
+        }
+    }
+    BottleneckOutcome {
+        shannon_before,
+        survivors,
+        recovery_ticks,
+    }
+}
@@ -0,0 +1,93 @@
+//! The walls around the arena and the obstacles inside it.
+
+use bevy::prelude::*;
+
+use crate::config::SimulationConfig;
+use crate::Collider;
+
+pub const BOUNDARY_COLOR: Color = Color::rgb(0.8, 0.8, 0.8);
+
+// This bundle is a collection of the components that define a "boundary" in our game
+#[derive(Bundle)]
+pub struct BoundaryBundle {
+    // You can nest bundles inside of other bundles like this
+    // Allowing you to compose their functionality
+    sprite_bundle: SpriteBundle,
+    collider: Collider,
+}
+
+/// Which side of the arena is this boundary located on?
+pub(crate) enum BoundaryLocation {
+    Left,
+    Right,
+    Bottom,
+    Top,
+    /// Splits the arena in two in mirror mode.
+    Center,
+    /// An obstacle set in the config.
+    Obstacle {
+        center: Vec2,
+        size: Vec2,
+    },
+}
+
+impl BoundaryLocation {
+    fn position(&self, config: &SimulationConfig) -> Vec2 {
+        let center_x = (config.left_boundary + config.right_boundary) / 2.0;
+        let center_y = (config.bottom_boundary + config.top_boundary) / 2.0;
+        match self {
+            BoundaryLocation::Left => Vec2::new(config.left_boundary, center_y),
+            BoundaryLocation::Right => Vec2::new(config.right_boundary, center_y),
+            BoundaryLocation::Bottom => Vec2::new(center_x, config.bottom_boundary),
+            BoundaryLocation::Top => Vec2::new(center_x, config.top_boundary),
+            BoundaryLocation::Center => Vec2::new(center_x, center_y),
+            BoundaryLocation::Obstacle { center, .. } => *center,
+        }
+    }
+
+    fn size(&self, config: &SimulationConfig) -> Vec2 {
+        let arena_height = config.top_boundary - config.bottom_boundary;
+        let arena_width = config.right_boundary - config.left_boundary;
+        // Make sure we haven't messed up our config
+        assert!(arena_height > 0.0);
+        assert!(arena_width > 0.0);
+
+        let thickness = config.boundary_thickness;
+        match self {
+            BoundaryLocation::Left | BoundaryLocation::Right | BoundaryLocation::Center => {
+                Vec2::new(thickness, arena_height + thickness)
+            }
+            BoundaryLocation::Bottom | BoundaryLocation::Top => {
+                Vec2::new(arena_width + thickness, thickness)
+            }
+            BoundaryLocation::Obstacle { size, .. } => *size,
+        }
+    }
+}
+
+impl BoundaryBundle {
+    // This "builder method" allows us to reuse logic across our boundary entities,
+    // making our code easier to read and less prone to bugs when we change the logic
+    pub(crate) fn new(location: BoundaryLocation, config: &SimulationConfig) -> BoundaryBundle {
+        BoundaryBundle {
+            sprite_bundle: SpriteBundle {
+                transform: Transform {
+                    // We need to convert our Vec2 into a Vec3, by giving it a z-coordinate
+                    // This is used to determine the order of our sprites
+                    translation: location.position(config).extend(0.0),
+                    // The z-scale of 2D objects must always be 1.0,
+                    // or their ordering will be affected in surprising ways.
+                    // See https://github.com/bevyengine/bevy/issues/4149
+                    scale: location.size(config).extend(1.0),
+                    ..default()
+                },
+                sprite: Sprite {
+                    color: BOUNDARY_COLOR,
+                    ..default()
+                },
+                ..default()
+            },
+            collider: Collider,
+        }
+    }
+}
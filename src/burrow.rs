@@ -0,0 +1,67 @@
+//! Burrowing: organisms with a strong enough burrowing gene hide
+//! underground when a predator comes close. Underground they are out of
+//! reach and out of sight of carnivores, but cannot eat while their energy
+//! keeps draining.
+
+use bevy::prelude::*;
+
+use crate::config::SimulationConfig;
+use crate::genes::Traits;
+use crate::organism::Organism;
+use crate::spatial::SpatialGrid;
+use crate::topology::{self, displacement};
+
+/// Ticks an organism stays underground once it burrows.
+pub const BURROW_DURATION: usize = 30;
+/// Burrowing gene above which an organism burrows.
+pub const BURROW_THRESHOLD: f32 = 0.5;
+
+/// Ticks the organism has left underground, 0 when above ground.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct Burrowed(pub usize);
+
+impl Burrowed {
+    pub fn underground(&self) -> bool {
+        self.0 > 0
+    }
+}
+
+/// How close a predator has to come for an organism to burrow.
+fn burrow_range(config: &SimulationConfig) -> f32 {
+    config.organism_vision / 2.0
+}
+
+/// Counts down the ticks of burrowed organisms, and burrows those above
+/// ground with a predator in range: a carnivore more carnivorous than
+/// them, that could eat them.
+pub(crate) fn burrow(
+    config: Res<SimulationConfig>,
+    grid: Res<SpatialGrid>,
+    mut organism_query: Query<(Entity, &Transform, &Traits, &mut Burrowed), With<Organism>>,
+    predator_query: Query<(Entity, &Transform, &Traits), With<Organism>>,
+) {
+    let range = burrow_range(&config);
+    for (organism, transform, traits, mut burrowed) in &mut organism_query {
+        if burrowed.underground() {
+            burrowed.0 -= 1;
+            continue;
+        }
+        if traits.burrowing <= BURROW_THRESHOLD {
+            continue;
+        }
+        let position = transform.translation;
+        let nearby = topology::query_circle(&config, &grid, position.truncate(), range);
+        let threatened = predator_query.iter_many(&nearby).any(
+            |(predator, predator_transform, predator_traits)| {
+                predator != organism
+                    && predator_traits.diet >= config.carnivore_threshold
+                    && predator_traits.diet > traits.diet
+                    && displacement(&config, position, predator_transform.translation).length()
+                        < range
+            },
+        );
+        if threatened {
+            burrowed.0 = BURROW_DURATION;
+        }
+    }
+}
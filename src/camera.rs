@@ -0,0 +1,168 @@
+//! Moving the camera around the arena: WASD or the arrow keys and dragging
+//! with the middle mouse button pan, the scroll wheel zooms on the cursor
+//! and Home goes back to the whole arena. F follows an organism around.
+
+use bevy::input::mouse::{MouseMotion, MouseScrollUnit, MouseWheel};
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+
+use crate::config::SimulationConfig;
+use crate::inspector::Selected;
+use crate::organism::Organism;
+use crate::Age;
+
+/// Pan speed of the keys, in screen pixels per second.
+const PAN_SPEED: f32 = 600.0;
+/// Zoom factor of one line of scrolling.
+const ZOOM_STEP: f32 = 1.1;
+/// Pixels of scrolling that count as one line, for touchpads.
+const PIXELS_PER_LINE: f32 = 100.0;
+/// Range of projection scales, world units per screen pixel.
+const SCALE_RANGE: (f32, f32) = (0.05, 4.0);
+/// Room left around the arena by Home.
+const ARENA_MARGIN: f32 = 1.05;
+/// How fast the camera catches up with the followed organism, the gap
+/// shrinks by a factor of e every `1 / FOLLOW_RATE` seconds.
+const FOLLOW_RATE: f32 = 5.0;
+
+/// The organism the camera follows, if any, and where the camera was before
+/// it started following.
+#[derive(Resource, Default)]
+pub struct CameraFollow {
+    pub target: Option<Entity>,
+    free_position: Vec3,
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn camera_controls(
+    keys: Res<Input<KeyCode>>,
+    mouse: Res<Input<MouseButton>>,
+    mut motion: EventReader<MouseMotion>,
+    mut wheel: EventReader<MouseWheel>,
+    time: Res<Time>,
+    config: Res<SimulationConfig>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    mut camera_query: Query<(
+        &Camera,
+        &GlobalTransform,
+        &mut Transform,
+        &mut OrthographicProjection,
+    )>,
+) {
+    let Ok(window) = window_query.get_single() else {
+        return;
+    };
+    let Ok((camera, camera_transform, mut transform, mut projection)) =
+        camera_query.get_single_mut()
+    else {
+        return;
+    };
+
+    if keys.just_pressed(KeyCode::Home) {
+        let min = Vec2::new(config.left_boundary, config.bottom_boundary);
+        let max = Vec2::new(config.right_boundary, config.top_boundary);
+        let center = (min + max) / 2.0;
+        transform.translation = center.extend(transform.translation.z);
+        let fit = (max - min) / Vec2::new(window.width(), window.height());
+        projection.scale = (fit.max_element() * ARENA_MARGIN).clamp(SCALE_RANGE.0, SCALE_RANGE.1);
+        return;
+    }
+
+    // Ctrl+A dumps the elite archive, it shouldn't pan too
+    let ctrl = keys.any_pressed([KeyCode::LControl, KeyCode::RControl]);
+    let mut pan = Vec2::ZERO;
+    if !ctrl {
+        for (keys_pressed, direction) in [
+            ([KeyCode::W, KeyCode::Up], Vec2::Y),
+            ([KeyCode::S, KeyCode::Down], Vec2::NEG_Y),
+            ([KeyCode::A, KeyCode::Left], Vec2::NEG_X),
+            ([KeyCode::D, KeyCode::Right], Vec2::X),
+        ] {
+            if keys.any_pressed(keys_pressed) {
+                pan += direction;
+            }
+        }
+    }
+    pan *= PAN_SPEED * time.delta_seconds();
+    let dragged: Vec2 = motion.iter().map(|event| event.delta).sum();
+    if mouse.pressed(MouseButton::Middle) {
+        // the screen y axis points down
+        pan += Vec2::new(-dragged.x, dragged.y);
+    }
+    let pan = pan * projection.scale;
+    transform.translation += pan.extend(0.0);
+
+    let lines: f32 = wheel
+        .iter()
+        .map(|event| match event.unit {
+            MouseScrollUnit::Line => event.y,
+            MouseScrollUnit::Pixel => event.y / PIXELS_PER_LINE,
+        })
+        .sum();
+    if lines == 0.0 {
+        return;
+    }
+    let scale = (projection.scale * ZOOM_STEP.powf(-lines)).clamp(SCALE_RANGE.0, SCALE_RANGE.1);
+    // keep the point under the cursor where it is on screen, the global
+    // transform is still the one from before the pan
+    if let Some(cursor) = window
+        .cursor_position()
+        .and_then(|cursor| camera.viewport_to_world(camera_transform, cursor))
+        .map(|ray| ray.origin.truncate() + pan)
+    {
+        let offset = transform.translation.truncate() - cursor;
+        let center = cursor + offset * scale / projection.scale;
+        transform.translation = center.extend(transform.translation.z);
+    }
+    projection.scale = scale;
+}
+
+/// F toggles following the selected organism, or the oldest one when none is
+/// selected. The camera goes back to where it was once the followed organism
+/// dies.
+#[allow(clippy::type_complexity)]
+pub(crate) fn follow_camera(
+    keys: Res<Input<KeyCode>>,
+    time: Res<Time>,
+    mut follow: ResMut<CameraFollow>,
+    selected_query: Query<Entity, (With<Selected>, With<Organism>)>,
+    organism_query: Query<(Entity, &Transform, &Age), (With<Organism>, Without<Camera>)>,
+    mut camera_query: Query<&mut Transform, With<Camera>>,
+) {
+    let Ok(mut camera) = camera_query.get_single_mut() else {
+        return;
+    };
+    let selected = selected_query.get_single().ok();
+    if keys.just_pressed(KeyCode::F) {
+        if follow.target.is_some() {
+            follow.target = None;
+            return;
+        }
+        follow.target = selected.or_else(|| {
+            organism_query
+                .iter()
+                .max_by_key(|&(_, _, age)| age.0)
+                .map(|(organism, ..)| organism)
+        });
+        follow.free_position = camera.translation;
+    }
+    let Some(mut target) = follow.target else {
+        return;
+    };
+    // a new selection is followed from where the camera is
+    if let Some(selected) = selected {
+        target = selected;
+        follow.target = Some(selected);
+    }
+    let Ok((_, transform, _)) = organism_query.get(target) else {
+        follow.target = None;
+        camera.translation = follow.free_position;
+        return;
+    };
+    let goal = transform
+        .translation
+        .truncate()
+        .extend(camera.translation.z);
+    let t = 1.0 - (-FOLLOW_RATE * time.delta_seconds()).exp();
+    camera.translation = camera.translation.lerp(goal, t);
+}
@@ -0,0 +1,122 @@
+//! A periodic census of entities, assets and the resources that grow over a
+//! run, to catch unbounded growth in long runs.
+
+use std::io::Write;
+
+use bevy::prelude::*;
+
+use crate::config::SimulationConfig;
+use crate::elite::EliteArchive;
+use crate::food::Food;
+use crate::organism::Organism;
+use crate::plugin::{LogTimer, SimState};
+use crate::stats::{GrowthCurve, SimStats};
+use crate::SimTick;
+
+/// Names of the quantities counted by the census, in the order of the
+/// census.csv columns.
+pub const CENSUS_QUANTITIES: [&str; 8] = [
+    "organisms",
+    "food",
+    "other_entities",
+    "meshes",
+    "materials",
+    "stats_history",
+    "elite_archive",
+    "growth_curve_bins",
+];
+
+/// Last census samples and the quantities found leaking.
+#[derive(Resource, Default)]
+pub struct Census {
+    /// Log ticks seen so far, a census is taken every `census.interval`.
+    log_ticks: usize,
+    /// Values of the last census, in the order of [`CENSUS_QUANTITIES`].
+    pub values: Vec<usize>,
+    /// Consecutive samples each quantity has grown for.
+    growing: Vec<usize>,
+    /// Quantities above their bound that kept growing for
+    /// `census.growth_samples` samples.
+    pub leaks: Vec<&'static str>,
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn take_census(
+    config: Res<SimulationConfig>,
+    tick: Res<SimTick>,
+    timer: Res<LogTimer>,
+    mut census: ResMut<Census>,
+    mut next_state: ResMut<NextState<SimState>>,
+    mut log: Local<Option<std::io::BufWriter<std::fs::File>>>,
+    entity_query: Query<Entity>,
+    organism_query: Query<(), With<Organism>>,
+    food_query: Query<(), With<Food>>,
+    meshes: Option<Res<Assets<Mesh>>>,
+    materials: Option<Res<Assets<ColorMaterial>>>,
+    stats: Res<SimStats>,
+    elite_archive: Res<EliteArchive>,
+    growth_curve: Res<GrowthCurve>,
+) {
+    if !timer.0.just_finished() {
+        return;
+    }
+    census.log_ticks += 1;
+    if !census.log_ticks.is_multiple_of(config.census.interval) {
+        return;
+    }
+
+    let organisms = organism_query.iter().count();
+    let food = food_query.iter().count();
+    let entities = entity_query.iter().count();
+    let bounds = &config.census;
+    let values = [
+        (organisms, bounds.max_entities),
+        (food, bounds.max_entities),
+        (entities - organisms - food, bounds.max_entities),
+        (meshes.map_or(0, |m| m.len()), bounds.max_assets),
+        (materials.map_or(0, |m| m.len()), bounds.max_assets),
+        (stats.history_len(), bounds.max_resource_len),
+        (elite_archive.organisms.len(), bounds.max_resource_len),
+        (growth_curve.bins.len(), bounds.max_resource_len),
+    ];
+
+    let census = &mut *census;
+    census.growing.resize(values.len(), 0);
+    census.leaks.clear();
+    for (i, &(value, bound)) in values.iter().enumerate() {
+        if census
+            .values
+            .get(i)
+            .is_some_and(|&previous| value > previous)
+        {
+            census.growing[i] += 1;
+        } else {
+            census.growing[i] = 0;
+        }
+        if value > bound && census.growing[i] >= bounds.growth_samples {
+            census.leaks.push(CENSUS_QUANTITIES[i]);
+        }
+    }
+    census.values = values.iter().map(|&(value, _)| value).collect();
+    if !census.leaks.is_empty() {
+        warn!(
+            "possible leak, still growing past their bound: {}",
+            census.leaks.join(", ")
+        );
+        if bounds.pause_on_leak {
+            next_state.set(SimState::Paused);
+        }
+    }
+
+    let log = log.get_or_insert_with(|| {
+        let file = std::fs::File::create("census.csv").unwrap();
+        let mut file = std::io::BufWriter::new(file);
+        writeln!(file, "tick,{}", CENSUS_QUANTITIES.join(",")).unwrap();
+        file
+    });
+    write!(log, "{}", tick.0).unwrap();
+    for value in &census.values {
+        write!(log, ",{value}").unwrap();
+    }
+    writeln!(log).unwrap();
+}
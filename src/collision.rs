@@ -0,0 +1,209 @@
+//! Overlap tests between the circles organisms and food are drawn as and the
+//! boxes of the walls and obstacles, and where a line crosses a box.
+
+use bevy::prelude::*;
+
+/// How two shapes overlap.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Contact {
+    /// Unit vector from the other shape towards the circle, the way the
+    /// circle has to move to stop overlapping.
+    pub normal: Vec2,
+    /// How far the shapes overlap along `normal`.
+    pub depth: f32,
+}
+
+/// Overlap of a circle at `center` with one at `other`, `None` when they
+/// don't overlap or only touch.
+pub fn circle_circle(center: Vec2, radius: f32, other: Vec2, other_radius: f32) -> Option<Contact> {
+    let offset = center - other;
+    let distance = offset.length();
+    let depth = radius + other_radius - distance;
+    if depth <= 0.0 {
+        return None;
+    }
+    Some(Contact {
+        // concentric circles can be pushed apart any way
+        normal: offset.try_normalize().unwrap_or(Vec2::X),
+        depth,
+    })
+}
+
+/// Overlap of a circle at `center` with the box around `box_center` reaching
+/// `half_size` out on either side, `None` when they don't overlap or only
+/// touch.
+pub fn circle_aabb(
+    center: Vec2,
+    radius: f32,
+    box_center: Vec2,
+    half_size: Vec2,
+) -> Option<Contact> {
+    let offset = center - box_center;
+    let closest = offset.clamp(-half_size, half_size);
+    if closest != offset {
+        // the center is outside the box, so the closest point is on its edge
+        let to_center = offset - closest;
+        let distance = to_center.length();
+        if distance >= radius {
+            return None;
+        }
+        return Some(Contact {
+            normal: to_center / distance,
+            depth: radius - distance,
+        });
+    }
+    // the center is inside the box, push it out through the nearest side
+    let gap = half_size - offset.abs();
+    let normal = if gap.x < gap.y {
+        Vec2::new(offset.x.signum(), 0.0)
+    } else {
+        Vec2::new(0.0, offset.y.signum())
+    };
+    Some(Contact {
+        normal,
+        depth: gap.min_element() + radius,
+    })
+}
+
+/// Fraction of the way from `start` to `start + delta` at which the segment
+/// enters the box around `box_center` reaching `half_size` out on either
+/// side, 0 when it starts inside, `None` when it misses the box.
+pub fn segment_aabb(start: Vec2, delta: Vec2, box_center: Vec2, half_size: Vec2) -> Option<f32> {
+    let (min, max) = (box_center - half_size, box_center + half_size);
+    let (mut enter, mut exit) = (0.0_f32, 1.0_f32);
+    for axis in 0..2 {
+        if delta[axis] == 0.0 {
+            // parallel to the sides of this axis, so it has to be between them
+            if start[axis] < min[axis] || start[axis] > max[axis] {
+                return None;
+            }
+            continue;
+        }
+        let near = (min[axis] - start[axis]) / delta[axis];
+        let far = (max[axis] - start[axis]) / delta[axis];
+        enter = enter.max(near.min(far));
+        exit = exit.min(near.max(far));
+        if enter > exit {
+            return None;
+        }
+    }
+    Some(enter)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_contact(contact: Option<Contact>, normal: Vec2, depth: f32) {
+        let contact = contact.expect("no contact");
+        assert!(
+            contact.normal.abs_diff_eq(normal, 1e-6),
+            "normal {} instead of {normal}",
+            contact.normal
+        );
+        assert!(
+            (contact.depth - depth).abs() < 1e-6,
+            "depth {} instead of {depth}",
+            contact.depth
+        );
+    }
+
+    #[test]
+    fn separated_and_tangent_circles_do_not_touch() {
+        let other = Vec2::new(1.0, 2.0);
+        assert_eq!(
+            circle_circle(other + Vec2::new(3.0, 4.0), 2.0, other, 2.0),
+            None
+        );
+        assert_eq!(
+            circle_circle(other + Vec2::new(3.0, 4.0), 2.0, other, 3.0),
+            None
+        );
+    }
+
+    #[test]
+    fn overlapping_circles_are_pushed_apart_along_their_centers() {
+        let other = Vec2::new(1.0, 2.0);
+        assert_contact(
+            circle_circle(other + Vec2::new(3.0, 4.0), 2.0, other, 4.0),
+            Vec2::new(0.6, 0.8),
+            1.0,
+        );
+    }
+
+    #[test]
+    fn a_circle_inside_another_is_pushed_all_the_way_out() {
+        let other = Vec2::new(1.0, 2.0);
+        assert_contact(
+            circle_circle(other + Vec2::new(-3.0, 4.0), 1.0, other, 10.0),
+            Vec2::new(-0.6, 0.8),
+            6.0,
+        );
+        // concentric, any way out will do
+        assert_contact(circle_circle(other, 1.0, other, 10.0), Vec2::X, 11.0);
+    }
+
+    #[test]
+    fn separated_and_tangent_circles_do_not_touch_a_box() {
+        let box_center = Vec2::new(10.0, 0.0);
+        let half_size = Vec2::new(2.0, 1.0);
+        // beyond a side and beyond a corner
+        assert_eq!(
+            circle_aabb(Vec2::new(10.0, 5.0), 3.0, box_center, half_size),
+            None
+        );
+        assert_eq!(
+            circle_aabb(Vec2::new(15.0, 5.0), 4.0, box_center, half_size),
+            None
+        );
+        // touching a side and a corner
+        assert_eq!(
+            circle_aabb(Vec2::new(10.0, 5.0), 4.0, box_center, half_size),
+            None
+        );
+        assert_eq!(
+            circle_aabb(Vec2::new(15.0, 5.0), 5.0, box_center, half_size),
+            None
+        );
+    }
+
+    #[test]
+    fn a_circle_overlapping_a_box_is_pushed_away_from_the_closest_point() {
+        let box_center = Vec2::new(10.0, 0.0);
+        let half_size = Vec2::new(2.0, 1.0);
+        assert_contact(
+            circle_aabb(Vec2::new(7.0, 0.5), 2.0, box_center, half_size),
+            Vec2::NEG_X,
+            1.0,
+        );
+        assert_contact(
+            circle_aabb(Vec2::new(15.0, 5.0), 6.0, box_center, half_size),
+            Vec2::new(0.6, 0.8),
+            1.0,
+        );
+    }
+
+    #[test]
+    fn a_circle_centered_in_a_box_is_pushed_out_through_the_nearest_side() {
+        let box_center = Vec2::new(10.0, 0.0);
+        let half_size = Vec2::new(4.0, 2.0);
+        // fully inside, nearest the top
+        assert_contact(
+            circle_aabb(Vec2::new(11.0, 1.0), 0.5, box_center, half_size),
+            Vec2::Y,
+            1.5,
+        );
+        // nearest the left side
+        assert_contact(
+            circle_aabb(Vec2::new(7.0, -0.5), 0.5, box_center, half_size),
+            Vec2::NEG_X,
+            1.5,
+        );
+        // the box fully inside the circle
+        assert_contact(
+            circle_aabb(Vec2::new(10.0, -1.0), 20.0, box_center, half_size),
+            Vec2::NEG_Y,
+            21.0,
+        );
+    }
+}
@@ -0,0 +1,81 @@
+//! Detects when the community settles on a stable composition, a sign that
+//! the population reached an evolutionarily stable mix of strategies.
+
+use bevy::prelude::*;
+
+use crate::genes::{GeneInfo, NETWORK_LENGTH, N_TRAITS};
+use crate::organism::Organism;
+use crate::SimTick;
+
+/// Ticks between two compositions that are compared.
+const COMPOSITION_INTERVAL: u64 = 50;
+/// Ticks the composition has to barely move for the community to be stable.
+const STABLE_TICKS: u64 = 200;
+/// Largest distance between two compositions [`COMPOSITION_INTERVAL`] ticks
+/// apart that still counts as barely moving.
+pub const STABILITY_THRESHOLD: f32 = 0.02;
+
+/// The composition of the community is the mean of the trait genes of its
+/// organisms, the strategy of the average organism.
+pub type Composition = [f32; N_TRAITS];
+
+#[derive(Resource, Default)]
+pub struct CommunityStability {
+    previous: Option<Composition>,
+    /// Tick since which every composition stayed within
+    /// [`STABILITY_THRESHOLD`] of the one before.
+    steady_since: Option<u64>,
+    /// L2 distance between the last two compositions.
+    pub distance: f32,
+    pub stable: bool,
+    /// Tick and composition of the last time the community became stable.
+    pub stable_composition: Option<(u64, Composition)>,
+}
+
+fn composition<'a>(genes: impl Iterator<Item = &'a GeneInfo>) -> Option<Composition> {
+    let mut sum = [0.0; N_TRAITS];
+    let mut count = 0;
+    for gene in genes {
+        for (total, g) in sum.iter_mut().zip(&gene.0[NETWORK_LENGTH..]) {
+            *total += g;
+        }
+        count += 1;
+    }
+    (count > 0).then(|| sum.map(|total| total / count as f32))
+}
+
+pub(crate) fn update_community_stability(
+    tick: Res<SimTick>,
+    mut stability: ResMut<CommunityStability>,
+    query: Query<&GeneInfo, With<Organism>>,
+) {
+    if !tick.0.is_multiple_of(COMPOSITION_INTERVAL) {
+        return;
+    }
+    let Some(current) = composition(query.iter()) else {
+        *stability = CommunityStability::default();
+        return;
+    };
+    let Some(previous) = stability.previous.replace(current) else {
+        return;
+    };
+    stability.distance = previous
+        .iter()
+        .zip(&current)
+        .map(|(a, b)| (a - b).powi(2))
+        .sum::<f32>()
+        .sqrt();
+    if stability.distance >= STABILITY_THRESHOLD {
+        stability.steady_since = None;
+        stability.stable = false;
+        return;
+    }
+    let steady_since = *stability
+        .steady_since
+        .get_or_insert(tick.0 - COMPOSITION_INTERVAL);
+    if !stability.stable && tick.0 - steady_since >= STABLE_TICKS {
+        stability.stable = true;
+        stability.stable_composition = Some((tick.0, current));
+        info!("COMMUNITY STABLE at tick {}", tick.0);
+    }
+}
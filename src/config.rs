@@ -0,0 +1,916 @@
+use std::path::PathBuf;
+
+use bevy::prelude::Resource;
+use serde::Deserialize;
+
+use crate::genes::MutationRates;
+
+/// All the tunable knobs of the simulation.
+///
+/// Loaded from a TOML file given on the command line; any field missing from
+/// the file keeps its default value.
+#[derive(Resource, Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SimulationConfig {
+    /// Seed for the simulation's random number generator, a random one is
+    /// picked when not given.
+    pub seed: Option<u64>,
+    /// Simulated time covered by every fixed tick; see `SimulationSpeed` for
+    /// changing how fast ticks run.
+    pub simulation_speed: f32,
+    pub top_boundary: f32,
+    pub bottom_boundary: f32,
+    pub right_boundary: f32,
+    pub left_boundary: f32,
+    pub boundary_thickness: f32,
+
+    /// Starting speed of organisms, and the max speed of the founders.
+    pub organism_default_speed: f32,
+    /// Vision range of the founders.
+    pub organism_vision: f32,
+    /// Energy used every tick per unit of an organism's vision range.
+    pub vision_range_energy_cost: f32,
+    /// Energy used every tick per radian of an organism's vision angle.
+    pub vision_angle_energy_cost: f32,
+    /// Energy used every tick by an organism glowing with a luminosity of 1,
+    /// it scales with the square of the luminosity.
+    pub luminosity_energy_cost: f32,
+    /// Frontal arc in degrees within which food has to be to get eaten, 360
+    /// lets organisms eat anything they touch.
+    pub mouth_arc: f32,
+    /// Maximum distance an organism can move in a tick per unit of energy.
+    pub max_speed_energy_coefficient: f32,
+    /// Weight of the previous turn and speed outputs when smoothing them,
+    /// between 0 (no smoothing) and 1.
+    pub steering_smoothing: f32,
+    /// How organisms change direction.
+    pub movement: MovementMode,
+    pub initial_population: usize,
+    pub food_per_timestep: usize,
+    pub pregnant_probability: f32,
+    /// Steepens selection for faster experiments when above 1, see
+    /// `organism::SelectionAmplification`.
+    pub selection_amplification: f32,
+    pub children_per_pregnancy: usize,
+    /// Ticks an organism stays pregnant before giving birth on the next one.
+    /// At 0 it gives birth on the tick after conceiving, too soon for the
+    /// pregnancy outline and the gestation in the inspector to be seen.
+    pub gestation_ticks: u64,
+
+    pub pregnancy_energy_minimum: f32,
+    pub organism_min_energy: f32,
+    pub organism_max_energy: f32,
+    /// Lifetime of the founders.
+    pub organism_default_lifetime: usize,
+    /// Side of the cells of the pheromone field, in world units.
+    pub pheromone_cell_size: f32,
+    /// Fraction of the pheromone in every cell left after a tick.
+    pub pheromone_decay: f32,
+    /// Fraction of the difference with each neighboring cell that flows
+    /// between them every tick, no diffusion when 0.
+    pub pheromone_diffusion: f32,
+    /// Emission intensity output of the network under which an organism
+    /// leaves no pheromone on a sensory tick.
+    pub pheromone_threshold: f32,
+    /// Energy an organism spends on a pheromone emitted at full intensity.
+    pub pheromone_energy_cost: f32,
+    /// Fertile age of the founders, the others become fertile at the same
+    /// fraction of their own lifetime.
+    pub fertile_age: usize,
+    pub food_lifetime: usize,
+    /// Probability of a new food item being poisonous, taking energy from
+    /// the organism eating it instead of giving it.
+    pub poison_probability: f32,
+    /// Fraction of its energy an organism dying of old age or starvation
+    /// leaves behind as corpse food. No corpses when 0.
+    pub corpse_energy_fraction: f32,
+    /// Age ticks corpse food lasts.
+    pub corpse_lifetime: usize,
+    pub mutation_rate: f32,
+    /// Per group overrides of `mutation_rate`.
+    pub mutation_rates: GroupMutationRates,
+    /// Write every mutation to mutations.csv, and the mutation rates to
+    /// mutations.json.
+    pub log_mutations: bool,
+    /// Mutations kept between two writes of the mutation log, the oldest
+    /// are dropped past it.
+    pub mutation_log_capacity: usize,
+    pub food_supply: FoodSupply,
+    /// Where the food is spawned.
+    pub food_distribution: FoodDistribution,
+    /// Spawn food following the V chemical of a Gray-Scott reaction-diffusion
+    /// model instead of uniformly, when given.
+    pub reaction_diffusion: Option<ReactionDiffusionConfig>,
+    /// Return the energy of dead organisms to the soil and spawn food where
+    /// the soil is richest, when given.
+    pub nutrient_cycling: Option<NutrientCyclingConfig>,
+    /// Pregnant organisms lay eggs that hatch later instead of giving birth,
+    /// when given.
+    pub eggs: Option<EggConfig>,
+    /// Zones that kill any organism entering them.
+    pub death_traps: Vec<TrapConfig>,
+    /// Rectangles inside the arena that block movement and sight.
+    pub obstacles: Vec<ObstacleConfig>,
+    /// Where the founders are placed at the start of a run.
+    pub founder_layout: FounderLayout,
+    /// Organisms get pregnant by touching another fertile organism and have
+    /// children with a crossover of both genes, instead of by eating.
+    pub sexual_reproduction: bool,
+    /// Split the arena in two halves with mirrored food and founders.
+    pub mirror_arena: bool,
+    /// Whether the arena has walls or wraps around.
+    pub world_topology: WorldTopology,
+    /// Energy an organism loses every tick for every organism of a different
+    /// color touching it, see `signal::VisualSignal`. No competition when 0.
+    pub competition_cost: f32,
+    /// Fraction of `competition_cost` paid for touching organisms of about
+    /// the same color.
+    pub conspecific_competition: f32,
+    /// Fraction of their food gain organisms lose when fully overlapping
+    /// others following the same strategy, see
+    /// `behavior::BehaviorArchetype`. No competition when 0.
+    pub competition_strength: f32,
+    /// Energy an organism following a strategy nobody else follows gets on
+    /// top of every food item it eats, less the more common its strategy.
+    /// No advantage when 0.
+    pub rare_advantage: f32,
+    /// Organisms within half the founders' vision of each other pool the
+    /// food they sense in front, see `swarm::SharedSensory`.
+    pub swarm_sensing: bool,
+    /// Diet from which an organism eats the smaller, less carnivorous
+    /// organisms it touches.
+    pub carnivore_threshold: f32,
+    /// Fraction of the energy of its prey a carnivore gains.
+    pub predation_efficiency: f32,
+    /// Energy a pure carnivore burns every tick on top of what herbivores
+    /// do, scaled by the diet of the organism.
+    pub carnivore_metabolism: f32,
+    /// Number of all-time best organisms kept in the elite archive.
+    pub elite_archive_capacity: usize,
+    /// Number of organisms that ate the most over their lives kept in the
+    /// hall of fame.
+    pub hall_of_fame_capacity: usize,
+    pub census: CensusConfig,
+    pub genome_snapshots: GenomeSnapshotConfig,
+    pub organism_log: OrganismLogConfig,
+    pub saves: SaveConfig,
+
+    pub play_sounds: bool,
+}
+
+/// Where and how often a row for every organism is appended to the organism
+/// log.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OrganismLogConfig {
+    pub path: PathBuf,
+    /// Fixed ticks between two rows of the same organism.
+    pub interval: u64,
+}
+
+impl Default for OrganismLogConfig {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from("organisms.csv"),
+            interval: 600,
+        }
+    }
+}
+
+/// Where the simulation is saved to, on F5 and every `autosave_interval`
+/// ticks when given.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SaveConfig {
+    pub path: PathBuf,
+    pub autosave_interval: Option<u64>,
+}
+
+impl Default for SaveConfig {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from("save.json"),
+            autosave_interval: None,
+        }
+    }
+}
+
+/// How often the population's genes are written to genome_snapshots.bin.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GenomeSnapshotConfig {
+    /// Fixed ticks between two snapshots, no snapshots are taken when not
+    /// given.
+    pub interval: Option<u64>,
+    /// Snapshots after which no more are written, to bound the file size.
+    pub max_snapshots: usize,
+}
+
+impl Default for GenomeSnapshotConfig {
+    fn default() -> Self {
+        Self {
+            interval: None,
+            max_snapshots: 100,
+        }
+    }
+}
+
+/// How often the census in census.csv is taken and the bounds that the
+/// counted quantities are expected to stay under.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CensusConfig {
+    /// Log ticks between two censuses.
+    pub interval: usize,
+    /// Bound on the number of entities of every kind.
+    pub max_entities: usize,
+    /// Bound on the number of meshes and of materials.
+    pub max_assets: usize,
+    /// Bound on the length of resources that keep a history.
+    pub max_resource_len: usize,
+    /// Consecutive growing censuses past a bound that count as a leak.
+    pub growth_samples: usize,
+    /// Pause the simulation when a leak is found.
+    pub pause_on_leak: bool,
+}
+
+impl Default for CensusConfig {
+    fn default() -> Self {
+        Self {
+            interval: 1,
+            max_entities: 20000,
+            max_assets: 20000,
+            max_resource_len: 1000,
+            growth_samples: 5,
+            pause_on_leak: false,
+        }
+    }
+}
+
+/// Mutation rates of the gene groups, the ones left out use `mutation_rate`.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GroupMutationRates {
+    pub bias: Option<f32>,
+    pub food: Option<f32>,
+    pub wall: Option<f32>,
+    pub state: Option<f32>,
+    pub neighbor: Option<f32>,
+    pub traits: Option<f32>,
+}
+
+/// How many food items are spawned on every food tick.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case", deny_unknown_fields)]
+pub enum FoodSupply {
+    /// Always spawn `food_per_timestep` items.
+    #[default]
+    Fixed,
+    /// Adjust the spawn rate with a PI controller every age tick so that the
+    /// number of food items per organism tracks `target_ratio`.
+    Controller {
+        target_ratio: f32,
+        kp: f32,
+        ki: f32,
+        min_food_per_timestep: usize,
+        max_food_per_timestep: usize,
+    },
+}
+
+/// Where the food is spawned on every food tick.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case", deny_unknown_fields)]
+pub enum FoodDistribution {
+    /// Uniformly at random over the arena.
+    #[default]
+    Uniform,
+    /// Normally distributed with standard deviation `patch_radius` around
+    /// `patch_count` centers, see `patches::FoodPatches`, that drift
+    /// `drift_speed` every tick.
+    Patchy {
+        patch_count: usize,
+        patch_radius: f32,
+        drift_speed: f32,
+    },
+}
+
+/// Parameters of the Gray-Scott model in `RDGrid`, per grid cell and fixed
+/// tick. The defaults make spots.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReactionDiffusionConfig {
+    /// Rate U is fed at, F.
+    pub feed: f32,
+    /// Rate V is removed at, k.
+    pub kill: f32,
+    pub diffusion_u: f32,
+    pub diffusion_v: f32,
+}
+
+impl Default for ReactionDiffusionConfig {
+    fn default() -> Self {
+        Self {
+            feed: 0.035,
+            kill: 0.065,
+            diffusion_u: 0.16,
+            diffusion_v: 0.08,
+        }
+    }
+}
+
+/// How organisms lay their eggs.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EggConfig {
+    /// Age ticks an egg incubates before hatching.
+    pub incubation: usize,
+    /// Fraction of its energy a parent divides among its eggs.
+    pub energy_fraction: f32,
+    /// Least energy an egg is laid with. A parent that can't afford
+    /// `children_per_pregnancy` eggs lays fewer.
+    pub min_energy: f32,
+}
+
+impl Default for EggConfig {
+    fn default() -> Self {
+        Self {
+            incubation: 10,
+            energy_fraction: 0.5,
+            min_energy: 0.5,
+        }
+    }
+}
+
+/// Parameters of the `SoilNutrient` grid, per grid cell and fixed tick.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NutrientCyclingConfig {
+    /// Fraction of its nutrients a cell passes to each of its neighbors.
+    pub diffusion: f32,
+    /// Weight every cell has in spawning food on top of its nutrients, so
+    /// that food still grows on bare soil.
+    pub background: f32,
+}
+
+impl Default for NutrientCyclingConfig {
+    fn default() -> Self {
+        Self {
+            diffusion: 0.05,
+            background: 0.001,
+        }
+    }
+}
+
+/// A `trap::DeathTrap` of `radius` around `position`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TrapConfig {
+    pub position: [f32; 2],
+    pub radius: f32,
+}
+
+/// A rectangular obstacle of `size` around `center`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ObstacleConfig {
+    pub center: [f32; 2],
+    pub size: [f32; 2],
+}
+
+/// Where the founders are placed, in mirror mode within the left half of the
+/// arena and mirrored to the right one.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(tag = "layout", rename_all = "snake_case", deny_unknown_fields)]
+pub enum FounderLayout {
+    /// Uniformly at random.
+    #[default]
+    Uniform,
+    /// Normally distributed around the center with standard deviation
+    /// `sigma`.
+    CenterCluster { sigma: f32 },
+    /// Uniformly at random along `wall`.
+    Edge { wall: Wall },
+    /// On the points of an evenly spaced grid.
+    Grid,
+    /// At the given positions, cycling through them when there are fewer
+    /// positions than founders.
+    Custom { positions: Vec<[f32; 2]> },
+}
+
+/// How organisms change direction.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MovementMode {
+    /// Turning by the turn output of the network on every sensory tick.
+    #[default]
+    Steering,
+    /// Running straight for the run length of the organism, then tumbling to
+    /// a random direction, unless food got closer during the run. The turn
+    /// output of the network is not used.
+    RunAndTumble,
+}
+
+/// What is at the edges of the arena.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WorldTopology {
+    /// Walls that organisms bounce off.
+    #[default]
+    Bounded,
+    /// No walls, what leaves through an edge comes back through the opposite
+    /// one.
+    Torus,
+}
+
+/// One of the walls of the arena.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Wall {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        let organism_default_lifetime = 100;
+        Self {
+            seed: None,
+            simulation_speed: 5.0,
+            top_boundary: 300.0,
+            bottom_boundary: -300.0,
+            right_boundary: 600.0,
+            left_boundary: -600.0,
+            boundary_thickness: 4.0,
+
+            organism_default_speed: 8.0,
+            organism_vision: 100.0,
+            vision_range_energy_cost: 0.000001,
+            vision_angle_energy_cost: 0.00001,
+            luminosity_energy_cost: 0.0002,
+            mouth_arc: 360.0,
+            max_speed_energy_coefficient: 1.0,
+            steering_smoothing: 0.0,
+            movement: MovementMode::Steering,
+            initial_population: 50,
+            food_per_timestep: 2,
+            pregnant_probability: 0.5,
+            selection_amplification: 1.0,
+            children_per_pregnancy: 10,
+            gestation_ticks: 0,
+
+            pregnancy_energy_minimum: 2.0,
+            organism_min_energy: 0.2,
+            organism_max_energy: 4.0,
+            organism_default_lifetime,
+            pheromone_cell_size: 10.0,
+            pheromone_decay: 0.997,
+            pheromone_diffusion: 0.0,
+            pheromone_threshold: 0.2,
+            pheromone_energy_cost: 0.002,
+            fertile_age: organism_default_lifetime / 4,
+            food_lifetime: 100,
+            poison_probability: 0.0,
+            corpse_energy_fraction: 0.5,
+            corpse_lifetime: 50,
+            mutation_rate: 0.2,
+            mutation_rates: GroupMutationRates::default(),
+            log_mutations: false,
+            mutation_log_capacity: 100_000,
+            food_supply: FoodSupply::Fixed,
+            food_distribution: FoodDistribution::Uniform,
+            reaction_diffusion: None,
+            nutrient_cycling: None,
+            eggs: None,
+            death_traps: Vec::new(),
+            obstacles: Vec::new(),
+            founder_layout: FounderLayout::Uniform,
+            sexual_reproduction: false,
+            mirror_arena: false,
+            world_topology: WorldTopology::Bounded,
+            competition_cost: 0.0,
+            conspecific_competition: 0.25,
+            competition_strength: 0.0,
+            rare_advantage: 0.0,
+            swarm_sensing: false,
+            carnivore_threshold: 0.5,
+            predation_efficiency: 0.5,
+            carnivore_metabolism: 0.001,
+            elite_archive_capacity: 20,
+            hall_of_fame_capacity: 20,
+            census: CensusConfig::default(),
+            genome_snapshots: GenomeSnapshotConfig::default(),
+            organism_log: OrganismLogConfig::default(),
+            saves: SaveConfig::default(),
+
+            play_sounds: false,
+        }
+    }
+}
+
+impl SimulationConfig {
+    /// The mutation rate of every gene group, with overrides applied.
+    pub fn mutation_rates(&self) -> MutationRates {
+        let rates = &self.mutation_rates;
+        let rate = self.mutation_rate;
+        MutationRates {
+            bias: rates.bias.unwrap_or(rate),
+            food: rates.food.unwrap_or(rate),
+            wall: rates.wall.unwrap_or(rate),
+            state: rates.state.unwrap_or(rate),
+            neighbor: rates.neighbor.unwrap_or(rate),
+            traits: rates.traits.unwrap_or(rate),
+        }
+    }
+
+    /// Reads and validates the config file at `path`.
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("could not read config file {path}: {e}"))?;
+        let config: Self =
+            toml::from_str(&text).map_err(|e| format!("invalid config file {path}: {e}"))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Checks that every field is within a range the simulation can work with.
+    pub fn validate(&self) -> Result<(), String> {
+        fn check(ok: bool, msg: &str) -> Result<(), String> {
+            if ok {
+                Ok(())
+            } else {
+                Err(msg.to_string())
+            }
+        }
+        check(
+            self.simulation_speed > 0.0,
+            "simulation_speed must be positive",
+        )?;
+        check(
+            self.top_boundary > self.bottom_boundary,
+            "top_boundary must be greater than bottom_boundary",
+        )?;
+        check(
+            self.right_boundary > self.left_boundary,
+            "right_boundary must be greater than left_boundary",
+        )?;
+        check(
+            self.boundary_thickness >= 0.0,
+            "boundary_thickness can not be negative",
+        )?;
+        check(
+            self.organism_default_speed > 0.0,
+            "organism_default_speed must be positive",
+        )?;
+        check(
+            self.organism_vision > 0.0,
+            "organism_vision must be positive",
+        )?;
+        check(
+            self.vision_range_energy_cost >= 0.0,
+            "vision_range_energy_cost can not be negative",
+        )?;
+        check(
+            self.vision_angle_energy_cost >= 0.0,
+            "vision_angle_energy_cost can not be negative",
+        )?;
+        check(
+            self.luminosity_energy_cost >= 0.0,
+            "luminosity_energy_cost can not be negative",
+        )?;
+        check(
+            self.mouth_arc > 0.0 && self.mouth_arc <= 360.0,
+            "mouth_arc must be more than 0 and at most 360 degrees",
+        )?;
+        check(
+            self.max_speed_energy_coefficient >= 0.0,
+            "max_speed_energy_coefficient can not be negative",
+        )?;
+        check(
+            (0.0..1.0).contains(&self.steering_smoothing),
+            "steering_smoothing must be at least 0 and less than 1",
+        )?;
+        check(
+            (0.0..=1.0).contains(&self.pregnant_probability),
+            "pregnant_probability must be between 0 and 1",
+        )?;
+        check(
+            self.selection_amplification >= 1.0,
+            "selection_amplification must be at least 1",
+        )?;
+        check(
+            (0.0..=1.0).contains(&self.mutation_rate),
+            "mutation_rate must be between 0 and 1",
+        )?;
+        let rates = self.mutation_rates();
+        check(
+            [
+                rates.bias,
+                rates.food,
+                rates.wall,
+                rates.state,
+                rates.neighbor,
+                rates.traits,
+            ]
+            .iter()
+            .all(|r| (0.0..=1.0).contains(r)),
+            "mutation_rates must be between 0 and 1",
+        )?;
+        check(
+            self.organism_min_energy >= 0.0,
+            "organism_min_energy can not be negative",
+        )?;
+        check(
+            self.organism_max_energy > self.organism_min_energy,
+            "organism_max_energy must be greater than organism_min_energy",
+        )?;
+        check(
+            self.organism_default_lifetime > 0,
+            "organism_default_lifetime must be positive",
+        )?;
+        check(
+            self.fertile_age <= self.organism_default_lifetime,
+            "fertile_age can not exceed organism_default_lifetime",
+        )?;
+        check(
+            self.food_per_timestep > 0,
+            "food_per_timestep must be positive",
+        )?;
+        check(
+            self.children_per_pregnancy > 0,
+            "children_per_pregnancy must be positive",
+        )?;
+        check(
+            self.mutation_log_capacity > 0,
+            "mutation_log_capacity must be positive",
+        )?;
+        check(
+            self.pheromone_cell_size > 0.0,
+            "pheromone_cell_size must be positive",
+        )?;
+        check(
+            (0.0..=1.0).contains(&self.pheromone_decay),
+            "pheromone_decay must be between 0 and 1",
+        )?;
+        check(
+            (0.0..=0.25).contains(&self.pheromone_diffusion),
+            "pheromone_diffusion must be between 0 and 0.25",
+        )?;
+        check(
+            (0.0..1.0).contains(&self.pheromone_threshold),
+            "pheromone_threshold must be at least 0 and less than 1",
+        )?;
+        check(
+            self.pheromone_energy_cost >= 0.0,
+            "pheromone_energy_cost can not be negative",
+        )?;
+        check(self.food_lifetime > 0, "food_lifetime must be positive")?;
+        check(
+            (0.0..=1.0).contains(&self.poison_probability),
+            "poison_probability must be between 0 and 1",
+        )?;
+        check(
+            (0.0..=1.0).contains(&self.corpse_energy_fraction),
+            "corpse_energy_fraction must be between 0 and 1",
+        )?;
+        check(self.corpse_lifetime > 0, "corpse_lifetime must be positive")?;
+        check(self.census.interval > 0, "census.interval must be positive")?;
+        check(
+            self.genome_snapshots.interval != Some(0),
+            "genome_snapshots.interval must be positive",
+        )?;
+        check(
+            self.census.max_entities > 0
+                && self.census.max_assets > 0
+                && self.census.max_resource_len > 0,
+            "census bounds must be positive",
+        )?;
+        check(
+            self.saves.autosave_interval != Some(0),
+            "saves.autosave_interval must be positive",
+        )?;
+        check(
+            self.organism_log.interval > 0,
+            "organism_log.interval must be positive",
+        )?;
+        check(
+            self.census.growth_samples > 1,
+            "census.growth_samples must be at least 2",
+        )?;
+        if let Some(rd) = &self.reaction_diffusion {
+            check(
+                rd.feed >= 0.0 && rd.kill >= 0.0,
+                "reaction_diffusion.feed and reaction_diffusion.kill can not be negative",
+            )?;
+            // the explicit update blows up past a quarter
+            check(
+                rd.diffusion_u > 0.0
+                    && rd.diffusion_u <= 0.25
+                    && rd.diffusion_v > 0.0
+                    && rd.diffusion_v <= 0.25,
+                "reaction_diffusion diffusion rates must be more than 0 and at most 0.25",
+            )?;
+        }
+        if let Some(eggs) = &self.eggs {
+            check(eggs.incubation > 0, "eggs.incubation must be positive")?;
+            check(
+                eggs.energy_fraction > 0.0 && eggs.energy_fraction < 1.0,
+                "eggs.energy_fraction must be between 0 and 1",
+            )?;
+            check(
+                eggs.min_energy > self.organism_min_energy,
+                "eggs.min_energy must be greater than organism_min_energy",
+            )?;
+        }
+        if let Some(cycling) = &self.nutrient_cycling {
+            check(
+                cycling.diffusion >= 0.0 && cycling.diffusion <= 0.25,
+                "nutrient_cycling.diffusion must be between 0 and 0.25",
+            )?;
+            check(
+                cycling.background > 0.0,
+                "nutrient_cycling.background must be positive",
+            )?;
+            check(
+                self.reaction_diffusion.is_none(),
+                "nutrient_cycling and reaction_diffusion can not be used together",
+            )?;
+        }
+        check(
+            self.competition_cost >= 0.0,
+            "competition_cost can not be negative",
+        )?;
+        check(
+            (0.0..=1.0).contains(&self.conspecific_competition),
+            "conspecific_competition must be between 0 and 1",
+        )?;
+        check(
+            (0.0..=1.0).contains(&self.competition_strength),
+            "competition_strength must be between 0 and 1",
+        )?;
+        check(
+            (0.0..=1.0).contains(&self.rare_advantage),
+            "rare_advantage must be between 0 and 1",
+        )?;
+        check(
+            (0.0..=1.0).contains(&self.carnivore_threshold),
+            "carnivore_threshold must be between 0 and 1",
+        )?;
+        check(
+            (0.0..=1.0).contains(&self.predation_efficiency),
+            "predation_efficiency must be between 0 and 1",
+        )?;
+        check(
+            self.carnivore_metabolism >= 0.0,
+            "carnivore_metabolism can not be negative",
+        )?;
+        check(
+            !(self.mirror_arena && self.world_topology == WorldTopology::Torus),
+            "mirror_arena needs a bounded world_topology",
+        )?;
+        check(
+            self.death_traps.iter().all(|trap| trap.radius > 0.0),
+            "death_traps radius must be positive",
+        )?;
+        check(
+            self.death_traps.iter().all(|trap| {
+                let [x, y] = trap.position;
+                (self.left_boundary..=self.right_boundary).contains(&x)
+                    && (self.bottom_boundary..=self.top_boundary).contains(&y)
+            }),
+            "death_traps positions must be inside the arena",
+        )?;
+        check(
+            self.obstacles
+                .iter()
+                .all(|obstacle| obstacle.size[0] > 0.0 && obstacle.size[1] > 0.0),
+            "obstacles size must be positive",
+        )?;
+        check(
+            self.obstacles.iter().all(|obstacle| {
+                let [x, y] = obstacle.center;
+                (self.left_boundary..=self.right_boundary).contains(&x)
+                    && (self.bottom_boundary..=self.top_boundary).contains(&y)
+            }),
+            "obstacles centers must be inside the arena",
+        )?;
+        check(
+            !self.mirror_arena || self.obstacles.is_empty(),
+            "obstacles can not be used with mirror_arena",
+        )?;
+        match &self.founder_layout {
+            FounderLayout::CenterCluster { sigma } => {
+                check(*sigma > 0.0, "founder_layout.sigma must be positive")?
+            }
+            FounderLayout::Custom { positions } => {
+                check(
+                    !positions.is_empty(),
+                    "founder_layout.positions can not be empty",
+                )?;
+                check(
+                    positions.iter().all(|&[x, y]| {
+                        (self.left_boundary..=self.right_boundary).contains(&x)
+                            && (self.bottom_boundary..=self.top_boundary).contains(&y)
+                    }),
+                    "founder_layout.positions must be inside the arena",
+                )?;
+            }
+            _ => {}
+        }
+        if let FoodDistribution::Patchy {
+            patch_count,
+            patch_radius,
+            drift_speed,
+        } = self.food_distribution
+        {
+            check(
+                patch_count > 0,
+                "food_distribution.patch_count must be positive",
+            )?;
+            check(
+                patch_radius > 0.0,
+                "food_distribution.patch_radius must be positive",
+            )?;
+            check(
+                drift_speed >= 0.0,
+                "food_distribution.drift_speed can not be negative",
+            )?;
+            check(
+                self.reaction_diffusion.is_none() && self.nutrient_cycling.is_none(),
+                "patchy food can not be used with reaction_diffusion or nutrient_cycling",
+            )?;
+        }
+        if let FoodSupply::Controller {
+            target_ratio,
+            kp,
+            ki,
+            min_food_per_timestep,
+            max_food_per_timestep,
+        } = self.food_supply
+        {
+            check(
+                target_ratio > 0.0,
+                "food_supply.target_ratio must be positive",
+            )?;
+            check(
+                kp >= 0.0 && ki >= 0.0,
+                "food_supply.kp and food_supply.ki can not be negative",
+            )?;
+            check(
+                min_food_per_timestep <= max_food_per_timestep,
+                "food_supply.min_food_per_timestep can not exceed max_food_per_timestep",
+            )?;
+            check(
+                (min_food_per_timestep..=max_food_per_timestep).contains(&self.food_per_timestep),
+                "food_per_timestep must be between food_supply.min_food_per_timestep and max_food_per_timestep",
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_config_is_valid() {
+        assert_eq!(SimulationConfig::default().validate(), Ok(()));
+    }
+
+    #[test]
+    fn fertile_age_past_lifetime_is_rejected() {
+        let config = SimulationConfig {
+            fertile_age: 101,
+            organism_default_lifetime: 100,
+            ..SimulationConfig::default()
+        };
+        assert!(config.validate().unwrap_err().contains("fertile_age"));
+    }
+
+    #[test]
+    fn empty_spawns_are_rejected() {
+        let config = SimulationConfig {
+            food_per_timestep: 0,
+            ..SimulationConfig::default()
+        };
+        assert!(config.validate().unwrap_err().contains("food_per_timestep"));
+        let config = SimulationConfig {
+            children_per_pregnancy: 0,
+            ..SimulationConfig::default()
+        };
+        assert!(config
+            .validate()
+            .unwrap_err()
+            .contains("children_per_pregnancy"));
+        let config = SimulationConfig {
+            mutation_log_capacity: 0,
+            ..SimulationConfig::default()
+        };
+        assert!(config
+            .validate()
+            .unwrap_err()
+            .contains("mutation_log_capacity"));
+    }
+}
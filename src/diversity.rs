@@ -0,0 +1,125 @@
+//! Genetic diversity of the population, treating every distinct quantized
+//! genotype as a species.
+
+use std::collections::{HashMap, VecDeque};
+use std::io::Write;
+
+use bevy::prelude::*;
+
+use crate::genes::GeneInfo;
+use crate::organism::Organism;
+use crate::plugin::LogTimer;
+use crate::SimTick;
+
+/// Genes closer than this end up in the same genotype.
+const QUANTIZATION: f32 = 0.5;
+/// Shannon index under which the population is considered too uniform.
+pub const LOW_DIVERSITY: f32 = 1.0;
+/// Log ticks of Shannon index kept for the HUD sparkline.
+const HISTORY_LENGTH: usize = 30;
+
+/// Diversity indices as of the last log tick.
+#[derive(Resource, Default)]
+pub struct DiversityMetrics {
+    /// Simpson's index of diversity, the probability that two organisms
+    /// picked at random have different genotypes.
+    pub simpsons_d: f32,
+    /// Shannon index of the genotype frequencies.
+    pub shannon_h: f32,
+    /// Number of distinct genotypes.
+    pub genotypes: usize,
+    /// Shannon index of the last log ticks, oldest first.
+    pub history: VecDeque<f32>,
+}
+
+/// Sent on every log tick the Shannon index is under [`LOW_DIVERSITY`].
+pub struct DiversityAlert {
+    pub shannon_h: f32,
+}
+
+fn genotype(gene: &GeneInfo) -> Vec<i8> {
+    gene.0
+        .iter()
+        .map(|g| (g / QUANTIZATION).round() as i8)
+        .collect()
+}
+
+fn genotype_counts<'a>(genes: impl Iterator<Item = &'a GeneInfo>) -> HashMap<Vec<i8>, usize> {
+    let mut counts: HashMap<Vec<i8>, usize> = HashMap::new();
+    for gene in genes {
+        *counts.entry(genotype(gene)).or_default() += 1;
+    }
+    counts
+}
+
+/// Simpson's index of diversity and Shannon index of the genotype counts.
+fn indices(counts: &HashMap<Vec<i8>, usize>) -> (f32, f32) {
+    let total = counts.values().sum::<usize>() as f32;
+    if total <= 1.0 {
+        return (0.0, 0.0);
+    }
+    let same = counts
+        .values()
+        .map(|&n| n as f32 * (n as f32 - 1.0))
+        .sum::<f32>()
+        / (total * (total - 1.0));
+    let shannon = counts
+        .values()
+        .map(|&n| {
+            let p = n as f32 / total;
+            p * p.recip().ln()
+        })
+        .sum::<f32>();
+    (1.0 - same, shannon)
+}
+
+/// Shannon index of the genotypes of `genes`, what [`DiversityMetrics`]
+/// keeps as of the last log tick.
+pub fn shannon_index<'a>(genes: impl Iterator<Item = &'a GeneInfo>) -> f32 {
+    indices(&genotype_counts(genes)).1
+}
+
+pub(crate) fn update_diversity(
+    tick: Res<SimTick>,
+    timer: Res<LogTimer>,
+    mut metrics: ResMut<DiversityMetrics>,
+    mut alerts: EventWriter<DiversityAlert>,
+    mut log: Local<Option<std::io::BufWriter<std::fs::File>>>,
+    query: Query<&GeneInfo, With<Organism>>,
+) {
+    if !timer.0.just_finished() {
+        return;
+    }
+    let counts = genotype_counts(query.iter());
+    let (simpsons_d, shannon_h) = indices(&counts);
+
+    let was_low = metrics.shannon_h < LOW_DIVERSITY;
+    metrics.simpsons_d = simpsons_d;
+    metrics.shannon_h = shannon_h;
+    metrics.genotypes = counts.len();
+    metrics.history.push_back(shannon_h);
+    if metrics.history.len() > HISTORY_LENGTH {
+        metrics.history.pop_front();
+    }
+    if shannon_h < LOW_DIVERSITY {
+        // the founders share one gene, so only warn when diversity is lost
+        if !was_low {
+            warn!("low genetic diversity, Shannon index {shannon_h:.3}");
+        }
+        alerts.send(DiversityAlert { shannon_h });
+    }
+
+    let log = log.get_or_insert_with(|| {
+        let file = std::fs::File::create("diversity.csv").unwrap();
+        let mut file = std::io::BufWriter::new(file);
+        file.write_all(b"tick,simpsons_d,shannon_h,genotypes\n")
+            .unwrap();
+        file
+    });
+    writeln!(
+        log,
+        "{},{},{},{}",
+        tick.0, simpsons_d, shannon_h, metrics.genotypes
+    )
+    .unwrap();
+}
@@ -0,0 +1,104 @@
+//! How much energy, standing in for carbon, the organisms put through the
+//! ecosystem: what they absorbed from food and prey less what they wasted
+//! on offspring that died young, per age tick of their lives. Averaged by
+//! generation, it tells whether the population evolves towards processing
+//! energy more efficiently.
+
+use std::collections::BTreeSet;
+use std::io::Write;
+
+use bevy::prelude::*;
+
+use crate::config::SimulationConfig;
+use crate::genes::Traits;
+use crate::organism::DeathEvent;
+use crate::plugin::LogTimer;
+use crate::SimTick;
+
+#[derive(Resource, Default)]
+pub struct EcosystemServiceMetric {
+    /// Organisms that died before reaching their fertile age.
+    pub juvenile_deaths: u64,
+    /// Organisms that died after reaching their fertile age.
+    pub adult_deaths: u64,
+    /// Sum of the ecosystem service rates and number of deaths, by
+    /// generation.
+    pub generations: Vec<(f32, usize)>,
+}
+
+impl EcosystemServiceMetric {
+    /// Fraction of the organisms that lived to their fertile age, 1 until
+    /// any died.
+    pub fn offspring_survival(&self) -> f32 {
+        let deaths = self.juvenile_deaths + self.adult_deaths;
+        if deaths == 0 {
+            return 1.0;
+        }
+        self.adult_deaths as f32 / deaths as f32
+    }
+
+    /// Mean ecosystem service rate of the dead of `generation`, founders
+    /// being generation 0.
+    pub fn mean(&self, generation: usize) -> Option<f32> {
+        self.generations
+            .get(generation)
+            .filter(|(_, deaths)| *deaths > 0)
+            .map(|(sum, deaths)| sum / *deaths as f32)
+    }
+
+    /// The latest generation any organism died in.
+    pub fn last_generation(&self) -> Option<usize> {
+        self.generations.iter().rposition(|(_, deaths)| *deaths > 0)
+    }
+}
+
+/// Adds the dead to the metric, and appends the mean rate of every
+/// generation that had deaths since the last log tick to
+/// ecosystem_service.csv.
+pub(crate) fn update_ecosystem_service(
+    config: Res<SimulationConfig>,
+    tick: Res<SimTick>,
+    timer: Res<LogTimer>,
+    mut metric: ResMut<EcosystemServiceMetric>,
+    mut death_events: EventReader<DeathEvent>,
+    mut changed: Local<BTreeSet<usize>>,
+    mut log: Local<Option<std::io::BufWriter<std::fs::File>>>,
+) {
+    for event in death_events.iter() {
+        if event.age > Traits::from_gene(&event.gene).fertile_age(&config) {
+            metric.adult_deaths += 1;
+        } else {
+            metric.juvenile_deaths += 1;
+        }
+        if metric.generations.len() <= event.generation {
+            metric.generations.resize(event.generation + 1, (0.0, 0));
+        }
+        let (sum, deaths) = &mut metric.generations[event.generation];
+        *sum += event.ecosystem_service();
+        *deaths += 1;
+        changed.insert(event.generation);
+    }
+    if !timer.0.just_finished() || changed.is_empty() {
+        return;
+    }
+
+    let log = log.get_or_insert_with(|| {
+        let file = std::fs::File::create("ecosystem_service.csv").unwrap();
+        let mut file = std::io::BufWriter::new(file);
+        file.write_all(b"tick,generation,deaths,mean_rate\n")
+            .unwrap();
+        file
+    });
+    for generation in std::mem::take(&mut *changed) {
+        let (sum, deaths) = metric.generations[generation];
+        writeln!(
+            log,
+            "{},{},{},{}",
+            tick.0,
+            generation,
+            deaths,
+            sum / deaths as f32
+        )
+        .unwrap();
+    }
+}
@@ -0,0 +1,82 @@
+//! Egg laying: with `eggs` set in the config, pregnant organisms lay eggs
+//! that hatch once they have incubated instead of giving birth on the spot.
+//! Until they hatch, eggs are food to every organism but their parent.
+
+use bevy::prelude::*;
+
+use crate::config::{EggConfig, SimulationConfig};
+use crate::food::Food;
+use crate::genealogy::{Genealogy, OrganismId};
+use crate::genes::{GeneInfo, Mutation};
+use crate::organism::{child_age, random_direction, Generation, MutationLog, OrganismBundle};
+use crate::stats::SimStats;
+use crate::{Age, Collider, Dead, DeathCause, Energy, Lifetime, SimRng, SimTick};
+
+pub const EGG_COLOR: Color = Color::rgb(0.9, 0.85, 0.7);
+pub const EGG_SIZE: Vec3 = Vec3::new(3.0, 3.0, 0.0);
+
+/// The organism an egg hatches into.
+#[derive(Component)]
+pub struct Egg {
+    pub parent: OrganismId,
+    pub generation: Generation,
+    pub gene: GeneInfo,
+    /// Mutations of `gene` from the parent's, kept when `log_mutations` is
+    /// on.
+    pub mutations: Vec<Mutation>,
+}
+
+/// A freshly laid egg at `position`, holding `energy` for the organism it
+/// hatches into.
+pub(crate) fn egg_bundle(eggs: &EggConfig, egg: Egg, position: Vec3, energy: f32) -> impl Bundle {
+    (
+        Transform::from_translation(position).with_scale(EGG_SIZE),
+        Food,
+        egg,
+        Age(1),
+        Lifetime(eggs.incubation),
+        Energy(energy),
+        Collider,
+    )
+}
+
+/// Hatches the eggs that outlived their incubation into organisms with
+/// their energy. Runs once the `Dead` markers of the tick are applied, so
+/// that eggs eaten on the tick they would hatch stay eaten.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn hatch_eggs(
+    mut commands: Commands,
+    config: Res<SimulationConfig>,
+    mut rng: ResMut<SimRng>,
+    tick: Res<SimTick>,
+    mut genealogy: ResMut<Genealogy>,
+    mut mutation_log: ResMut<MutationLog>,
+    mut stats: ResMut<SimStats>,
+    query: Query<(&Transform, &Energy, &Egg, &Dead)>,
+) {
+    for (transform, energy, egg, dead) in &query {
+        if dead.0 != DeathCause::Expired {
+            continue;
+        }
+        let birth = genealogy.birth(Some(egg.parent.0), tick.0);
+        let child_id = birth.0;
+        commands
+            .spawn((
+                OrganismBundle::new(
+                    &config,
+                    egg.gene.clone(),
+                    transform.translation,
+                    random_direction(&mut rng.0),
+                    energy.0,
+                    tick.0,
+                ),
+                birth,
+            ))
+            .insert((child_age(&mut rng.0), egg.generation));
+        if config.log_mutations {
+            mutation_log.mutations.extend(egg.mutations.iter().copied());
+            mutation_log.record(config.mutation_log_capacity, tick.0, egg.parent, child_id);
+        }
+        stats.births += 1;
+    }
+}
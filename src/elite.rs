@@ -0,0 +1,181 @@
+//! Archives of the best organisms seen over the whole run.
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+
+use bevy::prelude::*;
+use serde::Serialize;
+
+use crate::config::SimulationConfig;
+use crate::genealogy::Genealogy;
+use crate::genes::{GeneInfo, GENE_VERSION};
+use crate::obstacle::outside_obstacles;
+use crate::organism::{random_direction, DeathEvent, OrganismBundle};
+use crate::{random_position, SimRng, SimTick};
+
+const ELITE_ARCHIVE_FILE: &str = "elite_archive.json";
+
+/// A dead organism's gene and how well it did, see [`EliteArchive`] and
+/// [`HallOfFame`] for how they score it.
+#[derive(Clone, Debug, Serialize)]
+pub struct EliteEntry {
+    pub score: f32,
+    pub gene: GeneInfo,
+}
+
+impl PartialEq for EliteEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for EliteEntry {}
+
+impl PartialOrd for EliteEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for EliteEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.score.total_cmp(&other.score)
+    }
+}
+
+/// The top `capacity` organisms of all time, scored by their
+/// `SurvivabilityScore` at death. It is never cleared while the simulation
+/// runs.
+#[derive(Resource)]
+pub struct EliteArchive {
+    /// Min-heap so the weakest entry is the one that gets replaced.
+    pub organisms: BinaryHeap<Reverse<EliteEntry>>,
+    pub capacity: usize,
+}
+
+impl EliteArchive {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            organisms: BinaryHeap::with_capacity(capacity + 1),
+            capacity,
+        }
+    }
+
+    /// Adds the gene to the archive, pushing out the weakest entry once the
+    /// archive is full.
+    pub fn offer(&mut self, score: f32, gene: &GeneInfo) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.organisms.len() >= self.capacity {
+            match self.organisms.peek() {
+                Some(Reverse(lowest)) if lowest.score < score => {
+                    self.organisms.pop();
+                }
+                _ => return,
+            }
+        }
+        self.organisms.push(Reverse(EliteEntry {
+            score,
+            gene: gene.clone(),
+        }));
+    }
+
+    /// Entries ordered from the best to the worst.
+    pub fn sorted(&self) -> Vec<&EliteEntry> {
+        let mut entries: Vec<&EliteEntry> = self.organisms.iter().map(|e| &e.0).collect();
+        entries.sort_by(|a, b| b.cmp(a));
+        entries
+    }
+}
+
+/// The organisms that ate the most food over their lives, scored by the
+/// number of items eaten, to reseed a population with genes that are proven
+/// to find food.
+#[derive(Resource)]
+pub struct HallOfFame(pub EliteArchive);
+
+pub(crate) fn update_elite_archive(
+    mut archive: ResMut<EliteArchive>,
+    mut death_events: EventReader<DeathEvent>,
+) {
+    for event in death_events.iter() {
+        archive.offer(event.survivability, &event.gene);
+    }
+}
+
+pub(crate) fn update_hall_of_fame(
+    mut hall: ResMut<HallOfFame>,
+    mut death_events: EventReader<DeathEvent>,
+) {
+    for event in death_events.iter() {
+        hall.0.offer(event.food_eaten as f32, &event.gene);
+    }
+}
+
+/// H spawns an organism with a gene from the hall of fame, going down the
+/// ranking with every press.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn respawn_from_hall_of_fame(
+    mut commands: Commands,
+    keys: Res<Input<KeyCode>>,
+    config: Res<SimulationConfig>,
+    hall: Res<HallOfFame>,
+    tick: Res<SimTick>,
+    mut rng: ResMut<SimRng>,
+    mut genealogy: ResMut<Genealogy>,
+    mut next: Local<usize>,
+) {
+    if !keys.just_pressed(KeyCode::H) {
+        return;
+    }
+    let entries = hall.0.sorted();
+    if entries.is_empty() {
+        info!("the hall of fame is still empty");
+        return;
+    }
+    let entry = entries[*next % entries.len()];
+    *next += 1;
+    let position = outside_obstacles(&config, || random_position(&config, &mut rng.0));
+    let direction = random_direction(&mut rng.0);
+    // the respawned organism founds a lineage of its own
+    commands.spawn((
+        OrganismBundle::new(
+            &config,
+            entry.gene.clone(),
+            position,
+            direction,
+            1.0,
+            tick.0,
+        ),
+        genealogy.birth(None, tick.0),
+    ));
+    info!(
+        "respawned an organism whose gene ate {} food items",
+        entry.score
+    );
+}
+
+pub(crate) fn dump_elite_archive(keys: Res<Input<KeyCode>>, archive: Res<EliteArchive>) {
+    let ctrl = keys.any_pressed([KeyCode::LControl, KeyCode::RControl]);
+    if !(ctrl && keys.just_pressed(KeyCode::A)) {
+        return;
+    }
+    let dump = serde_json::json!({
+        "gene_version": GENE_VERSION,
+        "organisms": archive.sorted(),
+    });
+    let result = std::fs::File::create(ELITE_ARCHIVE_FILE)
+        .map_err(|e| e.to_string())
+        .and_then(|file| {
+            serde_json::to_writer_pretty(std::io::BufWriter::new(file), &dump)
+                .map_err(|e| e.to_string())
+        });
+    match result {
+        Ok(()) => info!(
+            "saved {} elite organisms to {ELITE_ARCHIVE_FILE}",
+            archive.organisms.len()
+        ),
+        Err(e) => error!("could not save {ELITE_ARCHIVE_FILE}: {e}"),
+    }
+}
@@ -0,0 +1,91 @@
+//! Energy bars: a bar over every organism filled by how far its energy is
+//! from the starvation limit to the overflow one, turning from green to red
+//! as it nears either. Toggled with B, since every bar is one more draw.
+
+use bevy::prelude::*;
+
+use crate::config::SimulationConfig;
+use crate::organism::Organism;
+use crate::Energy;
+
+/// Height of a bar and its gap above the organism, relative to the
+/// organism's diameter.
+const BAR_HEIGHT: f32 = 0.15;
+const BAR_GAP: f32 = 0.1;
+
+/// Bar drawn as a child of the organism, so that it moves and grows with it.
+#[derive(Component)]
+pub struct EnergyBar;
+
+/// Whether the energy bars are drawn.
+#[derive(Resource, Default)]
+pub struct EnergyBarsShown(pub bool);
+
+/// Red at either energy limit, through yellow to green halfway between them.
+fn bar_color(fill: f32) -> Color {
+    let safety = 2.0 * fill.min(1.0 - fill);
+    Color::rgb((2.0 - 2.0 * safety).min(1.0), (2.0 * safety).min(1.0), 0.0)
+}
+
+pub(crate) fn add_energy_bars(
+    mut commands: Commands,
+    shown: Res<EnergyBarsShown>,
+    organism_query: Query<Entity, Added<Organism>>,
+) {
+    let visibility = if shown.0 {
+        Visibility::Inherited
+    } else {
+        Visibility::Hidden
+    };
+    for organism in &organism_query {
+        let bar = commands
+            .spawn((
+                EnergyBar,
+                SpriteBundle {
+                    sprite: Sprite {
+                        custom_size: Some(Vec2::ONE),
+                        ..default()
+                    },
+                    visibility,
+                    ..default()
+                },
+            ))
+            .id();
+        commands.entity(organism).add_child(bar);
+    }
+}
+
+pub(crate) fn update_energy_bars(
+    keys: Res<Input<KeyCode>>,
+    config: Res<SimulationConfig>,
+    mut shown: ResMut<EnergyBarsShown>,
+    mut bar_query: Query<(&Parent, &mut Transform, &mut Sprite, &mut Visibility), With<EnergyBar>>,
+    organism_query: Query<&Energy, With<Organism>>,
+) {
+    let toggled = keys.just_pressed(KeyCode::B);
+    if toggled {
+        shown.0 = !shown.0;
+    }
+    if !shown.0 && !toggled {
+        return;
+    }
+    for (parent, mut transform, mut sprite, mut visibility) in &mut bar_query {
+        if !shown.0 {
+            *visibility = Visibility::Hidden;
+            continue;
+        }
+        *visibility = Visibility::Inherited;
+        let Ok(energy) = organism_query.get(parent.get()) else {
+            continue;
+        };
+        let fill = ((energy.0 - config.organism_min_energy)
+            / (config.organism_max_energy - config.organism_min_energy))
+            .clamp(0.0, 1.0);
+        // in the organism's frame, where its circle is 1 across, filled
+        // from the left
+        transform.translation =
+            Vec3::new((fill - 1.0) / 2.0, 0.5 + BAR_GAP + BAR_HEIGHT / 2.0, 0.0);
+        transform.scale = Vec3::new(fill, BAR_HEIGHT, 1.0);
+        sprite.color = bar_color(fill);
+    }
+}
@@ -0,0 +1,490 @@
+//! Food spawning, and the controller that can regulate its supply.
+
+use std::io::Write;
+
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::config::{FoodDistribution, FoodSupply, SimulationConfig};
+use crate::egg::Egg;
+use crate::mirror::{left_half, mirror_position};
+use crate::nutrient::SoilNutrient;
+use crate::obstacle::outside_obstacles;
+use crate::organism::{InteractionRadius, Organism, FOOD_ENERGY};
+use crate::patches::FoodPatches;
+use crate::plugin::{AgeTimer, CircleAssets, LogTimer};
+use crate::reaction_diffusion::RDGrid;
+use crate::{random_position, Age, Collider, Dead, DeathCause, Energy, Lifetime, SimRng, SimTick};
+
+pub const FOOD_COLOR: Color = Color::rgb(0.1, 0.4, 0.1);
+pub const FOOD_SIZE: Vec3 = Vec3::new(4.0, 4.0, 0.0);
+pub const CORPSE_COLOR: Color = Color::rgb(0.45, 0.3, 0.15);
+pub const POISON_COLOR: Color = Color::rgb(0.4, 0.45, 0.3);
+/// Energy taken from an organism eating a fresh poisonous food item.
+pub const POISON_ENERGY: f32 = -FOOD_ENERGY;
+/// Most food items a corpse is split into.
+const MAX_CORPSE_PIECES: usize = 4;
+
+#[derive(Component)]
+pub struct Food;
+
+/// Food left behind by a dead organism, with the energy it had when it was
+/// dropped.
+#[derive(Component)]
+pub struct Corpse(pub f32);
+
+/// Food that takes energy from the organism eating it instead of giving it.
+/// It looks like food, so only the poison inputs tell it apart.
+#[derive(Component)]
+pub struct Poison;
+
+/// Food items currently spawned on every food tick.
+#[derive(Resource)]
+pub(crate) struct FoodRate(pub(crate) usize);
+
+/// State of the PI controller driving [`FoodRate`] in `FoodSupply::Controller` mode.
+#[derive(Resource, Default)]
+pub(crate) struct FoodController {
+    pub(crate) integral: f32,
+    log: Option<std::io::BufWriter<std::fs::File>>,
+}
+
+impl FoodController {
+    /// Food items to spawn per food tick to bring the number of food items
+    /// per organism from `ratio` towards the target, `None` in
+    /// `FoodSupply::Fixed` mode.
+    pub(crate) fn update(&mut self, config: &SimulationConfig, ratio: f32) -> Option<usize> {
+        let FoodSupply::Controller {
+            target_ratio,
+            kp,
+            ki,
+            min_food_per_timestep,
+            max_food_per_timestep,
+        } = config.food_supply
+        else {
+            return None;
+        };
+        let error = target_ratio - ratio;
+        let output = config.food_per_timestep as f32 + kp * error + ki * (self.integral + error);
+        let (min_rate, max_rate) = (min_food_per_timestep as f32, max_food_per_timestep as f32);
+        // only integrate while the output is not saturated to avoid windup
+        if (min_rate..=max_rate).contains(&output) {
+            self.integral += error;
+        }
+        Some(output.clamp(min_rate, max_rate).round() as usize)
+    }
+}
+
+#[derive(Resource)]
+pub(crate) struct FoodTimer(pub(crate) Timer);
+
+/// Side of the grid cells food is counted in for [`patchiness_index`].
+const PATCHINESS_CELL_SIZE: f32 = 50.0;
+
+/// Patchiness of the food as of the last log tick.
+#[derive(Resource, Default)]
+pub struct FoodPatchiness(pub f32);
+
+/// Variance-to-mean ratio of the number of food items in each grid cell of
+/// the arena. It is about 1 for uniformly random food, higher when food is
+/// clustered and lower when it is spread out evenly.
+pub fn patchiness_index(config: &SimulationConfig, positions: impl Iterator<Item = Vec2>) -> f32 {
+    let width = config.right_boundary - config.left_boundary;
+    let height = config.top_boundary - config.bottom_boundary;
+    let columns = (width / PATCHINESS_CELL_SIZE).ceil().max(1.0) as usize;
+    let rows = (height / PATCHINESS_CELL_SIZE).ceil().max(1.0) as usize;
+    let mut counts = vec![0.0f32; columns * rows];
+    for position in positions {
+        let column = ((position.x - config.left_boundary) / PATCHINESS_CELL_SIZE) as usize;
+        let row = ((position.y - config.bottom_boundary) / PATCHINESS_CELL_SIZE) as usize;
+        counts[row.min(rows - 1) * columns + column.min(columns - 1)] += 1.0;
+    }
+    let mean = counts.iter().sum::<f32>() / counts.len() as f32;
+    if mean == 0.0 {
+        return 0.0;
+    }
+    let variance = counts.iter().map(|c| (c - mean).powi(2)).sum::<f32>() / counts.len() as f32;
+    variance / mean
+}
+
+/// A fresh food item at `position`.
+pub(crate) fn food_bundle(config: &SimulationConfig, position: Vec3) -> impl Bundle {
+    (
+        Transform::from_translation(position).with_scale(FOOD_SIZE),
+        Food,
+        Age(1),
+        Lifetime(config.food_lifetime),
+        Energy(FOOD_ENERGY),
+        Collider,
+    )
+}
+
+/// A fresh poisonous food item at `position`.
+pub(crate) fn poison_bundle(config: &SimulationConfig, position: Vec3) -> impl Bundle {
+    (
+        Transform::from_translation(position).with_scale(FOOD_SIZE),
+        Food,
+        Poison,
+        Age(1),
+        Lifetime(config.food_lifetime),
+        Energy(POISON_ENERGY),
+        Collider,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn generate_food(
+    config: Res<SimulationConfig>,
+    food_rate: Res<FoodRate>,
+    mut rng: ResMut<SimRng>,
+    fixed_time: Res<FixedTime>,
+    mut timer: ResMut<FoodTimer>,
+    rd_grid: Res<RDGrid>,
+    mut soil: ResMut<SoilNutrient>,
+    patches: Res<FoodPatches>,
+    mut commands: Commands,
+) {
+    if timer.0.tick(fixed_time.period).just_finished() {
+        let mut item = |rng: &mut SimRng| {
+            let position = outside_obstacles(&config, || {
+                if let FoodDistribution::Patchy { patch_radius, .. } = config.food_distribution {
+                    patches.sample_position(&config, patch_radius, &mut rng.0)
+                } else if config.reaction_diffusion.is_some() {
+                    rd_grid.sample_position(&config, &mut rng.0)
+                } else if let Some(cycling) = &config.nutrient_cycling {
+                    soil.sample_position(&config, cycling.background, &mut rng.0)
+                } else {
+                    random_position(&config, &mut rng.0)
+                }
+            });
+            // only drawn when enabled, so that runs without poison stay the
+            // same
+            let poison =
+                config.poison_probability > 0.0 && rng.0.gen::<f32>() < config.poison_probability;
+            if config.nutrient_cycling.is_some() && !poison {
+                // the food takes up what it will give to the organism eating it
+                soil.take(&config, position.truncate(), FOOD_ENERGY);
+            }
+            (position, poison)
+        };
+        let spawn = |commands: &mut Commands, position, poison| {
+            if poison {
+                commands.spawn(poison_bundle(&config, position));
+            } else {
+                commands.spawn(food_bundle(&config, position));
+            }
+        };
+        if config.mirror_arena {
+            for _ in 0..food_rate.0.div_ceil(2) {
+                let (position, poison) = item(&mut rng);
+                let position = left_half(&config, position);
+                spawn(&mut commands, position, poison);
+                spawn(&mut commands, mirror_position(&config, position), poison);
+            }
+        } else {
+            for _ in 0..food_rate.0 {
+                let (position, poison) = item(&mut rng);
+                spawn(&mut commands, position, poison);
+            }
+        }
+    }
+}
+
+/// Organisms dying of old age or starvation leave `corpse_energy_fraction`
+/// of their energy behind as corpse food, split into pieces of about a food
+/// item's worth scattered over their body.
+pub(crate) fn drop_corpses(
+    mut commands: Commands,
+    config: Res<SimulationConfig>,
+    mut rng: ResMut<SimRng>,
+    query: Query<(&Transform, &Energy, &InteractionRadius, &Dead), With<Organism>>,
+) {
+    if config.corpse_energy_fraction == 0.0 {
+        return;
+    }
+    for (transform, energy, radius, dead) in &query {
+        if !matches!(dead.0, DeathCause::Expired | DeathCause::Energy) {
+            continue;
+        }
+        let amount = config.corpse_energy_fraction * energy.0;
+        if amount <= 0.0 {
+            continue;
+        }
+        let pieces = ((amount / FOOD_ENERGY).ceil() as usize).clamp(1, MAX_CORPSE_PIECES);
+        for _ in 0..pieces {
+            let offset = Vec2::from_angle(rng.0.gen_range(0.0..std::f32::consts::TAU))
+                * radius.0
+                * rng.0.gen::<f32>().sqrt();
+            let position = transform.translation.truncate() + offset;
+            commands.spawn((
+                Transform::from_translation(position.extend(0.0)).with_scale(FOOD_SIZE),
+                Food,
+                Corpse(amount / pieces as f32),
+                Age(1),
+                Lifetime(config.corpse_lifetime),
+                Energy(amount / pieces as f32),
+                Collider,
+            ));
+        }
+    }
+}
+
+/// Food other than eggs loses its energy linearly with age, from what it had
+/// when it was spawned to nothing at the end of its lifetime.
+#[allow(clippy::type_complexity)]
+pub(crate) fn decay_food(
+    mut query: Query<
+        (
+            &Age,
+            &Lifetime,
+            &mut Energy,
+            Option<&Corpse>,
+            Option<&Poison>,
+        ),
+        (With<Food>, Without<Egg>),
+    >,
+) {
+    for (age, lifetime, mut energy, corpse, poison) in &mut query {
+        let freshness = 1.0 - (age.0 as f32 / lifetime.0 as f32).min(1.0);
+        energy.0 = initial_energy(corpse, poison) * freshness;
+    }
+}
+
+/// Energy a food item had when it was spawned.
+fn initial_energy(corpse: Option<&Corpse>, poison: Option<&Poison>) -> f32 {
+    match (corpse, poison) {
+        (Some(corpse), _) => corpse.0,
+        (None, Some(_)) => POISON_ENERGY,
+        (None, None) => FOOD_ENERGY,
+    }
+}
+
+/// Fades food out as it loses its energy.
+#[allow(clippy::type_complexity)]
+pub(crate) fn food_fade(
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut circles: ResMut<CircleAssets>,
+    mut query: Query<
+        (
+            &mut Handle<ColorMaterial>,
+            &Energy,
+            Option<&Corpse>,
+            Option<&Poison>,
+        ),
+        (With<Food>, Without<Egg>),
+    >,
+) {
+    for (mut handle, energy, corpse, poison) in &mut query {
+        let mut color = match (corpse, poison) {
+            (Some(_), _) => CORPSE_COLOR,
+            (None, Some(_)) => POISON_COLOR,
+            (None, None) => FOOD_COLOR,
+        };
+        color.set_a(energy.0 / initial_energy(corpse, poison));
+        let faded = circles.material(&mut materials, color);
+        if *handle != faded {
+            *handle = faded;
+        }
+    }
+}
+
+/// Writes the food patchiness, total food energy and food supply mode to
+/// environment.csv on every log tick.
+pub(crate) fn log_environment(
+    config: Res<SimulationConfig>,
+    tick: Res<SimTick>,
+    timer: Res<LogTimer>,
+    mut patchiness: ResMut<FoodPatchiness>,
+    mut log: Local<Option<std::io::BufWriter<std::fs::File>>>,
+    food_query: Query<(&Transform, &Energy), With<Food>>,
+) {
+    if !timer.0.just_finished() {
+        return;
+    }
+    patchiness.0 = patchiness_index(
+        &config,
+        food_query.iter().map(|(t, _)| t.translation.truncate()),
+    );
+    let food_energy: f32 = food_query.iter().map(|(_, e)| e.0).sum();
+    let mode = match config.food_supply {
+        FoodSupply::Fixed => "fixed",
+        FoodSupply::Controller { .. } => "controller",
+    };
+
+    let log = log.get_or_insert_with(|| {
+        let file = std::fs::File::create("environment.csv").unwrap();
+        let mut file = std::io::BufWriter::new(file);
+        file.write_all(b"tick,food,food_energy,patchiness,food_supply,mirror_arena\n")
+            .unwrap();
+        file
+    });
+    writeln!(
+        log,
+        "{},{},{},{},{},{}",
+        tick.0,
+        food_query.iter().count(),
+        food_energy,
+        patchiness.0,
+        mode,
+        config.mirror_arena
+    )
+    .unwrap();
+}
+
+pub(crate) fn control_food_supply(
+    config: Res<SimulationConfig>,
+    tick: Res<SimTick>,
+    age_timer: Res<AgeTimer>,
+    mut controller: ResMut<FoodController>,
+    mut food_rate: ResMut<FoodRate>,
+    organism_query: Query<(), With<Organism>>,
+    food_query: Query<(), With<Food>>,
+) {
+    let FoodSupply::Controller { target_ratio, .. } = config.food_supply else {
+        return;
+    };
+    if !age_timer.0.just_finished() {
+        return;
+    }
+    let organisms = organism_query.iter().count();
+    if organisms == 0 {
+        return;
+    }
+    let ratio = food_query.iter().count() as f32 / organisms as f32;
+    let error = target_ratio - ratio;
+    if let Some(rate) = controller.update(&config, ratio) {
+        food_rate.0 = rate;
+    }
+
+    let integral = controller.integral;
+    let log = controller.log.get_or_insert_with(|| {
+        let file = std::fs::File::create("food_controller.csv").unwrap();
+        let mut file = std::io::BufWriter::new(file);
+        file.write_all(b"tick,organisms,food_ratio,error,integral,food_per_timestep\n")
+            .unwrap();
+        file
+    });
+    writeln!(
+        log,
+        "{},{},{},{},{},{}",
+        tick.0, organisms, ratio, error, integral, food_rate.0
+    )
+    .unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+
+    fn controlled_config() -> SimulationConfig {
+        SimulationConfig {
+            food_per_timestep: 10,
+            food_supply: FoodSupply::Controller {
+                target_ratio: 2.0,
+                kp: 1.0,
+                ki: 0.1,
+                min_food_per_timestep: 0,
+                max_food_per_timestep: 100,
+            },
+            ..SimulationConfig::default()
+        }
+    }
+
+    #[test]
+    fn controller_tracks_the_target_ratio() {
+        let config = controlled_config();
+        let mut controller = FoodController::default();
+        // five food ticks per age tick, a tenth of the food eaten or gone
+        // on every age tick, and a population that jumps halfway through
+        let mut food = 0.0;
+        let mut rate = config.food_per_timestep;
+        for step in 0..400 {
+            let organisms = if step < 200 { 100.0 } else { 160.0 };
+            food = 0.9 * food + 5.0 * rate as f32;
+            let ratio = food / organisms;
+            if step == 199 || step >= 350 {
+                // the rate is rounded to whole items, so it can only get
+                // within a few percent
+                assert!((ratio - 2.0).abs() < 0.05, "ratio {ratio} at {step}");
+            }
+            rate = controller.update(&config, ratio).unwrap();
+        }
+    }
+
+    #[test]
+    fn controller_rate_stays_within_bounds() {
+        let config = controlled_config();
+        let mut controller = FoodController::default();
+        assert_eq!(controller.update(&config, 1000.0), Some(0));
+        assert_eq!(controller.update(&config, 0.0), Some(12));
+        assert_eq!(controller.integral, 2.0);
+    }
+
+    #[test]
+    fn fixed_supply_has_no_controller() {
+        let config = SimulationConfig::default();
+        let mut controller = FoodController::default();
+        assert_eq!(controller.update(&config, 0.0), None);
+        assert_eq!(controller.integral, 0.0);
+    }
+
+    #[test]
+    fn evenly_spread_food_has_no_patchiness() {
+        let config = SimulationConfig::default();
+        // one item in the middle of every cell
+        let positions = (0..12).flat_map(|row| {
+            (0..24).map(move |column| {
+                Vec2::new(
+                    -600.0 + 50.0 * column as f32 + 25.0,
+                    -300.0 + 50.0 * row as f32 + 25.0,
+                )
+            })
+        });
+        assert_eq!(patchiness_index(&config, positions), 0.0);
+    }
+
+    #[test]
+    fn uniformly_random_food_has_a_patchiness_of_one() {
+        let config = SimulationConfig::default();
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let positions = (0..10_000).map(|_| {
+            Vec2::new(
+                rng.gen_range(config.left_boundary..config.right_boundary),
+                rng.gen_range(config.bottom_boundary..config.top_boundary),
+            )
+        });
+        let index = patchiness_index(&config, positions);
+        assert!((index - 1.0).abs() < 0.3, "{index}");
+    }
+
+    #[test]
+    fn clustered_food_is_patchy() {
+        let config = SimulationConfig::default();
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let centers = [
+            Vec2::new(-400.0, 100.0),
+            Vec2::new(0.0, -150.0),
+            Vec2::new(350.0, 200.0),
+        ];
+        let positions = (0..3000).map(|i| {
+            centers[i % centers.len()]
+                + Vec2::new(rng.gen_range(-40.0..40.0), rng.gen_range(-40.0..40.0))
+        });
+        assert!(patchiness_index(&config, positions) > 10.0);
+    }
+
+    #[test]
+    fn a_single_cluster_has_the_largest_patchiness() {
+        let config = SimulationConfig::default();
+        let cells = 24.0 * 12.0;
+        let items = 100;
+        let positions = (0..items).map(|i| Vec2::new(10.0 + i as f32 * 0.1, 10.0));
+        // all items in one cell out of `cells`
+        let expected = items as f32 * (cells - 1.0) / cells;
+        let index = patchiness_index(&config, positions);
+        assert!((index - expected).abs() < 1e-3, "{index}");
+    }
+}
@@ -0,0 +1,175 @@
+//! A food source the user places with E and drags around with the left
+//! mouse button, to see how quickly the organisms turn towards it.
+
+use std::io::Write;
+
+use bevy::{prelude::*, sprite::Mesh2dHandle, window::PrimaryWindow};
+
+use crate::config::SimulationConfig;
+use crate::food::{food_bundle, FoodTimer};
+use crate::founders::gaussian;
+use crate::obstacle::in_obstacle;
+use crate::organism::{Direction, Organism};
+use crate::plugin::LogTimer;
+use crate::topology::{displacement, is_torus, wrap};
+use crate::{SimRng, SimTick};
+
+/// Food items the source spawns on every food tick.
+const SOURCE_FOOD_PER_TIMESTEP: usize = 3;
+/// Standard deviation of the distance of the food from the source.
+const SOURCE_SPREAD: f32 = 15.0;
+/// How far from the source a click still grabs it.
+const GRAB_RADIUS: f32 = 25.0;
+const SOURCE_COLOR: Color = Color::rgba(0.3, 1.0, 0.3, 0.5);
+
+/// The food source, drawn as a green disc as wide as it can be grabbed, and
+/// the tick it was last moved on.
+#[derive(Component)]
+pub struct InteractiveFoodSource {
+    pub moved_tick: u64,
+}
+
+/// Where the cursor is in the world, if it is in the window.
+fn cursor_position(
+    window_query: &Query<&Window, With<PrimaryWindow>>,
+    camera_query: &Query<(&Camera, &GlobalTransform)>,
+) -> Option<Vec2> {
+    let window = window_query.get_single().ok()?;
+    let (camera, camera_transform) = camera_query.get_single().ok()?;
+    window
+        .cursor_position()
+        .and_then(|cursor| camera.viewport_to_world(camera_transform, cursor))
+        .map(|ray| ray.origin.truncate())
+}
+
+/// E places the source under the cursor or removes it, and dragging it with
+/// the left mouse button moves it.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn control_food_source(
+    mut commands: Commands,
+    keys: Res<Input<KeyCode>>,
+    mouse: Res<Input<MouseButton>>,
+    tick: Res<SimTick>,
+    mut dragging: Local<bool>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+    mut source_query: Query<(Entity, &mut Transform, &mut InteractiveFoodSource)>,
+) {
+    let cursor = cursor_position(&window_query, &camera_query);
+    if keys.just_pressed(KeyCode::E) {
+        if let Ok((source, ..)) = source_query.get_single() {
+            commands.entity(source).despawn();
+        } else if let Some(cursor) = cursor {
+            commands.spawn((
+                InteractiveFoodSource { moved_tick: tick.0 },
+                Mesh2dHandle(meshes.add(shape::Circle::default().into())),
+                materials.add(ColorMaterial::from(SOURCE_COLOR)),
+                SpatialBundle::from_transform(
+                    Transform::from_translation(cursor.extend(-0.2))
+                        .with_scale(Vec3::splat(2.0 * GRAB_RADIUS)),
+                ),
+            ));
+        }
+        return;
+    }
+    let Ok((_, mut transform, mut source)) = source_query.get_single_mut() else {
+        return;
+    };
+    let Some(cursor) = cursor else {
+        return;
+    };
+    if mouse.just_pressed(MouseButton::Left) {
+        *dragging = transform.translation.truncate().distance(cursor) < GRAB_RADIUS;
+    }
+    if !mouse.pressed(MouseButton::Left) {
+        *dragging = false;
+    }
+    if *dragging && transform.translation.truncate() != cursor {
+        transform.translation = cursor.extend(transform.translation.z);
+        source.moved_tick = tick.0;
+    }
+}
+
+/// Spawns food around the source on every food tick.
+pub(crate) fn feed_from_source(
+    mut commands: Commands,
+    config: Res<SimulationConfig>,
+    timer: Res<FoodTimer>,
+    mut rng: ResMut<SimRng>,
+    source_query: Query<&Transform, With<InteractiveFoodSource>>,
+) {
+    if !timer.0.just_finished() {
+        return;
+    }
+    let Ok(transform) = source_query.get_single() else {
+        return;
+    };
+    let min = Vec3::new(config.left_boundary, config.bottom_boundary, 0.0);
+    let max = Vec3::new(config.right_boundary, config.top_boundary, 0.0);
+    for _ in 0..SOURCE_FOOD_PER_TIMESTEP {
+        let offset = gaussian(&mut rng.0) * SOURCE_SPREAD;
+        let position = (transform.translation.truncate() + offset).extend(0.0);
+        let position = if is_torus(&config) {
+            wrap(&config, position)
+        } else {
+            position.clamp(min, max)
+        };
+        if in_obstacle(&config, position.truncate()) {
+            continue;
+        }
+        commands.spawn(food_bundle(&config, position));
+    }
+}
+
+/// Writes how well the organisms head towards the source to food_source.csv
+/// on every log tick: the mean cosine of the angle between their direction
+/// and the way to the source, 1 when all of them head straight to it.
+pub(crate) fn log_food_source_alignment(
+    config: Res<SimulationConfig>,
+    tick: Res<SimTick>,
+    timer: Res<LogTimer>,
+    mut log: Local<Option<std::io::BufWriter<std::fs::File>>>,
+    source_query: Query<(&Transform, &InteractiveFoodSource)>,
+    organism_query: Query<(&Transform, &Direction), With<Organism>>,
+) {
+    if !timer.0.just_finished() {
+        return;
+    }
+    let Ok((source_transform, source)) = source_query.get_single() else {
+        return;
+    };
+    let source_position = source_transform.translation.truncate().extend(0.0);
+    let (count, sum) = organism_query
+        .iter()
+        .filter_map(|(transform, direction)| {
+            let to_source = displacement(&config, transform.translation, source_position);
+            let to_source = to_source.truncate().try_normalize()?;
+            Some(direction.dot(to_source))
+        })
+        .fold((0, 0.0), |(count, sum), alignment| {
+            (count + 1, sum + alignment)
+        });
+    if count == 0 {
+        return;
+    }
+
+    let log = log.get_or_insert_with(|| {
+        let file = std::fs::File::create("food_source.csv").unwrap();
+        let mut file = std::io::BufWriter::new(file);
+        file.write_all(b"tick,source_x,source_y,ticks_since_moved,alignment\n")
+            .unwrap();
+        file
+    });
+    writeln!(
+        log,
+        "{},{},{},{},{}",
+        tick.0,
+        source_position.x,
+        source_position.y,
+        tick.0.saturating_sub(source.moved_tick),
+        sum / count as f32
+    )
+    .unwrap();
+}
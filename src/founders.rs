@@ -0,0 +1,158 @@
+//! Founder layouts: where the organisms of the first generation are placed.
+
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::config::{FounderLayout, Wall};
+
+/// Distance from the wall of founders placed along it.
+pub const EDGE_INSET: f32 = 10.0;
+
+/// Positions of `count` founders laid out with `layout` in the rectangle
+/// from `min` to `max`. Custom positions are taken as they are.
+pub fn founder_positions(
+    layout: &FounderLayout,
+    min: Vec2,
+    max: Vec2,
+    count: usize,
+    rng: &mut impl Rng,
+) -> Vec<Vec3> {
+    let size = max - min;
+    let positions: Vec<Vec2> = match layout {
+        FounderLayout::Uniform => (0..count)
+            .map(|_| {
+                let (x, y): (f32, f32) = (rng.gen(), rng.gen());
+                min + Vec2::new(x, y) * size
+            })
+            .collect(),
+        FounderLayout::CenterCluster { sigma } => {
+            let center = (min + max) / 2.0;
+            (0..count)
+                .map(|_| (center + gaussian(rng) * *sigma).clamp(min, max))
+                .collect()
+        }
+        FounderLayout::Edge { wall } => {
+            let inset = Vec2::splat(EDGE_INSET).min(size / 2.0);
+            let (min, max) = (min + inset, max - inset);
+            (0..count)
+                .map(|_| {
+                    let t: f32 = rng.gen();
+                    match wall {
+                        Wall::Left => Vec2::new(min.x, min.y + t * (max.y - min.y)),
+                        Wall::Right => Vec2::new(max.x, min.y + t * (max.y - min.y)),
+                        Wall::Bottom => Vec2::new(min.x + t * (max.x - min.x), min.y),
+                        Wall::Top => Vec2::new(min.x + t * (max.x - min.x), max.y),
+                    }
+                })
+                .collect()
+        }
+        FounderLayout::Grid => {
+            // about square cells, each founder at the center of its own
+            let columns = ((count as f32 * size.x / size.y).sqrt().ceil() as usize).max(1);
+            let rows = count.div_ceil(columns).max(1);
+            let cell = size / Vec2::new(columns as f32, rows as f32);
+            (0..count)
+                .map(|i| {
+                    let index = Vec2::new((i % columns) as f32, (i / columns) as f32);
+                    min + (index + 0.5) * cell
+                })
+                .collect()
+        }
+        FounderLayout::Custom { positions } => positions
+            .iter()
+            .cycle()
+            .take(count)
+            .map(|&[x, y]| Vec2::new(x, y))
+            .collect(),
+    };
+    positions.into_iter().map(|p| p.extend(0.0)).collect()
+}
+
+/// A pair of independent standard normal samples, by the Box-Muller
+/// transform.
+pub(crate) fn gaussian(rng: &mut impl Rng) -> Vec2 {
+    let u: f32 = 1.0 - rng.gen::<f32>();
+    let v: f32 = rng.gen();
+    let r = (-2.0 * u.ln()).sqrt();
+    let angle = std::f32::consts::TAU * v;
+    Vec2::new(r * angle.cos(), r * angle.sin())
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+
+    const MIN: Vec2 = Vec2::new(-600.0, -300.0);
+    const MAX: Vec2 = Vec2::new(600.0, 300.0);
+
+    fn layouts() -> Vec<FounderLayout> {
+        vec![
+            FounderLayout::Uniform,
+            FounderLayout::CenterCluster { sigma: 400.0 },
+            FounderLayout::Edge { wall: Wall::Left },
+            FounderLayout::Edge { wall: Wall::Right },
+            FounderLayout::Edge { wall: Wall::Bottom },
+            FounderLayout::Edge { wall: Wall::Top },
+            FounderLayout::Grid,
+        ]
+    }
+
+    #[test]
+    fn layouts_place_every_founder_within_bounds() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        for layout in layouts() {
+            for count in [0, 1, 7, 50, 333] {
+                let positions = founder_positions(&layout, MIN, MAX, count, &mut rng);
+                assert_eq!(positions.len(), count, "{layout:?}");
+                for p in positions {
+                    assert!(
+                        p.truncate().cmpge(MIN).all() && p.truncate().cmple(MAX).all(),
+                        "{layout:?} placed a founder at {p}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn edge_layout_keeps_founders_on_their_wall() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let layout = FounderLayout::Edge { wall: Wall::Top };
+        for p in founder_positions(&layout, MIN, MAX, 20, &mut rng) {
+            assert_eq!(p.y, MAX.y - EDGE_INSET);
+        }
+    }
+
+    #[test]
+    fn grid_layout_puts_founders_in_distinct_cells() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let positions = founder_positions(&FounderLayout::Grid, MIN, MAX, 50, &mut rng);
+        for (i, a) in positions.iter().enumerate() {
+            for b in &positions[i + 1..] {
+                assert!(a.distance(*b) > 1.0);
+            }
+        }
+    }
+
+    #[test]
+    fn custom_layout_cycles_through_its_positions() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let layout = FounderLayout::Custom {
+            positions: vec![[1.0, 2.0], [3.0, 4.0]],
+        };
+        let positions = founder_positions(&layout, MIN, MAX, 5, &mut rng);
+        assert_eq!(
+            positions,
+            [
+                Vec3::new(1.0, 2.0, 0.0),
+                Vec3::new(3.0, 4.0, 0.0),
+                Vec3::new(1.0, 2.0, 0.0),
+                Vec3::new(3.0, 4.0, 0.0),
+                Vec3::new(1.0, 2.0, 0.0),
+            ]
+        );
+    }
+}
@@ -0,0 +1,137 @@
+//! Who descends from whom, to tell whether the surviving population comes
+//! from one founder or many. L exports the whole family tree.
+
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::Path;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::organism::EvaluationOffset;
+use crate::DeathCause;
+
+pub const GENEALOGY_DOT: &str = "genealogy.dot";
+pub const GENEALOGY_CSV: &str = "genealogy.csv";
+
+/// Id of an organism, unlike its entity never reused and kept across saves.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct OrganismId(pub u64);
+
+/// Id of the organism that gave birth to this one, none for founders.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct ParentId(pub Option<u64>);
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct LineageRecord {
+    pub parent: Option<u64>,
+    /// The founder at the root of the organism's lineage, itself for
+    /// founders.
+    pub founder: u64,
+    pub birth_tick: u64,
+    pub death: Option<(u64, DeathCause)>,
+}
+
+/// Every organism ever born, indexed by id.
+#[derive(Resource, Clone, Default, Serialize, Deserialize)]
+pub struct Genealogy {
+    pub records: Vec<LineageRecord>,
+}
+
+impl Genealogy {
+    /// Records the birth of an organism and returns the ids to spawn it with,
+    /// and its sensory tick offset.
+    pub fn birth(
+        &mut self,
+        parent: Option<u64>,
+        tick: u64,
+    ) -> (OrganismId, ParentId, EvaluationOffset) {
+        let id = self.records.len() as u64;
+        let founder = parent.map_or(id, |parent| self.records[parent as usize].founder);
+        self.records.push(LineageRecord {
+            parent,
+            founder,
+            birth_tick: tick,
+            death: None,
+        });
+        (
+            OrganismId(id),
+            ParentId(parent),
+            EvaluationOffset::from_id(OrganismId(id)),
+        )
+    }
+
+    pub fn death(&mut self, id: OrganismId, tick: u64, cause: DeathCause) {
+        if let Some(record) = self.records.get_mut(id.0 as usize) {
+            record.death = Some((tick, cause));
+        }
+    }
+
+    /// Ancestors of `id` up to the founder of its lineage.
+    pub fn generation(&self, id: OrganismId) -> usize {
+        let mut generation = 0;
+        let mut parent = self.records.get(id.0 as usize).and_then(|r| r.parent);
+        while let Some(id) = parent {
+            generation += 1;
+            parent = self.records[id as usize].parent;
+        }
+        generation
+    }
+
+    pub fn founder(&self, id: OrganismId) -> Option<u64> {
+        self.records.get(id.0 as usize).map(|record| record.founder)
+    }
+
+    /// Number of founders with descendants among `ids`.
+    pub fn lineages<'a>(&self, ids: impl Iterator<Item = &'a OrganismId>) -> usize {
+        ids.filter_map(|&id| self.founder(id))
+            .collect::<HashSet<_>>()
+            .len()
+    }
+
+    /// Writes the tree as a GraphViz graph to `dot` and as one row per
+    /// organism to `csv`.
+    pub fn export(&self, dot: &Path, csv: &Path) -> std::io::Result<()> {
+        let mut file = std::io::BufWriter::new(std::fs::File::create(dot)?);
+        writeln!(file, "digraph genealogy {{")?;
+        writeln!(file, "    node [shape=point];")?;
+        for (id, record) in self.records.iter().enumerate() {
+            match record.parent {
+                Some(parent) => writeln!(file, "    {parent} -> {id};")?,
+                // founders are drawn bigger, as the roots of the lineages
+                None => writeln!(file, "    {id} [shape=circle, label=\"{id}\"];")?,
+            }
+        }
+        writeln!(file, "}}")?;
+        file.flush()?;
+
+        let mut file = std::io::BufWriter::new(std::fs::File::create(csv)?);
+        writeln!(file, "child_id,parent_id,birth_tick,death_tick,death_cause")?;
+        for (id, record) in self.records.iter().enumerate() {
+            let parent = record.parent.map(|p| p.to_string()).unwrap_or_default();
+            let (death_tick, cause) = match record.death {
+                Some((tick, cause)) => (tick.to_string(), format!("{cause:?}")),
+                None => (String::new(), String::new()),
+            };
+            writeln!(
+                file,
+                "{id},{parent},{},{death_tick},{cause}",
+                record.birth_tick
+            )?;
+        }
+        file.flush()
+    }
+}
+
+pub(crate) fn export_genealogy(keys: Res<Input<KeyCode>>, genealogy: Res<Genealogy>) {
+    if !keys.just_pressed(KeyCode::L) {
+        return;
+    }
+    match genealogy.export(Path::new(GENEALOGY_DOT), Path::new(GENEALOGY_CSV)) {
+        Ok(()) => info!(
+            "exported {} organisms to {GENEALOGY_DOT} and {GENEALOGY_CSV}",
+            genealogy.records.len()
+        ),
+        Err(e) => error!("could not export the genealogy: {e}"),
+    }
+}
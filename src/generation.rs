@@ -0,0 +1,101 @@
+//! Statistics by generation, founders being generation 0: how many of each
+//! generation died and how they fared, summed up as they die.
+
+use std::collections::BTreeSet;
+use std::io::Write;
+
+use bevy::prelude::*;
+
+use crate::organism::{DeathEvent, Generation, Organism};
+use crate::plugin::LogTimer;
+use crate::SimTick;
+
+/// Sums over the dead of one generation.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GenerationRecord {
+    pub deaths: usize,
+    /// Energy left at death.
+    pub energy: f32,
+    /// Age ticks lived.
+    pub lifespan: usize,
+    pub food_eaten: u64,
+}
+
+impl GenerationRecord {
+    pub fn mean_energy(&self) -> f32 {
+        self.energy / self.deaths.max(1) as f32
+    }
+
+    pub fn mean_lifespan(&self) -> f32 {
+        self.lifespan as f32 / self.deaths.max(1) as f32
+    }
+
+    pub fn mean_food_eaten(&self) -> f32 {
+        self.food_eaten as f32 / self.deaths.max(1) as f32
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct GenerationStats {
+    /// The dead of every generation, indexed by generation.
+    pub generations: Vec<GenerationRecord>,
+    /// Latest generation among the living organisms.
+    pub max_living: u32,
+}
+
+/// Adds the dead to the stats of their generation, and appends the stats
+/// of every generation that had deaths since the last log tick to
+/// generations.csv.
+pub(crate) fn update_generation_stats(
+    tick: Res<SimTick>,
+    timer: Res<LogTimer>,
+    mut stats: ResMut<GenerationStats>,
+    mut death_events: EventReader<DeathEvent>,
+    mut changed: Local<BTreeSet<usize>>,
+    mut log: Local<Option<std::io::BufWriter<std::fs::File>>>,
+    query: Query<&Generation, With<Organism>>,
+) {
+    for event in death_events.iter() {
+        if stats.generations.len() <= event.generation {
+            stats
+                .generations
+                .resize(event.generation + 1, GenerationRecord::default());
+        }
+        let record = &mut stats.generations[event.generation];
+        record.deaths += 1;
+        record.energy += event.energy;
+        record.lifespan += event.age;
+        record.food_eaten += event.food_eaten as u64;
+        changed.insert(event.generation);
+    }
+    stats.max_living = query
+        .iter()
+        .map(|generation| generation.0)
+        .max()
+        .unwrap_or(0);
+    if !timer.0.just_finished() || changed.is_empty() {
+        return;
+    }
+
+    let log = log.get_or_insert_with(|| {
+        let file = std::fs::File::create("generations.csv").unwrap();
+        let mut file = std::io::BufWriter::new(file);
+        file.write_all(b"tick,generation,deaths,mean_energy,mean_lifespan,mean_food_eaten\n")
+            .unwrap();
+        file
+    });
+    for generation in std::mem::take(&mut *changed) {
+        let record = stats.generations[generation];
+        writeln!(
+            log,
+            "{},{},{},{},{},{}",
+            tick.0,
+            generation,
+            record.deaths,
+            record.mean_energy(),
+            record.mean_lifespan(),
+            record.mean_food_eaten()
+        )
+        .unwrap();
+    }
+}
@@ -0,0 +1,702 @@
+//! The organism genome and the small neural network it encodes.
+
+use std::f32::consts::PI;
+
+use bevy::prelude::*;
+use rand::{
+    distributions::{Distribution, Standard},
+    Rng,
+};
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::config::SimulationConfig;
+use crate::organism::MEMORY_DECAY_TICKS;
+
+/// Version of the gene layout written to the logs, bumped every time the
+/// meaning of a locus changes.
+pub const GENE_VERSION: u32 = 17;
+/// Number of sensory inputs fed to the gene's network.
+pub const N_INPUTS: usize = 29;
+/// Number of outputs of the network: turning, speed change, the unused
+/// third one and pheromone emission intensity.
+pub const N_OUTPUTS: usize = 4;
+/// A bias for every output followed by a weight for every input of every
+/// output.
+pub const NETWORK_LENGTH: usize = N_OUTPUTS * (N_INPUTS + 1);
+/// Locus of the gene setting the organism's maximum speed.
+pub const MAX_SPEED_LOCUS: usize = NETWORK_LENGTH;
+/// Locus of the gene setting how wide the organism's field of view is.
+pub const VISION_ANGLE_LOCUS: usize = NETWORK_LENGTH + 1;
+/// Locus of the gene setting how far the organism can see.
+pub const VISION_RANGE_LOCUS: usize = NETWORK_LENGTH + 2;
+/// Locus of the gene setting how many age ticks the organism lives.
+pub const LIFETIME_LOCUS: usize = NETWORK_LENGTH + 3;
+/// Locus of the gene setting how brightly the organism glows.
+pub const LUMINOSITY_LOCUS: usize = NETWORK_LENGTH + 4;
+/// Locus of the gene setting how likely a well fed organism is to stash food.
+pub const STASH_LOCUS: usize = NETWORK_LENGTH + 5;
+/// Locus of the gene setting how fast the organism forgets where it saw food.
+pub const MEMORY_DECAY_LOCUS: usize = NETWORK_LENGTH + 6;
+/// Locus of the gene setting how carnivorous the organism is.
+pub const DIET_LOCUS: usize = NETWORK_LENGTH + 7;
+/// Locus of the gene setting how far ahead of the organism it smells food.
+pub const ANTENNA_LOCUS: usize = NETWORK_LENGTH + 8;
+/// Locus of the gene trading how hard the organism is for carnivores to
+/// see against how attractive it is to mates.
+pub const CAMOUFLAGE_LOCUS: usize = NETWORK_LENGTH + 9;
+/// Locus of the gene setting how many ticks the organism runs straight
+/// between tumbles in run-and-tumble mode.
+pub const RUN_LENGTH_LOCUS: usize = NETWORK_LENGTH + 10;
+/// Locus of the gene setting how much the organism takes in of what its
+/// swarm senses.
+pub const SWARM_SENSING_LOCUS: usize = NETWORK_LENGTH + 11;
+/// Locus of the gene setting whether the organism burrows when predators
+/// come close.
+pub const BURROWING_LOCUS: usize = NETWORK_LENGTH + 12;
+/// Locus of the gene setting the gain on the organism's pheromone inputs.
+pub const PHEROMONE_SENSITIVITY_LOCUS: usize = NETWORK_LENGTH + 13;
+/// Number of genes setting [`Traits`].
+pub const N_TRAITS: usize = 14;
+/// Locus of the first of the genes setting how each network gene changes
+/// with age, see [`AgeGeneMultiplier`].
+pub const AGE_GENES_LOCUS: usize = NETWORK_LENGTH + N_TRAITS;
+/// The network, the trait genes and then the age genes of the network.
+pub const GENE_LENGTH: usize = AGE_GENES_LOCUS + NETWORK_LENGTH;
+
+/// Range of maximum speeds the max speed gene maps to.
+pub const MAX_SPEED_RANGE: (f32, f32) = (4.0, 16.0);
+/// Range of vision angles, in radians, the vision angle gene maps to.
+pub const VISION_ANGLE_RANGE: (f32, f32) = (PI / 4.0, 2.0 * PI);
+/// Range of vision distances the vision range gene maps to.
+pub const VISION_RANGE_RANGE: (f32, f32) = (50.0, 200.0);
+/// Range of lifetimes, in age ticks, the lifetime gene maps to.
+pub const LIFETIME_RANGE: (f32, f32) = (50.0, 200.0);
+/// Range of luminosities the luminosity gene maps to.
+pub const LUMINOSITY_RANGE: (f32, f32) = (0.0, 1.0);
+/// Range of stash probabilities the stash gene maps to.
+pub const STASH_RANGE: (f32, f32) = (0.0, 1.0);
+/// Range of memory decay rates, per tick, the memory decay gene maps to.
+pub const MEMORY_DECAY_RANGE: (f32, f32) = (0.0, 1.0);
+/// Range of carnivory the diet gene maps to.
+pub const DIET_RANGE: (f32, f32) = (0.0, 1.0);
+/// Range of antenna offsets, in organism widths, the antenna gene maps to.
+pub const ANTENNA_RANGE: (f32, f32) = (0.0, 4.0);
+/// Range of camouflage the camouflage gene maps to.
+pub const CAMOUFLAGE_RANGE: (f32, f32) = (0.0, 1.0);
+/// Range of run lengths, in ticks, the run length gene maps to.
+pub const RUN_LENGTH_RANGE: (f32, f32) = (5.0, 50.0);
+/// Range of swarm sensing the swarm sensing gene maps to.
+pub const SWARM_SENSING_RANGE: (f32, f32) = (0.0, 1.0);
+/// Range of burrowing the burrowing gene maps to.
+pub const BURROWING_RANGE: (f32, f32) = (0.0, 1.0);
+/// Range of gains the pheromone sensitivity gene maps to.
+pub const PHEROMONE_SENSITIVITY_RANGE: (f32, f32) = (0.0, 3.0);
+
+/// Locus of the weight of `input` for `output`.
+pub fn weight_locus(output: usize, input: usize) -> usize {
+    N_OUTPUTS + output * N_INPUTS + input
+}
+
+/// Parts of the genome that can mutate at different rates.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GeneGroup {
+    /// The bias of each output.
+    Bias,
+    /// Weights of the food inputs.
+    Food,
+    /// Weights of the position and wall inputs.
+    Wall,
+    /// Weights of the organism's own speed, energy and lifetime.
+    State,
+    /// Weights of the density of other organisms and their pheromones
+    /// around.
+    Neighbor,
+    /// Genes setting the organism's [`Traits`].
+    Trait,
+}
+
+impl GeneGroup {
+    /// Group of the gene at `locus`.
+    pub fn of(locus: usize) -> Self {
+        // an age gene mutates like the network gene it changes
+        if locus >= AGE_GENES_LOCUS {
+            return GeneGroup::of(locus - AGE_GENES_LOCUS);
+        }
+        if locus >= NETWORK_LENGTH {
+            return GeneGroup::Trait;
+        }
+        if locus < N_OUTPUTS {
+            return GeneGroup::Bias;
+        }
+        match (locus - N_OUTPUTS) % N_INPUTS {
+            1 | 2 | 19 | 20 => GeneGroup::Wall,
+            5..=7 | 9..=11 | 21..=27 => GeneGroup::Food,
+            12..=18 | 28 => GeneGroup::Neighbor,
+            _ => GeneGroup::State,
+        }
+    }
+}
+
+/// Probability of mutating a single gene, by [`GeneGroup`].
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct MutationRates {
+    pub bias: f32,
+    pub food: f32,
+    pub wall: f32,
+    pub state: f32,
+    pub neighbor: f32,
+    pub traits: f32,
+}
+
+impl MutationRates {
+    /// The same rate for every group.
+    pub fn uniform(rate: f32) -> Self {
+        Self {
+            bias: rate,
+            food: rate,
+            wall: rate,
+            state: rate,
+            neighbor: rate,
+            traits: rate,
+        }
+    }
+
+    pub fn rate(&self, group: GeneGroup) -> f32 {
+        match group {
+            GeneGroup::Bias => self.bias,
+            GeneGroup::Food => self.food,
+            GeneGroup::Wall => self.wall,
+            GeneGroup::State => self.state,
+            GeneGroup::Neighbor => self.neighbor,
+            GeneGroup::Trait => self.traits,
+        }
+    }
+}
+
+/// A single gene changed by [`GeneInfo::mutate`].
+#[derive(Clone, Copy, Debug)]
+pub struct Mutation {
+    pub locus: usize,
+    pub old: f32,
+    pub new: f32,
+}
+
+#[derive(Component, Clone, Debug)]
+pub struct GeneInfo(pub [f32; GENE_LENGTH]);
+
+// serde only derives for arrays of up to 32 elements
+impl Serialize for GeneInfo {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.0.iter())
+    }
+}
+
+impl<'de> Deserialize<'de> for GeneInfo {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let gene = Vec::<f32>::deserialize(deserializer)?;
+        let len = gene.len();
+        gene.try_into()
+            .map(Self)
+            .map_err(|_| D::Error::invalid_length(len, &"a gene of GENE_LENGTH values"))
+    }
+}
+
+impl Distribution<GeneInfo> for Standard {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> GeneInfo {
+        let mut gene: [f32; GENE_LENGTH] = std::array::from_fn(|_| rng.gen());
+        gene = gene.map(|g| (g - 0.5) * 2.0);
+        gene[0] /= 2.0;
+        gene[1] /= 2.0;
+        gene[2] /= 2.0;
+        GeneInfo(gene)
+    }
+}
+
+impl GeneInfo {
+    /// A hand made gene that seeks out food, with the traits the config
+    /// gives founders.
+    pub fn planned(config: &SimulationConfig) -> Self {
+        let mut gene: [f32; GENE_LENGTH] = [0.0; GENE_LENGTH];
+        // leave pheromones behind at a moderate intensity
+        gene[3] = 0.75;
+        // slow down if food is on left or right
+        gene[weight_locus(1, 5)] = -0.1;
+        gene[weight_locus(1, 7)] = -0.1;
+        // speed up if there is food on the front
+        gene[weight_locus(1, 6)] = 1.0;
+        // go left if food is on left
+        gene[weight_locus(0, 5)] = 0.5;
+        // go right if food is on right
+        gene[weight_locus(0, 6)] = -0.5;
+        // veer off when heading out of the arena, turning harder makes them
+        // circle near the walls
+        gene[weight_locus(0, 20)] = 0.2;
+        gene[MAX_SPEED_LOCUS] = trait_gene(config.organism_default_speed, MAX_SPEED_RANGE);
+        gene[VISION_ANGLE_LOCUS] = trait_gene(2.0, VISION_ANGLE_RANGE);
+        gene[VISION_RANGE_LOCUS] = trait_gene(config.organism_vision, VISION_RANGE_RANGE);
+        gene[LIFETIME_LOCUS] = trait_gene(config.organism_default_lifetime as f32, LIFETIME_RANGE);
+        gene[LUMINOSITY_LOCUS] = trait_gene(0.0, LUMINOSITY_RANGE);
+        gene[STASH_LOCUS] = trait_gene(0.0, STASH_RANGE);
+        gene[MEMORY_DECAY_LOCUS] = trait_gene(1.0 / MEMORY_DECAY_TICKS as f32, MEMORY_DECAY_RANGE);
+        gene[DIET_LOCUS] = trait_gene(0.0, DIET_RANGE);
+        gene[ANTENNA_LOCUS] = trait_gene(0.0, ANTENNA_RANGE);
+        gene[CAMOUFLAGE_LOCUS] = trait_gene(0.0, CAMOUFLAGE_RANGE);
+        gene[RUN_LENGTH_LOCUS] = trait_gene(20.0, RUN_LENGTH_RANGE);
+        gene[SWARM_SENSING_LOCUS] = trait_gene(0.0, SWARM_SENSING_RANGE);
+        gene[BURROWING_LOCUS] = trait_gene(0.0, BURROWING_RANGE);
+        gene[PHEROMONE_SENSITIVITY_LOCUS] = trait_gene(1.0, PHEROMONE_SENSITIVITY_RANGE);
+        Self(gene)
+    }
+
+    /// Copies the gene, mutating every locus not in the genomic `islands`
+    /// with the rate of its group. Each mutation that happens is pushed to
+    /// `mutations` when given.
+    pub fn mutate(
+        &self,
+        rates: &MutationRates,
+        islands: &GenomicIslands,
+        rng: &mut impl Rng,
+        mut mutations: Option<&mut Vec<Mutation>>,
+    ) -> Self {
+        let mut new_gene = self.0;
+        for (locus, g) in new_gene.iter_mut().enumerate() {
+            if islands.0[locus] {
+                continue;
+            }
+            if rng.gen::<f32>() < rates.rate(GeneGroup::of(locus)) {
+                let old = *g;
+                *g = (old + rng.gen::<f32>() / 2.0 - 0.25).clamp(-1.0, 1.0);
+                if let Some(mutations) = mutations.as_deref_mut() {
+                    mutations.push(Mutation {
+                        locus,
+                        old,
+                        new: *g,
+                    });
+                }
+            }
+        }
+        Self(new_gene)
+    }
+
+    /// Uniform crossover, every locus comes from either gene with equal
+    /// probability.
+    pub fn crossover(&self, other: &GeneInfo, rng: &mut impl Rng) -> Self {
+        Self(std::array::from_fn(|locus| {
+            if rng.gen() {
+                self.0[locus]
+            } else {
+                other.0[locus]
+            }
+        }))
+    }
+
+    /// Runs the network as expressed by an organism `age_fraction` of the
+    /// way through its life.
+    pub fn process(
+        &self,
+        age_genes: &AgeGeneMultiplier,
+        age_fraction: f32,
+        inputs: &[f32; N_INPUTS],
+    ) -> [f32; N_OUTPUTS] {
+        let network: [f32; NETWORK_LENGTH] =
+            std::array::from_fn(|locus| self.0[locus] + age_genes.0[locus] * age_fraction);
+        std::array::from_fn(|output| {
+            let weights = &network[weight_locus(output, 0)..weight_locus(output + 1, 0)];
+            (network[output] + weights.iter().zip(inputs).map(|(c, i)| c * i).sum::<f32>())
+                .clamp(-1.0, 1.0)
+        })
+    }
+
+    pub fn color(&self) -> Color {
+        Color::rgb(
+            (self.0[0] + 1.0) / 2.0,
+            (self.0[1] + 1.0) / 2.0,
+            (self.0[2] + 1.0) / 2.0,
+        )
+    }
+}
+
+/// Maps a gene in -1..=1 linearly onto `range`.
+fn trait_value(gene: f32, range: (f32, f32)) -> f32 {
+    range.0 + (gene + 1.0) / 2.0 * (range.1 - range.0)
+}
+
+/// The gene that [`trait_value`] maps to `value`, or to the nearest end of
+/// `range` when it is out of it.
+fn trait_gene(value: f32, range: (f32, f32)) -> f32 {
+    ((value - range.0) / (range.1 - range.0) * 2.0 - 1.0).clamp(-1.0, 1.0)
+}
+
+/// How much each network gene changes over the organism's life, the gene
+/// expressed at an age is `gene + age_gene * age / lifetime`.
+#[derive(Component, Clone, Debug)]
+pub struct AgeGeneMultiplier(pub [f32; NETWORK_LENGTH]);
+
+impl AgeGeneMultiplier {
+    pub fn from_gene(gene: &GeneInfo) -> Self {
+        Self(std::array::from_fn(|locus| gene.0[AGE_GENES_LOCUS + locus]))
+    }
+}
+
+/// Properties of an organism set by its trait genes when it is born.
+#[derive(Component, Clone, Debug)]
+pub struct Traits {
+    pub max_speed: f32,
+    /// Full angle of the forward cone food is seen in, in radians.
+    pub vision_angle: f32,
+    /// How far away food can be seen.
+    pub vision: f32,
+    /// Age ticks the organism lives for.
+    pub lifetime: usize,
+    /// How brightly the organism glows, from 0 for not at all to 1.
+    pub luminosity: f32,
+    /// Probability that a well fed organism keeps food it finds in its
+    /// stash instead of eating it.
+    pub stash_probability: f32,
+    /// Rate per tick at which food sightings fade from memory, a sighting
+    /// `t` ticks old weighs `exp(-memory_decay * t)`.
+    pub memory_decay: f32,
+    /// How carnivorous the organism is, from 0 for eating only food to 1
+    /// for eating only other organisms.
+    pub diet: f32,
+    /// How far ahead of its center, in organism widths, the organism
+    /// detects food.
+    pub antenna_offset: f32,
+    /// How much less carnivores notice the organism, from 0 for not at all
+    /// to 1 for not seeing it.
+    pub camouflage: f32,
+    /// How likely fertile organisms touching it are to mate with it, the
+    /// other side of `camouflage`.
+    pub conspicuousness: f32,
+    /// Ticks the organism runs straight between tumbles in run-and-tumble
+    /// mode.
+    pub run_length: usize,
+    /// How much of the food its swarm senses in front the organism takes
+    /// in, from 0 for nothing to 1 for all of it.
+    pub swarm_sensing: f32,
+    /// How inclined the organism is to burrow, it burrows when predators
+    /// come close above `BURROW_THRESHOLD`.
+    pub burrowing: f32,
+    /// Gain on the pheromone concentration inputs. A high gain picks up
+    /// faint trails but saturates on anything stronger.
+    pub pheromone_sensitivity: f32,
+}
+
+impl Traits {
+    pub fn from_gene(gene: &GeneInfo) -> Self {
+        let camouflage = trait_value(gene.0[CAMOUFLAGE_LOCUS], CAMOUFLAGE_RANGE);
+        Self {
+            max_speed: trait_value(gene.0[MAX_SPEED_LOCUS], MAX_SPEED_RANGE),
+            vision_angle: trait_value(gene.0[VISION_ANGLE_LOCUS], VISION_ANGLE_RANGE),
+            vision: trait_value(gene.0[VISION_RANGE_LOCUS], VISION_RANGE_RANGE),
+            lifetime: trait_value(gene.0[LIFETIME_LOCUS], LIFETIME_RANGE).round() as usize,
+            luminosity: trait_value(gene.0[LUMINOSITY_LOCUS], LUMINOSITY_RANGE),
+            stash_probability: trait_value(gene.0[STASH_LOCUS], STASH_RANGE),
+            memory_decay: trait_value(gene.0[MEMORY_DECAY_LOCUS], MEMORY_DECAY_RANGE),
+            diet: trait_value(gene.0[DIET_LOCUS], DIET_RANGE),
+            antenna_offset: trait_value(gene.0[ANTENNA_LOCUS], ANTENNA_RANGE),
+            camouflage,
+            conspicuousness: 1.0 - camouflage,
+            run_length: trait_value(gene.0[RUN_LENGTH_LOCUS], RUN_LENGTH_RANGE).round() as usize,
+            swarm_sensing: trait_value(gene.0[SWARM_SENSING_LOCUS], SWARM_SENSING_RANGE),
+            burrowing: trait_value(gene.0[BURROWING_LOCUS], BURROWING_RANGE),
+            pheromone_sensitivity: trait_value(
+                gene.0[PHEROMONE_SENSITIVITY_LOCUS],
+                PHEROMONE_SENSITIVITY_RANGE,
+            ),
+        }
+    }
+
+    /// Age after which the organism can get pregnant, the same fraction of
+    /// its lifetime as `fertile_age` is of the founders' lifetime.
+    pub fn fertile_age(&self, config: &SimulationConfig) -> usize {
+        self.lifetime * config.fertile_age / config.organism_default_lifetime
+    }
+
+    /// `color` tinted red as much as the organism is carnivorous.
+    pub fn diet_tint(&self, color: Color) -> Color {
+        Color::rgba(
+            color.r() + (1.0 - color.r()) * self.diet,
+            color.g() * (1.0 - self.diet),
+            color.b() * (1.0 - self.diet),
+            color.a(),
+        )
+    }
+}
+
+/// Population variance under which a locus counts as conserved.
+pub const CONSERVATION_THRESHOLD: f32 = 1e-3;
+/// Ticks a locus has to stay conserved before it stops mutating.
+pub const CONSERVATION_TICKS: usize = 100;
+
+/// Loci protected from mutation, `true` once the population has kept a
+/// locus nearly constant for [`CONSERVATION_TICKS`].
+#[derive(Resource)]
+pub struct GenomicIslands(pub [bool; GENE_LENGTH]);
+
+impl Default for GenomicIslands {
+    fn default() -> Self {
+        Self([false; GENE_LENGTH])
+    }
+}
+
+/// Per locus progress towards becoming a genomic island.
+#[derive(Resource)]
+pub(crate) struct Conservation {
+    /// Whether the locus ever varied, the founders all share one gene so a
+    /// locus that never did isn't conserved, just not explored yet.
+    pub(crate) varied: [bool; GENE_LENGTH],
+    /// Ticks in a row the locus has stayed conserved.
+    pub(crate) ticks: [usize; GENE_LENGTH],
+}
+
+impl Default for Conservation {
+    fn default() -> Self {
+        Self {
+            varied: [false; GENE_LENGTH],
+            ticks: [0; GENE_LENGTH],
+        }
+    }
+}
+
+impl Conservation {
+    /// Moves every locus towards or out of being an island given its
+    /// variance over the population this tick.
+    fn update(&mut self, islands: &mut GenomicIslands, variances: &[f32; GENE_LENGTH]) {
+        for (locus, &variance) in variances.iter().enumerate() {
+            if variance >= CONSERVATION_THRESHOLD {
+                self.varied[locus] = true;
+                self.ticks[locus] = 0;
+                if islands.0[locus] {
+                    islands.0[locus] = false;
+                    info!("locus {locus} varies again and mutates again");
+                }
+            } else if self.varied[locus] && !islands.0[locus] {
+                self.ticks[locus] += 1;
+                if self.ticks[locus] >= CONSERVATION_TICKS {
+                    islands.0[locus] = true;
+                    info!("locus {locus} is conserved and no longer mutates");
+                }
+            }
+        }
+    }
+}
+
+/// Population variance of every locus, from the squared distances to the
+/// mean so that rounding can't make it negative like `E[x²] - mean²` can.
+fn locus_variances(genes: &[&GeneInfo]) -> [f32; GENE_LENGTH] {
+    let count = genes.len().max(1) as f32;
+    let mut mean = [0.0; GENE_LENGTH];
+    for gene in genes {
+        for (m, g) in mean.iter_mut().zip(gene.0) {
+            *m += g;
+        }
+    }
+    let mean = mean.map(|m| m / count);
+    let mut variance = [0.0f32; GENE_LENGTH];
+    for gene in genes {
+        for ((v, m), g) in variance.iter_mut().zip(mean).zip(gene.0) {
+            *v += (g - m) * (g - m);
+        }
+    }
+    variance.map(|v| (v / count).max(0.0))
+}
+
+/// Protects the loci that varied once and then had their variance over the
+/// population stay under [`CONSERVATION_THRESHOLD`] for
+/// [`CONSERVATION_TICKS`] ticks in a row. A protected locus mutates again
+/// once its variance gets back over the threshold, as when organisms of an
+/// earlier population come back from the hall of fame.
+pub(crate) fn update_genomic_islands(
+    mut islands: ResMut<GenomicIslands>,
+    mut conservation: ResMut<Conservation>,
+    query: Query<&GeneInfo>,
+) {
+    let genes: Vec<&GeneInfo> = query.iter().collect();
+    if genes.len() < 2 {
+        return;
+    }
+    conservation.update(&mut islands, &locus_variances(&genes));
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+
+    fn no_age_genes() -> AgeGeneMultiplier {
+        AgeGeneMultiplier([0.0; NETWORK_LENGTH])
+    }
+
+    #[test]
+    fn process_adds_the_weighted_inputs_to_the_bias() {
+        let mut gene = GeneInfo([0.0; GENE_LENGTH]);
+        gene.0[0] = 0.1;
+        gene.0[weight_locus(0, 3)] = 0.5;
+        gene.0[weight_locus(1, 3)] = -0.25;
+        gene.0[weight_locus(2, 4)] = 1.0;
+        let mut inputs = [0.0; N_INPUTS];
+        inputs[3] = 0.8;
+        let outputs = gene.process(&no_age_genes(), 0.0, &inputs);
+        assert_eq!(outputs, [0.1 + 0.5 * 0.8, -0.25 * 0.8, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn process_clamps_the_outputs() {
+        let mut gene = GeneInfo([0.0; GENE_LENGTH]);
+        gene.0[0] = 1.0;
+        gene.0[1] = -1.0;
+        gene.0[weight_locus(0, 0)] = 1.0;
+        gene.0[weight_locus(1, 0)] = 1.0;
+        let mut inputs = [0.0; N_INPUTS];
+        inputs[0] = -3.0;
+        let outputs = gene.process(&no_age_genes(), 0.0, &inputs);
+        assert_eq!(outputs[0], -1.0);
+        assert_eq!(outputs[1], -1.0);
+        inputs[0] = 3.0;
+        let outputs = gene.process(&no_age_genes(), 0.0, &inputs);
+        assert_eq!(outputs[0], 1.0);
+        assert_eq!(outputs[1], 1.0);
+    }
+
+    #[test]
+    fn process_expresses_the_age_genes_with_age() {
+        let gene = GeneInfo([0.0; GENE_LENGTH]);
+        let mut age_genes = no_age_genes();
+        age_genes.0[0] = 0.4;
+        let inputs = [0.0; N_INPUTS];
+        assert_eq!(gene.process(&age_genes, 0.0, &inputs)[0], 0.0);
+        assert_eq!(gene.process(&age_genes, 0.5, &inputs)[0], 0.2);
+        assert_eq!(gene.process(&age_genes, 1.0, &inputs)[0], 0.4);
+    }
+
+    #[test]
+    fn mutate_keeps_genes_within_bounds() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let islands = GenomicIslands::default();
+        let rates = MutationRates::uniform(1.0);
+        for edge in [-1.0, 1.0] {
+            let mut gene = GeneInfo([edge; GENE_LENGTH]);
+            for _ in 0..100 {
+                gene = gene.mutate(&rates, &islands, &mut rng, None);
+                assert!(gene.0.iter().all(|g| (-1.0..=1.0).contains(g)));
+            }
+        }
+    }
+
+    #[test]
+    fn mutate_leaves_islands_alone() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let mut islands = GenomicIslands::default();
+        islands.0[5] = true;
+        let gene = GeneInfo([0.0; GENE_LENGTH]);
+        let mut mutations = Vec::new();
+        let mutated = gene.mutate(
+            &MutationRates::uniform(1.0),
+            &islands,
+            &mut rng,
+            Some(&mut mutations),
+        );
+        assert_eq!(mutated.0[5], 0.0);
+        assert_eq!(mutations.len(), GENE_LENGTH - 1);
+        assert!(mutations.iter().all(|m| m.locus != 5));
+    }
+
+    #[test]
+    fn groups_mutate_at_their_own_rates() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let islands = GenomicIslands::default();
+        let rates = MutationRates {
+            bias: 0.1,
+            food: 0.3,
+            wall: 0.5,
+            state: 0.7,
+            neighbor: 0.2,
+            traits: 0.9,
+        };
+        let groups = [
+            GeneGroup::Bias,
+            GeneGroup::Food,
+            GeneGroup::Wall,
+            GeneGroup::State,
+            GeneGroup::Neighbor,
+            GeneGroup::Trait,
+        ];
+        let gene = GeneInfo([0.0; GENE_LENGTH]);
+        let trials = 2000;
+        let mut mutations = Vec::new();
+        for _ in 0..trials {
+            gene.mutate(&rates, &islands, &mut rng, Some(&mut mutations));
+        }
+        for group in groups {
+            let loci = (0..GENE_LENGTH)
+                .filter(|&locus| GeneGroup::of(locus) == group)
+                .count();
+            let mutated = mutations
+                .iter()
+                .filter(|m| GeneGroup::of(m.locus) == group)
+                .count();
+            let n = (loci * trials) as f32;
+            let p = rates.rate(group);
+            let frequency = mutated as f32 / n;
+            // five standard deviations of a binomial frequency
+            let tolerance = 5.0 * (p * (1.0 - p) / n).sqrt();
+            assert!(
+                (frequency - p).abs() < tolerance,
+                "{group:?} mutated at {frequency}, not {p}"
+            );
+        }
+    }
+
+    #[test]
+    fn variance_of_equal_genes_is_about_zero() {
+        for g in [0.1, -0.7, 0.999] {
+            let gene = GeneInfo([g; GENE_LENGTH]);
+            let genes = vec![&gene; 1001];
+            assert!(locus_variances(&genes)
+                .iter()
+                .all(|v| (0.0..1e-9).contains(v)));
+        }
+    }
+
+    #[test]
+    fn variance_matches_the_definition() {
+        let genes = [-0.5, 0.0, 0.25, 1.0].map(|g| GeneInfo([g; GENE_LENGTH]));
+        let genes: Vec<&GeneInfo> = genes.iter().collect();
+        // mean 0.1875
+        let expected =
+            (0.6875f32.powi(2) + 0.1875f32.powi(2) + 0.0625f32.powi(2) + 0.8125f32.powi(2)) / 4.0;
+        for variance in locus_variances(&genes) {
+            assert!((variance - expected).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn islands_lock_after_staying_conserved_and_unlock_when_varying() {
+        let mut islands = GenomicIslands::default();
+        let mut conservation = Conservation::default();
+        let varying = [1.0; GENE_LENGTH];
+        let conserved = [0.0; GENE_LENGTH];
+        // never varied, so never locked
+        for _ in 0..2 * CONSERVATION_TICKS {
+            conservation.update(&mut islands, &conserved);
+        }
+        assert!(!islands.0[0]);
+
+        conservation.update(&mut islands, &varying);
+        for _ in 0..CONSERVATION_TICKS - 1 {
+            conservation.update(&mut islands, &conserved);
+        }
+        assert!(!islands.0[0]);
+        conservation.update(&mut islands, &conserved);
+        assert!(islands.0.iter().all(|&island| island));
+
+        let mut one_varies = conserved;
+        one_varies[3] = 1.0;
+        conservation.update(&mut islands, &one_varies);
+        assert!(!islands.0[3]);
+        assert!(islands.0[0]);
+    }
+}
@@ -0,0 +1,118 @@
+//! A live scatter plot of the population's genes projected onto their first
+//! two principal components, to see the population split and drift, toggled
+//! with G.
+
+use bevy::prelude::*;
+
+use crate::genes::GeneInfo;
+use crate::organism::Organism;
+use crate::plugin::LogTimer;
+use crate::snapshots::PcaSolver;
+
+/// Power iterations per component on every log tick, the solver starts from
+/// the components of the last one so a few are enough.
+const PLOT_ITERATIONS: usize = 5;
+/// Side of the plot in pixels.
+const PLOT_SIZE: f32 = 200.0;
+const DOT_SIZE: f32 = 4.0;
+const PLOT_BACKGROUND: Color = Color::rgba(0.0, 0.0, 0.0, 0.6);
+
+/// Every organism's position in the plane of the first two principal
+/// components as of the last log tick, with its color.
+#[derive(Resource, Default)]
+pub struct GenomeProjection {
+    solver: PcaSolver,
+    pub points: Vec<([f32; 2], Color)>,
+}
+
+#[derive(Component)]
+pub(crate) struct GenomePlot;
+
+pub(crate) fn update_genome_projection(
+    timer: Res<LogTimer>,
+    mut projection: ResMut<GenomeProjection>,
+    query: Query<&GeneInfo, With<Organism>>,
+) {
+    if !timer.0.just_finished() {
+        return;
+    }
+    let genes: Vec<Vec<f32>> = query.iter().map(|gene| gene.0.to_vec()).collect();
+    let projection = &mut *projection;
+    let points = projection.solver.project(&genes, PLOT_ITERATIONS);
+    projection.points = points
+        .into_iter()
+        .zip(query.iter().map(GeneInfo::color))
+        .collect();
+}
+
+pub(crate) fn setup_genome_plot(mut commands: Commands) {
+    commands.spawn((
+        GenomePlot,
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    bottom: Val::Px(10.0),
+                    left: Val::Px(10.0),
+                    ..default()
+                },
+                size: Size::new(Val::Px(PLOT_SIZE), Val::Px(PLOT_SIZE)),
+                ..default()
+            },
+            background_color: PLOT_BACKGROUND.into(),
+            ..default()
+        },
+    ));
+}
+
+/// Redraws the dots whenever the projection changes, scaled so that they
+/// fill the plot.
+pub(crate) fn update_genome_plot(
+    mut commands: Commands,
+    keys: Res<Input<KeyCode>>,
+    projection: Res<GenomeProjection>,
+    mut plot_query: Query<(Entity, &mut Visibility), With<GenomePlot>>,
+) {
+    let Ok((plot, mut visibility)) = plot_query.get_single_mut() else {
+        return;
+    };
+    // Ctrl+G opens the genome library instead
+    let ctrl = keys.any_pressed([KeyCode::LControl, KeyCode::RControl]);
+    if !ctrl && keys.just_pressed(KeyCode::G) {
+        *visibility = match *visibility {
+            Visibility::Hidden => Visibility::Inherited,
+            _ => Visibility::Hidden,
+        };
+    }
+    if !projection.is_changed() {
+        return;
+    }
+    let (min, max) = projection.points.iter().fold(
+        (Vec2::splat(f32::INFINITY), Vec2::splat(f32::NEG_INFINITY)),
+        |(min, max), ([x, y], _)| {
+            let p = Vec2::new(*x, *y);
+            (min.min(p), max.max(p))
+        },
+    );
+    let range = (max - min).max(Vec2::splat(f32::EPSILON));
+    commands.entity(plot).despawn_descendants();
+    commands.entity(plot).with_children(|parent| {
+        for ([x, y], color) in &projection.points {
+            let p = (Vec2::new(*x, *y) - min) / range * (PLOT_SIZE - DOT_SIZE);
+            parent.spawn(NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: UiRect {
+                        left: Val::Px(p.x),
+                        bottom: Val::Px(p.y),
+                        ..default()
+                    },
+                    size: Size::new(Val::Px(DOT_SIZE), Val::Px(DOT_SIZE)),
+                    ..default()
+                },
+                background_color: (*color).into(),
+                ..default()
+            });
+        }
+    });
+}
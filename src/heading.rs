@@ -0,0 +1,203 @@
+//! Which way organisms are heading, drawn as a tick on the edge of their
+//! circle along their direction, and an outline around the pregnant ones so
+//! that reproduction can be seen coming. The outline is added when
+//! [`Pregnant`] is inserted and goes when it is removed or the organism dies.
+
+use bevy::prelude::*;
+
+use crate::organism::{Direction, Organism, Pregnant};
+use crate::plugin::CircleAssets;
+
+/// Length and width of the tick, relative to the organism's diameter.
+const TICK_SIZE: Vec2 = Vec2::new(0.4, 0.12);
+const TICK_COLOR: Color = Color::rgb(0.1, 0.1, 0.1);
+/// Diameter of the outline relative to the organism's.
+const OUTLINE_SCALE: f32 = 1.3;
+const OUTLINE_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.6);
+
+/// Tick drawn as a child of the organism, turned with its direction.
+#[derive(Component)]
+pub struct HeadingTick;
+
+/// Outline drawn under the organism as its child while it is pregnant.
+#[derive(Component)]
+pub struct PregnancyOutline;
+
+pub(crate) fn add_heading_ticks(
+    mut commands: Commands,
+    organism_query: Query<Entity, Added<Organism>>,
+) {
+    for organism in &organism_query {
+        let tick = commands
+            .spawn((
+                HeadingTick,
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: TICK_COLOR,
+                        custom_size: Some(TICK_SIZE),
+                        ..default()
+                    },
+                    ..default()
+                },
+            ))
+            .id();
+        commands.entity(organism).add_child(tick);
+    }
+}
+
+/// Turns the ticks to where their organisms are heading.
+pub(crate) fn sync_orientation(
+    mut tick_query: Query<(&Parent, &mut Transform), With<HeadingTick>>,
+    organism_query: Query<&Direction, With<Organism>>,
+) {
+    for (parent, mut transform) in &mut tick_query {
+        let Ok(direction) = organism_query.get(parent.get()) else {
+            continue;
+        };
+        // centered on the edge of the circle, which is 1 across in the
+        // organism's frame
+        transform.translation = (**direction * 0.5).extend(0.01);
+        transform.rotation = Quat::from_rotation_z(direction.y.atan2(direction.x));
+    }
+}
+
+/// Takes the outline off organisms that gave birth. Runs before
+/// [`add_pregnancy_outlines`] so that an organism that gave birth and
+/// conceived again since the last frame keeps one outline.
+pub(crate) fn remove_pregnancy_outlines(
+    mut commands: Commands,
+    mut removed: RemovedComponents<Pregnant>,
+    children_query: Query<&Children>,
+    outline_query: Query<(), With<PregnancyOutline>>,
+) {
+    for organism in removed.iter() {
+        // the outlines of dead organisms were despawned with them
+        let Ok(children) = children_query.get(organism) else {
+            continue;
+        };
+        for &child in children {
+            if outline_query.contains(child) {
+                commands.entity(child).despawn_recursive();
+            }
+        }
+    }
+}
+
+/// Puts an outline around organisms that conceived.
+pub(crate) fn add_pregnancy_outlines(
+    mut commands: Commands,
+    mut circles: ResMut<CircleAssets>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    organism_query: Query<Entity, (Added<Pregnant>, With<Organism>)>,
+) {
+    for organism in &organism_query {
+        let outline = commands
+            .spawn((
+                PregnancyOutline,
+                circles.mesh.clone(),
+                circles.material(&mut materials, OUTLINE_COLOR),
+                SpatialBundle::from_transform(
+                    Transform::from_xyz(0.0, 0.0, -0.01).with_scale(Vec3::splat(OUTLINE_SCALE)),
+                ),
+            ))
+            .id();
+        commands.entity(organism).add_child(outline);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::genealogy::Genealogy;
+    use crate::organism::DeathEvent;
+    use crate::plugin::reap_dead;
+    use crate::{Dead, DeathCause, SimTick};
+
+    /// An app that adds and removes outlines and despawns dead organisms,
+    /// with an organism in it.
+    fn app_with_organism() -> (App, Entity) {
+        let mut app = App::new();
+        app.add_plugin(TaskPoolPlugin::default())
+            .add_plugin(AssetPlugin::default())
+            .add_asset::<Mesh>()
+            .add_asset::<ColorMaterial>()
+            .init_resource::<SimTick>()
+            .init_resource::<Genealogy>()
+            .add_event::<DeathEvent>()
+            .add_systems(
+                (
+                    remove_pregnancy_outlines,
+                    add_pregnancy_outlines,
+                    apply_system_buffers,
+                    reap_dead,
+                )
+                    .chain(),
+            );
+        let circles = app
+            .world
+            .resource_scope(|world, mut meshes: Mut<Assets<Mesh>>| {
+                CircleAssets::new(
+                    &mut meshes,
+                    &mut world.resource_mut::<Assets<ColorMaterial>>(),
+                )
+            });
+        app.insert_resource(circles);
+        let organism = app.world.spawn((Organism, Direction(Vec2::X))).id();
+        (app, organism)
+    }
+
+    fn outlines(app: &mut App) -> Vec<Entity> {
+        app.world
+            .query_filtered::<Entity, With<PregnancyOutline>>()
+            .iter(&app.world)
+            .collect()
+    }
+
+    #[test]
+    fn outline_comes_with_conception_and_goes_with_birth() {
+        let (mut app, organism) = app_with_organism();
+        app.update();
+        assert!(outlines(&mut app).is_empty());
+
+        app.world.entity_mut(organism).insert(Pregnant(0));
+        app.update();
+        let outline = outlines(&mut app);
+        assert_eq!(outline.len(), 1);
+        assert_eq!(app.world.get::<Parent>(outline[0]).unwrap().get(), organism);
+
+        app.world.entity_mut(organism).remove::<Pregnant>();
+        app.update();
+        assert!(outlines(&mut app).is_empty());
+        assert!(app
+            .world
+            .get::<Children>(organism)
+            .is_none_or(|children| children.is_empty()));
+    }
+
+    #[test]
+    fn giving_birth_and_conceiving_again_keeps_one_outline() {
+        let (mut app, organism) = app_with_organism();
+        app.world.entity_mut(organism).insert(Pregnant(0));
+        app.update();
+        app.world.entity_mut(organism).remove::<Pregnant>();
+        app.world.entity_mut(organism).insert(Pregnant(0));
+        app.update();
+        assert_eq!(outlines(&mut app).len(), 1);
+    }
+
+    #[test]
+    fn outline_goes_with_an_organism_dying_pregnant() {
+        let (mut app, organism) = app_with_organism();
+        app.world.entity_mut(organism).insert(Pregnant(0));
+        app.update();
+        let outline = outlines(&mut app);
+        assert_eq!(outline.len(), 1);
+
+        app.world
+            .entity_mut(organism)
+            .insert(Dead(DeathCause::Energy));
+        app.update();
+        assert!(app.world.get_entity(organism).is_none());
+        assert!(app.world.get_entity(outline[0]).is_none());
+    }
+}
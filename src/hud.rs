@@ -0,0 +1,125 @@
+//! On-screen overlay with the population statistics.
+
+use std::fmt::Write;
+
+use bevy::prelude::*;
+
+use crate::census::Census;
+use crate::diversity::DiversityMetrics;
+use crate::food::FoodPatchiness;
+use crate::generation::GenerationStats;
+use crate::stats::SimStats;
+
+pub(crate) const UI_FONT: &str = "fonts/DejaVuSansMono.ttf";
+
+#[derive(Component)]
+pub(crate) struct HudText;
+
+pub(crate) fn setup_hud(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn((
+        HudText,
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: asset_server.load(UI_FONT),
+                font_size: 14.0,
+                color: Color::WHITE,
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                top: Val::Px(10.0),
+                left: Val::Px(10.0),
+                ..default()
+            },
+            ..default()
+        }),
+    ));
+}
+
+/// One block character per value, as high as the value relative to the
+/// largest one.
+fn sparkline(values: impl Iterator<Item = f32> + Clone) -> String {
+    const BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let max = values.clone().fold(0.0, f32::max);
+    values
+        .map(|v| {
+            let level = if max > 0.0 { v / max } else { 0.0 };
+            BLOCKS[((level * 7.0).round() as usize).min(7)]
+        })
+        .collect()
+}
+
+pub(crate) fn update_hud(
+    stats: Res<SimStats>,
+    patchiness: Res<FoodPatchiness>,
+    census: Res<Census>,
+    diversity: Res<DiversityMetrics>,
+    generations: Res<GenerationStats>,
+    mut text_query: Query<&mut Text, With<HudText>>,
+) {
+    if !stats.is_changed() {
+        return;
+    }
+    let Ok(mut text) = text_query.get_single_mut() else {
+        return;
+    };
+    let mut hud = String::new();
+    writeln!(
+        hud,
+        "organisms:   {}, {} pregnant",
+        stats.organisms, stats.pregnant
+    )
+    .unwrap();
+    writeln!(hud, "food:        {}", stats.food).unwrap();
+    writeln!(hud, "pheromones:  {:.1}", stats.pheromones).unwrap();
+    writeln!(hud, "mean energy: {:.3}", stats.mean_energy).unwrap();
+    writeln!(
+        hud,
+        "age:         {:.1} mean, {} max",
+        stats.mean_age, stats.max_age
+    )
+    .unwrap();
+    writeln!(
+        hud,
+        "last second: {} conceptions, {} births, {} deaths",
+        stats.recent_conceptions, stats.recent_births, stats.recent_deaths
+    )
+    .unwrap();
+    writeln!(hud, "lineages:    {}", stats.lineages).unwrap();
+    writeln!(hud, "generation:  {}", generations.max_living).unwrap();
+    if stats.kills > 0 {
+        writeln!(hud, "kills:       {}", stats.kills).unwrap();
+    }
+    if stats.corpses_eaten > 0 {
+        writeln!(hud, "scavenged:   {}", stats.corpses_eaten).unwrap();
+    }
+    if stats.eggs_eaten > 0 {
+        writeln!(hud, "eggs eaten:  {}", stats.eggs_eaten).unwrap();
+    }
+    if stats.trap_deaths > 0 {
+        writeln!(hud, "trap deaths: {}", stats.trap_deaths).unwrap();
+    }
+    if stats.poison_eaten > 0 {
+        writeln!(
+            hud,
+            "poisoned:    {} ({} died)",
+            stats.poison_eaten, stats.poison_deaths
+        )
+        .unwrap();
+    }
+    writeln!(hud, "patchiness:  {:.2}", patchiness.0).unwrap();
+    writeln!(
+        hud,
+        "diversity:   D {:.2}, H {:.2} {}",
+        diversity.simpsons_d,
+        diversity.shannon_h,
+        sparkline(diversity.history.iter().copied())
+    )
+    .unwrap();
+    if !census.leaks.is_empty() {
+        writeln!(hud, "LEAK:        {}", census.leaks.join(", ")).unwrap();
+    }
+    text.sections[0].value = hud;
+}
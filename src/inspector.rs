@@ -0,0 +1,324 @@
+//! Clicking on an organism selects it and shows its state in a panel.
+
+use std::fmt::Write;
+
+use bevy::{prelude::*, sprite::Mesh2dHandle, window::PrimaryWindow};
+
+use crate::burrow::Burrowed;
+use crate::config::SimulationConfig;
+use crate::genes::{weight_locus, GeneInfo, Traits, N_INPUTS, N_OUTPUTS};
+use crate::hud::UI_FONT;
+use crate::organism::{
+    FastMemory, FoodEaten, FoodStash, InteractionRadius, Organism, Pregnant, SensoryInputs,
+    SlowMemory, Speed, SurvivalProbability, ORGANISM_SIZE,
+};
+use crate::{Age, Energy, Lifetime};
+
+/// How far from an organism a click still selects it.
+const SELECT_RADIUS: f32 = 20.0;
+const SELECTION_COLOR: Color = Color::rgba(1.0, 1.0, 0.4, 0.6);
+const VISION_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.08);
+const INTERACTION_COLOR: Color = Color::rgba(1.0, 0.2, 0.2, 0.5);
+
+/// The organism shown in the inspector panel.
+#[derive(Component)]
+pub struct Selected;
+
+/// Highlight drawn behind the selected organism.
+#[derive(Component)]
+pub(crate) struct SelectionRing;
+
+/// Circle showing how far the selected organism can see, toggled with V.
+#[derive(Component)]
+pub(crate) struct VisionCircle;
+
+/// Circle showing the selected organism's interaction radius on top of it,
+/// to compare with its drawn size, toggled with I.
+#[derive(Component)]
+pub(crate) struct InteractionCircle;
+
+#[derive(Component)]
+pub(crate) struct InspectorText;
+
+pub(crate) fn setup_inspector(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    commands.spawn((
+        SelectionRing,
+        Mesh2dHandle(meshes.add(shape::Circle::default().into())),
+        materials.add(ColorMaterial::from(SELECTION_COLOR)),
+        SpatialBundle {
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+    ));
+    commands.spawn((
+        VisionCircle,
+        Mesh2dHandle(meshes.add(shape::Circle::default().into())),
+        materials.add(ColorMaterial::from(VISION_COLOR)),
+        SpatialBundle {
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+    ));
+    commands.spawn((
+        InteractionCircle,
+        Mesh2dHandle(meshes.add(shape::Circle::default().into())),
+        materials.add(ColorMaterial::from(INTERACTION_COLOR)),
+        SpatialBundle {
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+    ));
+    commands.spawn((
+        InspectorText,
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: asset_server.load(UI_FONT),
+                font_size: 14.0,
+                color: Color::WHITE,
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                top: Val::Px(10.0),
+                right: Val::Px(10.0),
+                ..default()
+            },
+            ..default()
+        }),
+    ));
+}
+
+/// Selects the organism nearest to a left click, or clears the selection
+/// when there is none close enough.
+pub(crate) fn select_organism(
+    mut commands: Commands,
+    mouse: Res<Input<MouseButton>>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+    organism_query: Query<(Entity, &Transform), With<Organism>>,
+    selected_query: Query<Entity, With<Selected>>,
+) {
+    if !mouse.just_pressed(MouseButton::Left) {
+        return;
+    }
+    let Ok(window) = window_query.get_single() else {
+        return;
+    };
+    let Ok((camera, camera_transform)) = camera_query.get_single() else {
+        return;
+    };
+    let Some(cursor) = window
+        .cursor_position()
+        .and_then(|cursor| camera.viewport_to_world(camera_transform, cursor))
+        .map(|ray| ray.origin.truncate())
+    else {
+        return;
+    };
+
+    for entity in &selected_query {
+        commands.entity(entity).remove::<Selected>();
+    }
+    let nearest = organism_query
+        .iter()
+        .map(|(entity, transform)| (entity, transform.translation.truncate().distance(cursor)))
+        .filter(|(_, distance)| *distance < SELECT_RADIUS)
+        .min_by(|a, b| a.1.total_cmp(&b.1));
+    if let Some((entity, _)) = nearest {
+        commands.entity(entity).insert(Selected);
+    }
+}
+
+pub(crate) fn update_selection_ring(
+    selected_query: Query<&Transform, (With<Selected>, Without<SelectionRing>)>,
+    mut ring_query: Query<(&mut Transform, &mut Visibility), With<SelectionRing>>,
+) {
+    let Ok((mut ring_transform, mut visibility)) = ring_query.get_single_mut() else {
+        return;
+    };
+    match selected_query.get_single() {
+        Ok(transform) => {
+            ring_transform.translation = transform.translation - Vec3::Z * 0.1;
+            ring_transform.scale = transform.scale.max(ORGANISM_SIZE) * 1.8;
+            *visibility = Visibility::Visible;
+        }
+        Err(_) => *visibility = Visibility::Hidden,
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub(crate) fn update_vision_circle(
+    keys: Res<Input<KeyCode>>,
+    mut shown: Local<bool>,
+    selected_query: Query<(&Transform, &Traits), (With<Selected>, Without<VisionCircle>)>,
+    mut circle_query: Query<(&mut Transform, &mut Visibility), With<VisionCircle>>,
+) {
+    if keys.just_pressed(KeyCode::V) {
+        *shown = !*shown;
+    }
+    let Ok((mut circle_transform, mut visibility)) = circle_query.get_single_mut() else {
+        return;
+    };
+    match selected_query.get_single() {
+        Ok((transform, traits)) if *shown => {
+            circle_transform.translation = transform.translation - Vec3::Z * 0.2;
+            circle_transform.scale = Vec3::splat(traits.vision * 2.0);
+            *visibility = Visibility::Visible;
+        }
+        _ => *visibility = Visibility::Hidden,
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub(crate) fn update_interaction_circle(
+    keys: Res<Input<KeyCode>>,
+    mut shown: Local<bool>,
+    selected_query: Query<
+        (&Transform, &InteractionRadius),
+        (With<Selected>, Without<InteractionCircle>),
+    >,
+    mut circle_query: Query<(&mut Transform, &mut Visibility), With<InteractionCircle>>,
+) {
+    if keys.just_pressed(KeyCode::I) {
+        *shown = !*shown;
+    }
+    let Ok((mut circle_transform, mut visibility)) = circle_query.get_single_mut() else {
+        return;
+    };
+    match selected_query.get_single() {
+        Ok((transform, radius)) if *shown => {
+            circle_transform.translation = transform.translation + Vec3::Z * 0.1;
+            circle_transform.scale = Vec3::splat(radius.0 * 2.0);
+            *visibility = Visibility::Visible;
+        }
+        _ => *visibility = Visibility::Hidden,
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub(crate) fn update_inspector(
+    config: Res<SimulationConfig>,
+    selected_query: Query<
+        (
+            &Energy,
+            &Age,
+            &Lifetime,
+            &Speed,
+            Option<&Pregnant>,
+            &SurvivalProbability,
+            &GeneInfo,
+            &SensoryInputs,
+            &Traits,
+            &FoodStash,
+            &FoodEaten,
+            (&FastMemory, &SlowMemory),
+            &Burrowed,
+        ),
+        With<Selected>,
+    >,
+    mut text_query: Query<&mut Text, With<InspectorText>>,
+) {
+    let Ok(mut text) = text_query.get_single_mut() else {
+        return;
+    };
+    let Ok((
+        energy,
+        age,
+        lifetime,
+        speed,
+        pregnant,
+        survival,
+        gene,
+        inputs,
+        traits,
+        stash,
+        food_eaten,
+        (fast_memory, slow_memory),
+        burrowed,
+    )) = selected_query.get_single()
+    else {
+        text.sections[0].value.clear();
+        return;
+    };
+
+    let mut panel = String::new();
+    writeln!(panel, "energy:    {:.3}", energy.0).unwrap();
+    writeln!(
+        panel,
+        "stash:     {:.2} ({:.0}% stashing)",
+        stash.0,
+        100.0 * traits.stash_probability
+    )
+    .unwrap();
+    writeln!(panel, "eaten:     {}", food_eaten.0).unwrap();
+    writeln!(
+        panel,
+        "remembers: {} sightings, {} meals",
+        fast_memory.positions.len(),
+        slow_memory.positions.len()
+    )
+    .unwrap();
+    writeln!(panel, "age:       {} / {}", age.0, lifetime.0).unwrap();
+    writeln!(panel, "speed:     {:.3} / {:.3}", speed.0, traits.max_speed).unwrap();
+    writeln!(
+        panel,
+        "vision:    {:.0} deg, {:.0} far",
+        traits.vision_angle.to_degrees(),
+        traits.vision
+    )
+    .unwrap();
+    writeln!(panel, "glow:      {:.2}", traits.luminosity).unwrap();
+    writeln!(panel, "forgets:   {:.4} / tick", traits.memory_decay).unwrap();
+    writeln!(panel, "diet:      {:.0}% carnivore", 100.0 * traits.diet).unwrap();
+    writeln!(
+        panel,
+        "antenna:   {:.2} widths ahead",
+        traits.antenna_offset
+    )
+    .unwrap();
+    writeln!(
+        panel,
+        "cryptic:   {:.2}, {:.2} conspicuous",
+        traits.camouflage, traits.conspicuousness
+    )
+    .unwrap();
+    writeln!(panel, "runs:      {} ticks", traits.run_length).unwrap();
+    writeln!(panel, "swarming:  {:.2}", traits.swarm_sensing).unwrap();
+    writeln!(panel, "smell:     {:.2}x", traits.pheromone_sensitivity).unwrap();
+    writeln!(
+        panel,
+        "burrowing: {:.2}, {} ticks underground",
+        traits.burrowing, burrowed.0
+    )
+    .unwrap();
+    writeln!(panel, "pregnant:  {}", pregnant.is_some()).unwrap();
+    if let Some(pregnant) = pregnant {
+        writeln!(
+            panel,
+            "gestation: {} / {}",
+            pregnant.0, config.gestation_ticks
+        )
+        .unwrap();
+    }
+    writeln!(panel, "survival:  {:.3}", survival.0).unwrap();
+    write!(panel, "inputs:   ").unwrap();
+    for input in inputs.0 {
+        write!(panel, " {input:+.2}").unwrap();
+    }
+    writeln!(panel).unwrap();
+    writeln!(panel, "genes (bias | weights):").unwrap();
+    for output in 0..N_OUTPUTS {
+        write!(panel, "  {:+.2} |", gene.0[output]).unwrap();
+        for input in 0..N_INPUTS {
+            write!(panel, " {:+.2}", gene.0[weight_locus(output, input)]).unwrap();
+        }
+        writeln!(panel).unwrap();
+    }
+    text.sections[0].value = panel;
+}
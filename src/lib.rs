@@ -0,0 +1,150 @@
+//! An evolution simulation where organisms driven by a tiny neural network
+//! encoded in their genes compete for food.
+
+use bevy::prelude::*;
+use rand::Rng;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+pub mod behavior;
+pub mod bottleneck;
+pub mod boundary;
+pub mod burrow;
+pub mod camera;
+pub mod census;
+pub mod collision;
+pub mod community;
+pub mod config;
+pub mod diversity;
+pub mod ecosystem;
+pub mod egg;
+pub mod elite;
+pub mod energy_bar;
+pub mod food;
+pub mod food_source;
+pub mod founders;
+pub mod genealogy;
+pub mod generation;
+pub mod genes;
+pub mod genome_plot;
+pub mod heading;
+pub mod hud;
+pub mod inspector;
+pub mod library;
+pub mod luminosity;
+pub mod mirror;
+pub mod network;
+pub mod nutrient;
+pub mod obstacle;
+pub mod organism;
+pub mod patches;
+pub mod pheromone;
+pub mod plugin;
+pub mod profiler;
+pub mod reaction_diffusion;
+pub mod save;
+pub mod scenario;
+pub mod signal;
+pub mod snapshots;
+pub mod spatial;
+pub mod stats;
+pub mod swarm;
+pub mod timeline;
+pub mod topology;
+pub mod trap;
+
+pub use behavior::{
+    BehaviorArchetype, BehaviorRepertoire, RepertoireLifespan, StrategyCompetition,
+    StrategyFrequency,
+};
+pub use burrow::Burrowed;
+pub use census::Census;
+pub use community::CommunityStability;
+pub use config::{
+    CensusConfig, EggConfig, FoodDistribution, FoodSupply, FounderLayout, GenomeSnapshotConfig,
+    MovementMode, NutrientCyclingConfig, OrganismLogConfig, ReactionDiffusionConfig, SaveConfig,
+    SimulationConfig, TrapConfig, Wall, WorldTopology,
+};
+pub use diversity::{DiversityAlert, DiversityMetrics};
+pub use ecosystem::EcosystemServiceMetric;
+pub use food::{Corpse, Food, Poison};
+pub use genealogy::{Genealogy, OrganismId, ParentId};
+pub use generation::GenerationStats;
+pub use genes::{GeneInfo, GenomicIslands, Traits};
+pub use network::{NetworkTopology, PheromoneNetwork};
+pub use nutrient::SoilNutrient;
+pub use organism::{
+    BirthTick, DeathEvent, Direction, EvaluationOffset, FastMemory, Generation, LifeEvent,
+    LifeEventKind, Organism, Pregnant, RunState, SelectionAmplification, SlowMemory, Speed,
+    SurvivalProbability, TimeSinceFood, SENSORY_STAGGER_N,
+};
+pub use patches::{FoodPatch, FoodPatches};
+pub use pheromone::PheromoneField;
+pub use plugin::{headless_app, EvolutionPlugin, SimState, SimulationSpeed, VisualsPlugin};
+pub use profiler::TimestepProfiler;
+pub use reaction_diffusion::RDGrid;
+pub use save::SavedWorld;
+pub use scenario::{Assertion, AssertionResults, Scenario};
+pub use signal::VisualSignal;
+pub use spatial::SpatialGrid;
+pub use stats::SimStats;
+pub use swarm::SharedSensory;
+pub use timeline::Timeline;
+pub use trap::DeathTrap;
+
+pub const TIME_STEP: f32 = 1.0 / 60.0;
+
+#[derive(Component)]
+pub struct Energy(pub f32);
+
+#[derive(Component)]
+pub struct Age(pub usize);
+
+#[derive(Component)]
+pub struct Lifetime(pub usize);
+
+#[derive(Component)]
+pub struct Name(pub String);
+
+#[derive(Component)]
+pub struct Collider;
+
+/// Marks an entity to be despawned at the end of the fixed tick, and why.
+#[derive(Component)]
+pub struct Dead(pub DeathCause);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DeathCause {
+    /// Outlived its `Lifetime`.
+    Expired,
+    /// Energy went out of the allowed range.
+    Energy,
+    OutOfBounds,
+    Eaten,
+    /// Entered a `DeathTrap`.
+    Trap,
+    /// Killed by the bottleneck test.
+    Culled,
+    /// Eaten by a carnivore.
+    Predation,
+    /// Ate poisonous food that took its energy below the minimum.
+    Poison,
+}
+
+/// The single source of randomness for the simulation, so that runs with the
+/// same seed are reproducible.
+#[derive(Resource, Deref, DerefMut)]
+pub struct SimRng(pub ChaCha8Rng);
+
+/// Number of fixed ticks simulated so far.
+#[derive(Resource, Default)]
+pub struct SimTick(pub u64);
+
+pub fn random_position(config: &SimulationConfig, rng: &mut impl Rng) -> Vec3 {
+    let (x, y): (f32, f32) = (rng.gen(), rng.gen());
+    Vec3::new(
+        config.left_boundary + x * (config.right_boundary - config.left_boundary),
+        config.bottom_boundary + y * (config.top_boundary - config.bottom_boundary),
+        0.0,
+    )
+}
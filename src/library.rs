@@ -0,0 +1,253 @@
+//! The genome library: every distinct genome that was ever alive, browsed in
+//! a window toggled with Ctrl+G. The table can be filtered and sorted, and
+//! double clicking a genome brings three organisms with it back.
+
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use crate::config::SimulationConfig;
+use crate::genealogy::Genealogy;
+use crate::genes::{GeneInfo, GENE_LENGTH};
+use crate::obstacle::outside_obstacles;
+use crate::organism::{random_direction, DeathEvent, Organism, OrganismBundle};
+use crate::plugin::LogTimer;
+use crate::{random_position, SimRng, SimTick};
+
+/// Organisms spawned by double clicking a genome.
+const INJECTED_ORGANISMS: usize = 3;
+/// Gene values shown in the table.
+const SHOWN_GENES: usize = 5;
+const ROW_HEIGHT: f32 = 18.0;
+
+pub struct LibraryEntry {
+    pub id: usize,
+    pub gene: GeneInfo,
+    pub first_seen: u64,
+    pub last_seen: u64,
+    /// Oldest age, in age ticks, an organism with the genome died at.
+    pub max_lifespan: usize,
+}
+
+/// Every distinct genome seen so far, in the order they were first seen.
+#[derive(Resource, Default)]
+pub struct GenomeLibrary {
+    pub entries: Vec<LibraryEntry>,
+    index: HashMap<[u32; GENE_LENGTH], usize>,
+}
+
+impl GenomeLibrary {
+    /// The entry of `gene`, added first seen at `tick` if it is new.
+    fn entry(&mut self, gene: &GeneInfo, tick: u64) -> &mut LibraryEntry {
+        let key = gene.0.map(f32::to_bits);
+        let next = self.entries.len();
+        let id = *self.index.entry(key).or_insert(next);
+        if id == next {
+            self.entries.push(LibraryEntry {
+                id,
+                gene: gene.clone(),
+                first_seen: tick,
+                last_seen: tick,
+                max_lifespan: 0,
+            });
+        }
+        &mut self.entries[id]
+    }
+}
+
+/// Adds the genomes of newborn organisms to the library, marks the genomes
+/// still alive as seen on every log tick, and records how old the organisms
+/// that died got.
+pub(crate) fn update_genome_library(
+    tick: Res<SimTick>,
+    timer: Res<LogTimer>,
+    mut library: ResMut<GenomeLibrary>,
+    mut death_events: EventReader<DeathEvent>,
+    born_query: Query<&GeneInfo, Added<Organism>>,
+    alive_query: Query<&GeneInfo, With<Organism>>,
+) {
+    for gene in &born_query {
+        library.entry(gene, tick.0);
+    }
+    if timer.0.just_finished() {
+        for gene in &alive_query {
+            library.entry(gene, tick.0).last_seen = tick.0;
+        }
+    }
+    for event in death_events.iter() {
+        let entry = library.entry(&event.gene, tick.0);
+        entry.last_seen = tick.0;
+        entry.max_lifespan = entry.max_lifespan.max(event.age);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+enum Column {
+    #[default]
+    Id,
+    FirstSeen,
+    LastSeen,
+    MaxLifespan,
+    Gene(usize),
+}
+
+/// What the library window shows.
+#[derive(Default)]
+pub(crate) struct LibraryView {
+    open: bool,
+    filter: String,
+    sort: Column,
+    descending: bool,
+}
+
+fn row_text(entry: &LibraryEntry) -> String {
+    let genes: Vec<String> = entry.gene.0[..SHOWN_GENES]
+        .iter()
+        .map(|g| format!("{g:+.2}"))
+        .collect();
+    format!(
+        "{} {} {} {} {}",
+        entry.id,
+        entry.first_seen,
+        entry.last_seen,
+        entry.max_lifespan,
+        genes.join(" ")
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn genome_library_window(
+    mut commands: Commands,
+    mut contexts: EguiContexts,
+    keys: Res<Input<KeyCode>>,
+    config: Res<SimulationConfig>,
+    tick: Res<SimTick>,
+    library: Res<GenomeLibrary>,
+    mut rng: ResMut<SimRng>,
+    mut genealogy: ResMut<Genealogy>,
+    mut view: Local<LibraryView>,
+) {
+    let ctrl = keys.any_pressed([KeyCode::LControl, KeyCode::RControl]);
+    if ctrl && keys.just_pressed(KeyCode::G) {
+        view.open = !view.open;
+    }
+    if !view.open {
+        return;
+    }
+    let view = &mut *view;
+
+    let filter = view.filter.to_lowercase();
+    let mut rows: Vec<&LibraryEntry> = library
+        .entries
+        .iter()
+        .filter(|entry| filter.is_empty() || row_text(entry).to_lowercase().contains(&filter))
+        .collect();
+    rows.sort_by(|a, b| {
+        let order = match view.sort {
+            Column::Id => a.id.cmp(&b.id),
+            Column::FirstSeen => a.first_seen.cmp(&b.first_seen),
+            Column::LastSeen => a.last_seen.cmp(&b.last_seen),
+            Column::MaxLifespan => a.max_lifespan.cmp(&b.max_lifespan),
+            Column::Gene(locus) => a.gene.0[locus].total_cmp(&b.gene.0[locus]),
+        };
+        if view.descending {
+            order.reverse()
+        } else {
+            order
+        }
+    });
+
+    let mut inject = None;
+    let mut open = view.open;
+    egui::Window::new("Genome library")
+        .open(&mut open)
+        .default_size([560.0, 400.0])
+        .show(contexts.ctx_mut(), |ui| {
+            ui.horizontal(|ui| {
+                ui.label("filter:");
+                ui.text_edit_singleline(&mut view.filter);
+                ui.label(format!(
+                    "{} of {} genomes",
+                    rows.len(),
+                    library.entries.len()
+                ));
+            });
+            ui.label("double click a genome to bring back three organisms with it");
+            ui.separator();
+
+            let mut header = |ui: &mut egui::Ui, title: String, column: Column, width: f32| {
+                let arrow = match (view.sort == column, view.descending) {
+                    (true, false) => " ^",
+                    (true, true) => " v",
+                    _ => "",
+                };
+                let button = egui::Button::new(format!("{title}{arrow}")).small();
+                if ui.add_sized([width, ROW_HEIGHT], button).clicked() {
+                    view.descending = view.sort == column && !view.descending;
+                    view.sort = column;
+                }
+            };
+            ui.horizontal(|ui| {
+                header(ui, "id".into(), Column::Id, 50.0);
+                header(ui, "first".into(), Column::FirstSeen, 60.0);
+                header(ui, "last".into(), Column::LastSeen, 60.0);
+                header(ui, "lifespan".into(), Column::MaxLifespan, 60.0);
+                ui.add_sized([24.0, ROW_HEIGHT], egui::Label::new(""));
+                for locus in 0..SHOWN_GENES {
+                    header(ui, format!("g{locus}"), Column::Gene(locus), 44.0);
+                }
+            });
+
+            egui::ScrollArea::vertical().show_rows(ui, ROW_HEIGHT, rows.len(), |ui, range| {
+                for entry in &rows[range] {
+                    ui.horizontal(|ui| {
+                        let cell = |ui: &mut egui::Ui, text: String, width: f32| {
+                            ui.add_sized(
+                                [width, ROW_HEIGHT],
+                                egui::Label::new(text).sense(egui::Sense::click()),
+                            )
+                        };
+                        let mut row = cell(ui, entry.id.to_string(), 50.0);
+                        row |= cell(ui, entry.first_seen.to_string(), 60.0);
+                        row |= cell(ui, entry.last_seen.to_string(), 60.0);
+                        row |= cell(ui, entry.max_lifespan.to_string(), 60.0);
+                        let color = entry.gene.color();
+                        let (swatch, painter) =
+                            ui.allocate_painter(egui::vec2(24.0, ROW_HEIGHT), egui::Sense::click());
+                        painter.rect_filled(
+                            swatch.rect.shrink(3.0),
+                            2.0,
+                            egui::Color32::from_rgb(
+                                (color.r() * 255.0) as u8,
+                                (color.g() * 255.0) as u8,
+                                (color.b() * 255.0) as u8,
+                            ),
+                        );
+                        row |= swatch;
+                        for gene in &entry.gene.0[..SHOWN_GENES] {
+                            row |= cell(ui, format!("{gene:+.2}"), 44.0);
+                        }
+                        if row.double_clicked() {
+                            inject = Some(entry.gene.clone());
+                        }
+                    });
+                }
+            });
+        });
+    view.open = open;
+
+    let Some(gene) = inject else {
+        return;
+    };
+    // the injected organisms found lineages of their own
+    for _ in 0..INJECTED_ORGANISMS {
+        let position = outside_obstacles(&config, || random_position(&config, &mut rng.0));
+        let direction = random_direction(&mut rng.0);
+        commands.spawn((
+            OrganismBundle::new(&config, gene.clone(), position, direction, 1.0, tick.0),
+            genealogy.birth(None, tick.0),
+        ));
+    }
+    info!("injected {INJECTED_ORGANISMS} organisms from the genome library");
+}
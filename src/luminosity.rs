@@ -0,0 +1,102 @@
+//! Bioluminescence: organisms with a luminosity gene light up their
+//! surroundings, letting the organisms nearby see farther.
+
+use bevy::prelude::*;
+
+use crate::genes::{GeneInfo, Traits};
+use crate::organism::Organism;
+use crate::plugin::CircleAssets;
+
+/// Radius lit by an organism with a luminosity of 1.
+pub const LIGHT_RADIUS: f32 = 100.0;
+/// Luminosity below which an organism is considered dark.
+const MIN_LUMINOSITY: f32 = 0.05;
+
+/// How far an organism with `luminosity` lights its surroundings.
+pub fn light_radius(luminosity: f32) -> f32 {
+    LIGHT_RADIUS * luminosity
+}
+
+/// How much farther `organism` at `position` sees thanks to the other
+/// luminous organisms lighting it: its vision is multiplied by one plus the
+/// strongest luminosity reaching it.
+pub fn vision_multiplier(organism: Entity, position: Vec2, lights: &[Light]) -> f32 {
+    1.0 + lights
+        .iter()
+        .filter(|light| {
+            light.organism != organism
+                && light.position.distance(position) < light_radius(light.luminosity)
+        })
+        .map(|light| light.luminosity)
+        .fold(0.0, f32::max)
+}
+
+/// A glowing organism, as seen by the others.
+pub struct Light {
+    pub organism: Entity,
+    pub position: Vec2,
+    pub luminosity: f32,
+}
+
+/// Every organism that glows.
+pub fn lights<'a>(
+    organisms: impl Iterator<Item = (Entity, &'a Transform, &'a Traits)>,
+) -> Vec<Light> {
+    organisms
+        .filter(|(_, _, traits)| traits.luminosity >= MIN_LUMINOSITY)
+        .map(|(organism, transform, traits)| Light {
+            organism,
+            position: transform.translation.truncate(),
+            luminosity: traits.luminosity,
+        })
+        .collect()
+}
+
+/// Halo drawn around a luminous organism, following it until it dies.
+#[derive(Component)]
+pub struct BioluminescentGlow {
+    pub organism: Entity,
+}
+
+pub(crate) fn add_glow(
+    mut commands: Commands,
+    mut circles: ResMut<CircleAssets>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    organism_query: Query<(Entity, &GeneInfo, &Traits), Added<Organism>>,
+) {
+    for (organism, gene, traits) in &organism_query {
+        if traits.luminosity < MIN_LUMINOSITY {
+            continue;
+        }
+        let mut color = gene.color();
+        color.set_a(0.25 * traits.luminosity);
+        commands.spawn((
+            BioluminescentGlow { organism },
+            circles.mesh.clone(),
+            circles.material(&mut materials, color),
+            SpatialBundle {
+                transform: Transform::from_scale(Vec3::splat(
+                    2.0 * light_radius(traits.luminosity),
+                )),
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+        ));
+    }
+}
+
+pub(crate) fn update_glow(
+    mut commands: Commands,
+    mut glow_query: Query<(Entity, &BioluminescentGlow, &mut Transform, &mut Visibility)>,
+    organism_query: Query<&Transform, (With<Organism>, Without<BioluminescentGlow>)>,
+) {
+    for (entity, glow, mut transform, mut visibility) in &mut glow_query {
+        match organism_query.get(glow.organism) {
+            Ok(organism_transform) => {
+                transform.translation = organism_transform.translation - Vec3::Z * 0.3;
+                *visibility = Visibility::Visible;
+            }
+            Err(_) => commands.entity(entity).despawn(),
+        }
+    }
+}
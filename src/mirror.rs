@@ -0,0 +1,100 @@
+//! Mirror mode: the arena is split down the middle into two halves that get
+//! mirrored food and founders, so the two populations can be compared.
+
+use std::io::Write;
+
+use bevy::prelude::*;
+
+use crate::config::SimulationConfig;
+use crate::food::Food;
+use crate::genes::{GeneInfo, GENE_LENGTH};
+use crate::organism::Organism;
+use crate::plugin::LogTimer;
+use crate::SimTick;
+
+/// x coordinate of the vertical centerline of the arena.
+pub fn center_x(config: &SimulationConfig) -> f32 {
+    (config.left_boundary + config.right_boundary) / 2.0
+}
+
+/// Reflects `position` across the vertical centerline.
+pub fn mirror_position(config: &SimulationConfig, position: Vec3) -> Vec3 {
+    Vec3::new(2.0 * center_x(config) - position.x, position.y, position.z)
+}
+
+/// Moves `position` into the left half, mirroring it if needed.
+pub fn left_half(config: &SimulationConfig, position: Vec3) -> Vec3 {
+    if position.x > center_x(config) {
+        mirror_position(config, position)
+    } else {
+        position
+    }
+}
+
+/// Count and mean gene of the organisms in one half.
+fn half_stats<'a>(genes: impl Iterator<Item = &'a GeneInfo>) -> (usize, [f32; GENE_LENGTH]) {
+    let mut count = 0;
+    let mut sum = [0.0; GENE_LENGTH];
+    for gene in genes {
+        count += 1;
+        for (s, g) in sum.iter_mut().zip(gene.0) {
+            *s += g;
+        }
+    }
+    (count, sum.map(|s| s / count.max(1) as f32))
+}
+
+/// Writes per-half counts and the distance between the halves' mean genes
+/// to mirror_halves.csv, the drift the two populations show by chance.
+pub(crate) fn log_mirror_halves(
+    config: Res<SimulationConfig>,
+    tick: Res<SimTick>,
+    timer: Res<LogTimer>,
+    mut log: Local<Option<std::io::BufWriter<std::fs::File>>>,
+    organism_query: Query<(&Transform, &GeneInfo), With<Organism>>,
+    food_query: Query<&Transform, With<Food>>,
+) {
+    if !config.mirror_arena || !timer.0.just_finished() {
+        return;
+    }
+    let center = center_x(&config);
+    let (left, left_gene) = half_stats(
+        organism_query
+            .iter()
+            .filter(|(t, _)| t.translation.x < center)
+            .map(|(_, g)| g),
+    );
+    let (right, right_gene) = half_stats(
+        organism_query
+            .iter()
+            .filter(|(t, _)| t.translation.x >= center)
+            .map(|(_, g)| g),
+    );
+    let left_food = food_query
+        .iter()
+        .filter(|t| t.translation.x < center)
+        .count();
+    let right_food = food_query.iter().count() - left_food;
+    let divergence = left_gene
+        .iter()
+        .zip(right_gene)
+        .map(|(l, r)| (l - r).powi(2))
+        .sum::<f32>()
+        .sqrt();
+
+    let log = log.get_or_insert_with(|| {
+        let file = std::fs::File::create("mirror_halves.csv").unwrap();
+        let mut file = std::io::BufWriter::new(file);
+        file.write_all(
+            b"tick,left_organisms,right_organisms,left_food,right_food,gene_divergence\n",
+        )
+        .unwrap();
+        file
+    });
+    writeln!(
+        log,
+        "{},{},{},{},{},{}",
+        tick.0, left, right, left_food, right_food, divergence
+    )
+    .unwrap();
+}
@@ -0,0 +1,150 @@
+//! The pheromone communication network, in which an organism is linked to
+//! every organism that smelled its pheromones, and the shape of that
+//! network, to tell whether pheromones ended up structuring the population.
+
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::io::Write;
+
+use bevy::prelude::*;
+
+use crate::organism::Organism;
+use crate::SimTick;
+
+/// Ticks between two analyses of the network.
+const NETWORK_INTERVAL: u64 = 50;
+/// Clustering coefficient above which the network has social structure.
+pub const SOCIAL_CLUSTERING: f32 = 0.6;
+
+/// Emitter and receiver of every pheromone detected on the last sensory tick.
+#[derive(Resource, Default)]
+pub struct PheromoneNetwork {
+    pub edges: BTreeSet<(Entity, Entity)>,
+}
+
+/// Metrics of the pheromone network as of the last analysis.
+#[derive(Resource, Default)]
+pub struct NetworkTopology {
+    /// Mean number of organisms an organism's pheromones reached.
+    pub mean_degree: f32,
+    /// Mean over all organisms of the fraction of pairs of their neighbors,
+    /// ignoring directions, that are neighbors themselves.
+    pub clustering_coefficient: f32,
+    /// Mean number of links in the shortest path between two organisms, over
+    /// the pairs connected by one.
+    pub mean_path_length: f32,
+    /// Whether the clustering coefficient is above [`SOCIAL_CLUSTERING`].
+    pub social_structure: bool,
+}
+
+fn clustering_coefficient(nodes: &[Entity], neighbors: &HashMap<Entity, HashSet<Entity>>) -> f32 {
+    let mut total = 0.0;
+    for node in nodes {
+        let Some(around) = neighbors.get(node) else {
+            continue;
+        };
+        let around: Vec<Entity> = around.iter().copied().collect();
+        let k = around.len();
+        if k < 2 {
+            continue;
+        }
+        let mut links = 0;
+        for (i, a) in around.iter().enumerate() {
+            for b in &around[i + 1..] {
+                if neighbors[a].contains(b) {
+                    links += 1;
+                }
+            }
+        }
+        total += links as f32 / (k * (k - 1) / 2) as f32;
+    }
+    total / nodes.len() as f32
+}
+
+fn mean_path_length(nodes: &[Entity], successors: &HashMap<Entity, Vec<Entity>>) -> f32 {
+    let mut total = 0;
+    let mut pairs = 0;
+    for &source in nodes {
+        let mut distances = HashMap::from([(source, 0)]);
+        let mut queue = VecDeque::from([source]);
+        while let Some(node) = queue.pop_front() {
+            let distance = distances[&node];
+            for &next in successors.get(&node).into_iter().flatten() {
+                if let Entry::Vacant(entry) = distances.entry(next) {
+                    entry.insert(distance + 1);
+                    queue.push_back(next);
+                    total += distance + 1;
+                    pairs += 1;
+                }
+            }
+        }
+    }
+    if pairs > 0 {
+        total as f32 / pairs as f32
+    } else {
+        0.0
+    }
+}
+
+pub(crate) fn analyze_network(
+    tick: Res<SimTick>,
+    network: Res<PheromoneNetwork>,
+    mut topology: ResMut<NetworkTopology>,
+    mut log: Local<Option<std::io::BufWriter<std::fs::File>>>,
+    query: Query<Entity, With<Organism>>,
+) {
+    if !tick.0.is_multiple_of(NETWORK_INTERVAL) {
+        return;
+    }
+    let nodes: Vec<Entity> = query.iter().collect();
+    if nodes.is_empty() {
+        return;
+    }
+    let alive: HashSet<Entity> = nodes.iter().copied().collect();
+    let mut successors: HashMap<Entity, Vec<Entity>> = HashMap::new();
+    let mut neighbors: HashMap<Entity, HashSet<Entity>> = HashMap::new();
+    let mut edges = 0;
+    for &(from, to) in &network.edges {
+        // pheromones outlive the organisms that left them
+        if !alive.contains(&from) || !alive.contains(&to) {
+            continue;
+        }
+        edges += 1;
+        successors.entry(from).or_default().push(to);
+        neighbors.entry(from).or_default().insert(to);
+        neighbors.entry(to).or_default().insert(from);
+    }
+
+    topology.mean_degree = edges as f32 / nodes.len() as f32;
+    topology.clustering_coefficient = clustering_coefficient(&nodes, &neighbors);
+    topology.mean_path_length = mean_path_length(&nodes, &successors);
+    let social_structure = topology.clustering_coefficient > SOCIAL_CLUSTERING;
+    if social_structure && !topology.social_structure {
+        info!(
+            "SOCIAL STRUCTURE DETECTED at tick {}, clustering coefficient {:.2}",
+            tick.0, topology.clustering_coefficient
+        );
+    }
+    topology.social_structure = social_structure;
+
+    let log = log.get_or_insert_with(|| {
+        let file = std::fs::File::create("network.csv").unwrap();
+        let mut file = std::io::BufWriter::new(file);
+        file.write_all(
+            b"tick,organisms,edges,mean_degree,clustering_coefficient,mean_path_length\n",
+        )
+        .unwrap();
+        file
+    });
+    writeln!(
+        log,
+        "{},{},{},{},{},{}",
+        tick.0,
+        nodes.len(),
+        edges,
+        topology.mean_degree,
+        topology.clustering_coefficient,
+        topology.mean_path_length
+    )
+    .unwrap();
+}
@@ -0,0 +1,159 @@
+//! Nutrient cycling: the energy of dead organisms goes into the soil where
+//! they died, spreads out, and is taken up again by the food growing there,
+//! so that the places many organisms die at become the ones with most food.
+
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::config::SimulationConfig;
+use crate::organism::{InteractionRadius, Organism};
+use crate::topology::is_torus;
+use crate::{Dead, DeathCause, Energy};
+
+/// Cells along each side of the grid.
+pub const SOIL_GRID_SIDE: usize = 64;
+pub const SOIL_GRID_SIZE: usize = SOIL_GRID_SIDE * SOIL_GRID_SIDE;
+
+/// Nutrients in every cell of a grid over the arena, row by row from the
+/// bottom left corner.
+#[derive(Resource)]
+pub struct SoilNutrient(pub [f32; SOIL_GRID_SIZE]);
+
+impl Default for SoilNutrient {
+    fn default() -> Self {
+        Self([0.0; SOIL_GRID_SIZE])
+    }
+}
+
+fn cell_size(config: &SimulationConfig) -> Vec2 {
+    Vec2::new(
+        config.right_boundary - config.left_boundary,
+        config.top_boundary - config.bottom_boundary,
+    ) / SOIL_GRID_SIDE as f32
+}
+
+impl SoilNutrient {
+    /// The cell `position` is in, the nearest one when it is out of the
+    /// arena.
+    pub fn cell(config: &SimulationConfig, position: Vec2) -> usize {
+        let min = Vec2::new(config.left_boundary, config.bottom_boundary);
+        let cell = ((position - min) / cell_size(config)).floor();
+        let clamp = |c: f32| (c.max(0.0) as usize).min(SOIL_GRID_SIDE - 1);
+        clamp(cell.y) * SOIL_GRID_SIDE + clamp(cell.x)
+    }
+
+    fn center(config: &SimulationConfig, cell: usize) -> Vec2 {
+        let min = Vec2::new(config.left_boundary, config.bottom_boundary);
+        let column_row = Vec2::new(
+            (cell % SOIL_GRID_SIDE) as f32,
+            (cell / SOIL_GRID_SIDE) as f32,
+        );
+        min + (column_row + 0.5) * cell_size(config)
+    }
+
+    /// Spreads `amount` evenly over the cells whose centers are within
+    /// `radius` of `position`, or puts it all in the cell of `position`
+    /// when there are none.
+    pub fn deposit(&mut self, config: &SimulationConfig, position: Vec2, radius: f32, amount: f32) {
+        let low = Self::cell(config, position - radius);
+        let high = Self::cell(config, position + radius);
+        let cells: Vec<usize> = (low / SOIL_GRID_SIDE..=high / SOIL_GRID_SIDE)
+            .flat_map(|row| {
+                (low % SOIL_GRID_SIDE..=high % SOIL_GRID_SIDE)
+                    .map(move |column| row * SOIL_GRID_SIDE + column)
+            })
+            .filter(|&cell| Self::center(config, cell).distance(position) <= radius)
+            .collect();
+        if cells.is_empty() {
+            self.0[Self::cell(config, position)] += amount;
+            return;
+        }
+        for &cell in &cells {
+            self.0[cell] += amount / cells.len() as f32;
+        }
+    }
+
+    /// Takes up to `amount` out of the cell of `position`.
+    pub fn take(&mut self, config: &SimulationConfig, position: Vec2, amount: f32) {
+        let cell = &mut self.0[Self::cell(config, position)];
+        *cell = (*cell - amount).max(0.0);
+    }
+
+    /// A random position in the arena, picked with a probability
+    /// proportional to the nutrients there plus `background` in every cell.
+    pub fn sample_position(
+        &self,
+        config: &SimulationConfig,
+        background: f32,
+        rng: &mut impl Rng,
+    ) -> Vec3 {
+        let total: f32 = self.0.iter().map(|n| n + background).sum();
+        let mut target = rng.gen::<f32>() * total;
+        let cell = self
+            .0
+            .iter()
+            .position(|n| {
+                target -= n + background;
+                target < 0.0
+            })
+            .unwrap_or(SOIL_GRID_SIZE - 1);
+        let offset = Vec2::new(rng.gen(), rng.gen()) - 0.5;
+        (Self::center(config, cell) + offset * cell_size(config)).extend(0.0)
+    }
+}
+
+/// Returns the energy of the organisms that died this tick to the soil
+/// under them, less what carnivores took of their prey and what was left
+/// as corpse food.
+pub(crate) fn return_nutrients(
+    config: Res<SimulationConfig>,
+    mut soil: ResMut<SoilNutrient>,
+    query: Query<(&Transform, &Energy, &InteractionRadius, &Dead), With<Organism>>,
+) {
+    if config.nutrient_cycling.is_none() {
+        return;
+    }
+    for (transform, energy, radius, dead) in &query {
+        let mut amount = energy.0.max(0.0);
+        match dead.0 {
+            DeathCause::Predation => amount *= 1.0 - config.predation_efficiency,
+            DeathCause::Expired | DeathCause::Energy => {
+                amount *= 1.0 - config.corpse_energy_fraction
+            }
+            _ => {}
+        }
+        soil.deposit(&config, transform.translation.truncate(), radius.0, amount);
+    }
+}
+
+/// Spreads the nutrients of every cell to its four neighbors. Nothing
+/// flows out of the arena, except across the edges of a torus.
+pub(crate) fn diffuse_nutrients(config: Res<SimulationConfig>, mut soil: ResMut<SoilNutrient>) {
+    let Some(cycling) = &config.nutrient_cycling else {
+        return;
+    };
+    let torus = is_torus(&config);
+    let previous = soil.0;
+    let step = |i: usize, delta: isize| -> usize {
+        let moved = i as isize + delta;
+        if torus {
+            moved.rem_euclid(SOIL_GRID_SIDE as isize) as usize
+        } else {
+            // the edge cells are their own neighbors beyond the walls
+            moved.clamp(0, SOIL_GRID_SIDE as isize - 1) as usize
+        }
+    };
+    for y in 0..SOIL_GRID_SIDE {
+        for x in 0..SOIL_GRID_SIDE {
+            let here = previous[y * SOIL_GRID_SIDE + x];
+            let neighbors = [
+                previous[y * SOIL_GRID_SIDE + step(x, -1)],
+                previous[y * SOIL_GRID_SIDE + step(x, 1)],
+                previous[step(y, -1) * SOIL_GRID_SIDE + x],
+                previous[step(y, 1) * SOIL_GRID_SIDE + x],
+            ];
+            let laplacian = neighbors.iter().sum::<f32>() - 4.0 * here;
+            soil.0[y * SOIL_GRID_SIDE + x] = here + cycling.diffusion * laplacian;
+        }
+    }
+}
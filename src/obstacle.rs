@@ -0,0 +1,77 @@
+//! Rectangular obstacles inside the arena, set in the config. Organisms
+//! bounce off them like off the walls and can't see through them, and no
+//! food grows inside them.
+
+use bevy::prelude::*;
+
+use crate::collision::segment_aabb;
+use crate::config::SimulationConfig;
+
+/// Samples drawn looking for a place outside the obstacles before giving up
+/// and pushing the last one out.
+const MAX_PLACEMENT_ATTEMPTS: usize = 100;
+
+/// Center and half size of every obstacle.
+fn boxes(config: &SimulationConfig) -> impl Iterator<Item = (Vec2, Vec2)> + '_ {
+    config
+        .obstacles
+        .iter()
+        .map(|obstacle| (Vec2::from(obstacle.center), Vec2::from(obstacle.size) / 2.0))
+}
+
+pub fn in_obstacle(config: &SimulationConfig, position: Vec2) -> bool {
+    boxes(config).any(|(center, half_size)| (position - center).abs().cmple(half_size).all())
+}
+
+/// Whether an obstacle stands between `from` and `to`.
+pub fn sight_blocked(config: &SimulationConfig, from: Vec2, to: Vec2) -> bool {
+    boxes(config)
+        .any(|(center, half_size)| segment_aabb(from, to - from, center, half_size).is_some())
+}
+
+/// Distance from `position` to the nearest obstacle straight ahead along
+/// `direction`, infinite when there is none.
+pub fn obstacle_distance(config: &SimulationConfig, position: Vec2, direction: Vec2) -> f32 {
+    // no obstacle is further away than the diagonal of the arena
+    let reach = Vec2::new(
+        config.right_boundary - config.left_boundary,
+        config.top_boundary - config.bottom_boundary,
+    )
+    .length();
+    boxes(config)
+        .filter_map(|(center, half_size)| {
+            segment_aabb(position, direction * reach, center, half_size)
+        })
+        .fold(f32::INFINITY, |nearest, t| nearest.min(t * reach))
+}
+
+/// `position` moved out of the obstacles it is in through their nearest
+/// sides.
+pub fn push_out(config: &SimulationConfig, position: Vec2) -> Vec2 {
+    boxes(config).fold(position, |position, (center, half_size)| {
+        let offset = position - center;
+        let gap = half_size - offset.abs();
+        if gap.min_element() < 0.0 {
+            return position;
+        }
+        if gap.x < gap.y {
+            Vec2::new(center.x + offset.x.signum() * half_size.x, position.y)
+        } else {
+            Vec2::new(position.x, center.y + offset.y.signum() * half_size.y)
+        }
+    })
+}
+
+/// The first position from `sample` that is outside the obstacles. Draws
+/// once when there are no obstacles, so that runs without them stay the
+/// same.
+pub fn outside_obstacles(config: &SimulationConfig, mut sample: impl FnMut() -> Vec3) -> Vec3 {
+    let mut position = sample();
+    for _ in 1..MAX_PLACEMENT_ATTEMPTS {
+        if !in_obstacle(config, position.truncate()) {
+            return position;
+        }
+        position = sample();
+    }
+    push_out(config, position.truncate()).extend(position.z)
+}
@@ -0,0 +1,1917 @@
+//! Organisms: how they sense, steer, move, eat and reproduce.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::f32::consts::PI;
+use std::io::Write;
+
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::behavior::{
+    BehaviorArchetype, BehaviorRepertoire, StrategyCompetition, StrategyFrequency,
+};
+use crate::burrow::Burrowed;
+use crate::collision::{circle_aabb, circle_circle};
+use crate::config::{MovementMode, SimulationConfig};
+use crate::ecosystem::EcosystemServiceMetric;
+use crate::egg::{egg_bundle, Egg};
+use crate::food::{Corpse, Food, Poison};
+use crate::genealogy::{Genealogy, OrganismId};
+use crate::genes::{
+    AgeGeneMultiplier, GeneInfo, GenomicIslands, Mutation, Traits, GENE_LENGTH, GENE_VERSION,
+    N_INPUTS,
+};
+use crate::luminosity::{lights, vision_multiplier};
+use crate::mirror::center_x;
+use crate::network::PheromoneNetwork;
+use crate::obstacle::{obstacle_distance, push_out, sight_blocked};
+use crate::pheromone::PheromoneField;
+use crate::plugin::LogTimer;
+use crate::signal::{color_distance, VisualSignal};
+use crate::spatial::SpatialGrid;
+use crate::stats::SimStats;
+use crate::swarm::SharedSensory;
+use crate::topology::{self, displacement, is_torus, wrap};
+use crate::{Age, Collider, Dead, DeathCause, Energy, Lifetime, SimRng, SimTick, TIME_STEP};
+
+/// Drawn size of an organism with an energy of 1. The z scale is 1 so that
+/// what is drawn as its children can be layered over and under it.
+pub const ORGANISM_SIZE: Vec3 = Vec3::new(15.0, 15.0, 1.0);
+/// Interaction radius of an organism with an energy of 1.
+pub const ORGANISM_BASE_RADIUS: f32 = 7.5;
+/// Energy of a fresh food item, it decays to nothing over the food's
+/// lifetime.
+pub const FOOD_ENERGY: f32 = 0.3;
+/// Most food energy an organism can carry around.
+pub const MAX_STASH: f32 = 1.0;
+/// Number of sensory ticks every organism's network is evaluated across,
+/// each organism on one of them.
+pub const SENSORY_STAGGER_N: usize = 3;
+/// Farthest a child is born from its parent.
+const CHILD_SPREAD: f32 = 2.0 * ORGANISM_BASE_RADIUS;
+/// Most age ticks a child is born older than 1, so that siblings don't all
+/// become fertile on the same tick.
+const CHILD_AGE_JITTER: usize = 2;
+/// Fraction of their overlap two organisms move apart every tick.
+const SEPARATION_RATE: f32 = 0.1;
+const SURVIVAL_MODEL_INTERVAL: u64 = 50;
+const SURVIVAL_LEARNING_RATE: f32 = 0.05;
+
+#[derive(Component)]
+pub struct Organism;
+
+/// Inserted when the organism conceives and removed when it gives birth,
+/// with the ticks it has been pregnant for. It gives birth once they reach
+/// `gestation_ticks`.
+#[derive(Component)]
+pub struct Pregnant(pub u64);
+
+#[derive(Component, Deref, DerefMut)]
+pub struct Direction(pub Vec2);
+
+/// Ancestors up to the founder of the lineage, 0 for founders.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct Generation(pub u32);
+
+/// The sensory tick, out of every `SENSORY_STAGGER_N`, the organism's
+/// network is evaluated on. Taken round-robin by order of birth.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct EvaluationOffset(pub usize);
+
+impl EvaluationOffset {
+    pub fn from_id(id: OrganismId) -> Self {
+        Self((id.0 % SENSORY_STAGGER_N as u64) as usize)
+    }
+}
+
+/// Food energy carried around to be eaten once the organism gets hungry.
+#[derive(Component, Default)]
+pub struct FoodStash(pub f32);
+
+/// Gene of the organism that made this one pregnant, the children get a
+/// crossover of both genes. `None` for asexual pregnancies.
+#[derive(Component, Default)]
+pub struct PartnerGenes(pub Option<GeneInfo>);
+
+/// How far from its center an organism touches walls and food, independent
+/// of how big it is drawn.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct InteractionRadius(pub f32);
+
+impl InteractionRadius {
+    /// `ORGANISM_BASE_RADIUS * sqrt(energy)`, so the area the organism
+    /// covers grows linearly with its energy.
+    pub fn from_energy(energy: f32) -> Self {
+        Self(ORGANISM_BASE_RADIUS * energy.max(0.0).sqrt())
+    }
+
+    /// Drawn size of an organism with this radius.
+    pub fn scale(&self) -> Vec3 {
+        ORGANISM_SIZE * self.0 / ORGANISM_BASE_RADIUS
+    }
+}
+
+#[derive(Component)]
+pub struct Speed(pub f32);
+
+/// Predicted probability that the organism lives past its fertile age.
+#[derive(Component)]
+pub struct SurvivalProbability(pub f32);
+
+/// Fixed tick the organism was born on.
+#[derive(Component)]
+pub struct BirthTick(pub u64);
+
+pub enum CollisionEvent {
+    Wall,
+    Food,
+}
+
+/// Everything a newly born organism is made of.
+#[derive(Bundle)]
+pub struct OrganismBundle {
+    transform: Transform,
+    organism: Organism,
+    gene: GeneInfo,
+    traits: Traits,
+    age_genes: AgeGeneMultiplier,
+    lifetime: Lifetime,
+    speed: Speed,
+    energy: Energy,
+    age: Age,
+    energy_record: EnergyRecord,
+    time_since_food: TimeSinceFood,
+    food_history: FoodDetectionHistory,
+    fast_memory: FastMemory,
+    slow_memory: SlowMemory,
+    sensory_inputs: SensoryInputs,
+    brain: LastBrainState,
+    survival: SurvivalProbability,
+    direction: Direction,
+    radius: InteractionRadius,
+    partner_genes: PartnerGenes,
+    stash: FoodStash,
+    birth_tick: BirthTick,
+    signal: VisualSignal,
+    food_eaten: FoodEaten,
+    carbon: CarbonProcessed,
+    poisoned: Poisoned,
+    survivability: SurvivabilityScore,
+    repertoire: BehaviorRepertoire,
+    run: RunState,
+    generation: Generation,
+    burrowed: Burrowed,
+}
+
+impl OrganismBundle {
+    pub fn new(
+        config: &SimulationConfig,
+        gene: GeneInfo,
+        position: Vec3,
+        direction: Vec2,
+        energy: f32,
+        tick: u64,
+    ) -> Self {
+        let traits = Traits::from_gene(&gene);
+        OrganismBundle {
+            signal: VisualSignal(gene.color()),
+            age_genes: AgeGeneMultiplier::from_gene(&gene),
+            transform: Transform::from_translation(position).with_scale(ORGANISM_SIZE),
+            organism: Organism,
+            speed: Speed(config.organism_default_speed.min(traits.max_speed)),
+            lifetime: Lifetime(traits.lifetime),
+            run: RunState {
+                ticks_remaining: traits.run_length,
+                food_signal: 0.0,
+            },
+            gene,
+            traits,
+            energy: Energy(energy),
+            age: Age(1),
+            energy_record: EnergyRecord::default(),
+            time_since_food: TimeSinceFood::default(),
+            food_history: FoodDetectionHistory::default(),
+            fast_memory: FastMemory::default(),
+            slow_memory: SlowMemory::default(),
+            sensory_inputs: SensoryInputs::default(),
+            brain: LastBrainState::default(),
+            survival: SurvivalProbability(0.5),
+            direction: Direction(direction),
+            radius: InteractionRadius::from_energy(energy),
+            partner_genes: PartnerGenes::default(),
+            stash: FoodStash::default(),
+            birth_tick: BirthTick(tick),
+            food_eaten: FoodEaten::default(),
+            carbon: CarbonProcessed::default(),
+            poisoned: Poisoned::default(),
+            survivability: SurvivabilityScore::default(),
+            repertoire: BehaviorRepertoire::default(),
+            generation: Generation::default(),
+            burrowed: Burrowed::default(),
+        }
+    }
+}
+
+/// Where the organism is in its current run in run-and-tumble mode.
+#[derive(Component, Clone, Debug, Default)]
+pub struct RunState {
+    /// Ticks left before the organism tumbles.
+    pub ticks_remaining: usize,
+    /// Total food sensed when the run started.
+    pub food_signal: f32,
+}
+
+/// The inputs given to the organism's network on the last sensory tick.
+#[derive(Component, Default)]
+pub struct SensoryInputs(pub [f32; N_INPUTS]);
+
+/// Exponentially smoothed turn and speed outputs of the organism's network,
+/// see `steering_smoothing`.
+#[derive(Component, Default)]
+pub struct LastBrainState {
+    pub turn: f32,
+    pub speed: f32,
+}
+
+impl LastBrainState {
+    /// Blends new `turn` and `speed` outputs into the smoothed ones, the old
+    /// ones weighing `smoothing`, and returns what to steer by. At 0 the new
+    /// outputs are returned as they are.
+    pub fn smooth(&mut self, smoothing: f32, turn: f32, speed: f32) -> (f32, f32) {
+        if smoothing > 0.0 {
+            self.turn = smoothing * self.turn + (1.0 - smoothing) * turn;
+            self.speed = smoothing * self.speed + (1.0 - smoothing) * speed;
+            (self.turn, self.speed)
+        } else {
+            (turn, speed)
+        }
+    }
+}
+
+/// Simulation ticks after which the founders' memory of a food sighting has
+/// faded to `1 / e`, see `Traits::memory_decay`.
+pub const MEMORY_DECAY_TICKS: u64 = 600;
+
+/// Tick on which food was last seen in each sector of the vision cone.
+#[derive(Component, Default)]
+pub struct FoodDetectionHistory {
+    pub left: u64,
+    pub front: u64,
+    pub right: u64,
+}
+
+impl FoodDetectionHistory {
+    /// Remembers `tick` for every sector that sees some food.
+    pub fn record(&mut self, foods: &[f32; 3], tick: u64) {
+        for (last, food) in [&mut self.left, &mut self.front, &mut self.right]
+            .into_iter()
+            .zip(foods)
+        {
+            if *food > 0.0 {
+                *last = tick;
+            }
+        }
+    }
+
+    /// How strongly each sector's last sighting is still remembered, from 1
+    /// for right now fading exponentially at `decay` per tick.
+    pub fn weights(&self, tick: u64, decay: f32) -> [f32; 3] {
+        [self.left, self.front, self.right]
+            .map(|last| (-decay * tick.saturating_sub(last) as f32).exp())
+    }
+
+    /// How far each sector's last sighting has faded, 0 for right now and
+    /// close to 1 once forgotten.
+    pub fn recency(&self, tick: u64, decay: f32) -> [f32; 3] {
+        self.weights(tick, decay).map(|weight| 1.0 - weight)
+    }
+}
+
+/// Food sightings kept in the fast memory.
+pub const FAST_MEMORY_CAPACITY: usize = 3;
+/// Meals kept in the slow memory.
+pub const SLOW_MEMORY_CAPACITY: usize = 10;
+
+/// Where the organism saw the nearest food on its last sensory ticks, the
+/// latest at the back, forgotten as soon as newer sightings come in.
+#[derive(Component, Clone, Debug)]
+pub struct FastMemory {
+    pub positions: VecDeque<Vec3>,
+    pub capacity: usize,
+}
+
+impl Default for FastMemory {
+    fn default() -> Self {
+        Self {
+            positions: VecDeque::with_capacity(FAST_MEMORY_CAPACITY),
+            capacity: FAST_MEMORY_CAPACITY,
+        }
+    }
+}
+
+impl FastMemory {
+    /// Remembers food seen at `position`, unless it is the food seen last.
+    pub fn remember(&mut self, position: Vec3) {
+        if self.positions.back() == Some(&position) {
+            return;
+        }
+        if self.positions.len() == self.capacity {
+            self.positions.pop_front();
+        }
+        self.positions.push_back(position);
+    }
+}
+
+/// Where the organism ate, the oldest meal forgotten first once full. Only
+/// updated on food contact, so it outlasts the fast memory by far.
+#[derive(Component, Clone, Debug)]
+pub struct SlowMemory {
+    pub positions: Vec<Vec3>,
+    pub capacity: usize,
+}
+
+impl Default for SlowMemory {
+    fn default() -> Self {
+        Self {
+            positions: Vec::with_capacity(SLOW_MEMORY_CAPACITY),
+            capacity: SLOW_MEMORY_CAPACITY,
+        }
+    }
+}
+
+impl SlowMemory {
+    pub fn remember(&mut self, position: Vec3) {
+        if self.positions.len() == self.capacity {
+            self.positions.remove(0);
+        }
+        self.positions.push(position);
+    }
+}
+
+/// Direction and distance inputs for the remembered position nearest to
+/// `origin`: the angle to it from `direction` over pi, positive when it is
+/// counterclockwise, and its distance over `range` up to 1. 0 and 1 when
+/// nothing is remembered.
+fn memory_inputs<'a>(
+    config: &SimulationConfig,
+    origin: Vec3,
+    direction: Vec2,
+    range: f32,
+    positions: impl Iterator<Item = &'a Vec3>,
+) -> [f32; 2] {
+    positions
+        .map(|&position| displacement(config, origin, position).truncate())
+        .min_by(|a, b| a.length().total_cmp(&b.length()))
+        .map_or([0.0, 1.0], |to_memory| {
+            let angle = if to_memory == Vec2::ZERO {
+                0.0
+            } else {
+                direction.angle_between(to_memory)
+            };
+            [angle / PI, (to_memory.length() / range).min(1.0)]
+        })
+}
+
+/// Integral of `energy * speed` over the organism's life so far, a measure
+/// of how well it did over its whole life rather than at one moment.
+#[derive(Component, Default)]
+pub struct SurvivabilityScore(pub f32);
+
+/// Food items the organism ate or stashed over its life.
+#[derive(Component, Default)]
+pub struct FoodEaten(pub u32);
+
+/// Energy the organism absorbed from food and prey over its life, less the
+/// share of what it passed to its offspring that is expected to be wasted
+/// on ones dying before their fertile age.
+#[derive(Component, Default)]
+pub struct CarbonProcessed(pub f32);
+
+/// Tick the organism last ate poisonous food on, to tell the deaths the
+/// poison caused from starvation.
+#[derive(Component, Default)]
+pub struct Poisoned(pub Option<u64>);
+
+/// Age ticks since the organism last ate.
+#[derive(Component, Default)]
+pub struct TimeSinceFood(pub usize);
+
+/// Sum of the organism's energy sampled on every age tick.
+#[derive(Component, Default)]
+pub struct EnergyRecord(pub f32);
+
+impl EnergyRecord {
+    /// Mean energy over a life that lasted `age` age ticks.
+    pub fn mean(&self, age: usize) -> f32 {
+        self.0 / age.max(1) as f32
+    }
+}
+
+/// Sent whenever an organism is despawned, by `reap_dead`.
+pub struct DeathEvent {
+    pub organism: Entity,
+    pub id: OrganismId,
+    pub cause: DeathCause,
+    pub gene: GeneInfo,
+    pub age: usize,
+    pub mean_energy: f32,
+    /// Energy left when the organism died.
+    pub energy: f32,
+    pub food_eaten: u32,
+    pub survivability: f32,
+    /// Number of distinct behavior archetypes followed over the life.
+    pub repertoire: usize,
+    pub carbon_processed: f32,
+    /// Ancestors up to the founder of the lineage, 0 for founders.
+    pub generation: usize,
+}
+
+impl DeathEvent {
+    /// Carbon processed per age tick of the life, see
+    /// `ecosystem::EcosystemServiceMetric`.
+    pub fn ecosystem_service(&self) -> f32 {
+        self.carbon_processed / self.age.max(1) as f32
+    }
+}
+
+/// Something notable that happened to a living organism, see
+/// `timeline::Timeline`.
+pub struct LifeEvent {
+    pub organism: Entity,
+    pub tick: u64,
+    pub kind: LifeEventKind,
+}
+
+/// `Born` and `Died` are never sent, the timeline takes them from
+/// `BirthTick` and the organism being gone.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LifeEventKind {
+    Born,
+    Ate,
+    /// Got pregnant.
+    Conceived,
+    /// Made another organism pregnant.
+    Mated,
+    /// Gave birth to this many children.
+    GaveBirth(usize),
+    /// Laid this many eggs.
+    LaidEggs(usize),
+    Died,
+}
+
+const MUTATION_LOG_FILE: &str = "mutations.csv";
+/// Written next to mutations.csv with the rates the mutations were drawn
+/// with.
+const MUTATION_PROVENANCE_FILE: &str = "mutations.json";
+
+/// A mutation of a child born on `tick`, as logged to mutations.csv.
+#[derive(Clone, Copy)]
+struct MutationEvent {
+    tick: u64,
+    parent: OrganismId,
+    child: OrganismId,
+    mutation: Mutation,
+}
+
+/// The mutations of the births since the last log tick, written to
+/// mutations.csv when `log_mutations` is on. At most `mutation_log_capacity`
+/// of them are kept, a burst of births pushes the oldest out.
+#[derive(Resource, Default)]
+pub(crate) struct MutationLog {
+    /// Mutations of the child being born.
+    pub(crate) mutations: Vec<Mutation>,
+    events: VecDeque<MutationEvent>,
+    /// Events pushed out since the last write.
+    dropped: u64,
+    file: Option<std::io::BufWriter<std::fs::File>>,
+}
+
+impl MutationLog {
+    /// Moves the mutations of the child being born to the buffer.
+    pub(crate) fn record(
+        &mut self,
+        capacity: usize,
+        tick: u64,
+        parent: OrganismId,
+        child: OrganismId,
+    ) {
+        for mutation in self.mutations.drain(..) {
+            if self.events.len() >= capacity {
+                self.events.pop_front();
+                self.dropped += 1;
+            }
+            self.events.push_back(MutationEvent {
+                tick,
+                parent,
+                child,
+                mutation,
+            });
+        }
+    }
+
+    /// Appends the buffered mutations to mutations.csv, creating it and its
+    /// provenance the first time.
+    fn write(&mut self, config: &SimulationConfig) -> std::io::Result<()> {
+        if self.file.is_none() {
+            let provenance = serde_json::json!({
+                "gene_version": GENE_VERSION,
+                "mutation_rates": config.mutation_rates(),
+                "mutation_log_capacity": config.mutation_log_capacity,
+            });
+            let file = std::fs::File::create(MUTATION_PROVENANCE_FILE)?;
+            serde_json::to_writer_pretty(std::io::BufWriter::new(file), &provenance)?;
+            let file = std::fs::File::create(MUTATION_LOG_FILE)?;
+            let mut file = std::io::BufWriter::new(file);
+            file.write_all(b"gene_version,tick,parent,child,locus,old,new\n")?;
+            self.file = Some(file);
+        }
+        let Some(file) = &mut self.file else {
+            unreachable!("the file was opened above");
+        };
+        for event in self.events.drain(..) {
+            writeln!(
+                file,
+                "{},{},{},{},{},{},{}",
+                GENE_VERSION,
+                event.tick,
+                event.parent.0,
+                event.child.0,
+                event.mutation.locus,
+                event.mutation.old,
+                event.mutation.new
+            )?;
+        }
+        file.flush()
+    }
+}
+
+/// Writes the buffered mutations every log tick. Failing to write is
+/// logged and the mutations are dropped, the simulation goes on.
+pub(crate) fn write_mutation_log(
+    config: Res<SimulationConfig>,
+    timer: Res<LogTimer>,
+    mut log: ResMut<MutationLog>,
+) {
+    if !config.log_mutations || !timer.0.just_finished() {
+        return;
+    }
+    if log.dropped > 0 {
+        warn!(
+            "dropped {} mutations over mutation_log_capacity since the last write",
+            log.dropped
+        );
+        log.dropped = 0;
+    }
+    if let Err(e) = log.write(&config) {
+        error!("could not write {MUTATION_LOG_FILE}: {e}");
+        log.events.clear();
+    }
+}
+
+/// Logistic regression from gene values to surviving past the fertile age,
+/// trained online from every organism death.
+#[derive(Resource)]
+pub(crate) struct SurvivalModel {
+    pub(crate) weights: [f32; GENE_LENGTH],
+    pub(crate) bias: f32,
+}
+
+impl Default for SurvivalModel {
+    fn default() -> Self {
+        Self {
+            weights: [0.0; GENE_LENGTH],
+            bias: 0.0,
+        }
+    }
+}
+
+impl SurvivalModel {
+    fn predict(&self, gene: &GeneInfo) -> f32 {
+        let z = self.bias
+            + self
+                .weights
+                .iter()
+                .zip(gene.0.iter())
+                .map(|(w, g)| w * g)
+                .sum::<f32>();
+        1.0 / (1.0 + (-z).exp())
+    }
+
+    fn train(&mut self, gene: &GeneInfo, survived: bool) {
+        let target = if survived { 1.0 } else { 0.0 };
+        let error = target - self.predict(gene);
+        for (w, g) in self.weights.iter_mut().zip(gene.0.iter()) {
+            *w += SURVIVAL_LEARNING_RATE * error * g;
+        }
+        self.bias += SURVIVAL_LEARNING_RATE * error;
+    }
+}
+
+/// How much steeper selection is than normal, 1 for not at all. Organisms
+/// that eat get pregnant with probability `p ^ (1 / amplification)`
+/// instead of `p`, so the better foragers breed away from the rest faster.
+/// Nothing dies by chance, so there is no death probability to amplify.
+#[derive(Resource)]
+pub struct SelectionAmplification(pub f32);
+
+impl Default for SelectionAmplification {
+    fn default() -> Self {
+        Self(1.0)
+    }
+}
+
+impl SelectionAmplification {
+    pub fn pregnancy(&self, probability: f32) -> f32 {
+        probability.powf(1.0 / self.0)
+    }
+}
+
+/// Fires `SENSORY_STAGGER_N` times per sensory period, with the number of
+/// times it fired so far.
+#[derive(Resource)]
+pub(crate) struct SensoryTimer(pub(crate) Timer, pub(crate) u64);
+
+pub fn random_direction(rng: &mut impl Rng) -> Vec2 {
+    let (x, y): (f32, f32) = (rng.gen(), rng.gen());
+    let v = Vec2::new(x - 0.5, y - 0.5);
+    v / v.length()
+}
+
+/// Distance from `position` to the wall or obstacle straight ahead along
+/// `direction`, the centerline counting as a wall in a mirrored arena. Negative, the
+/// distance back to the arena, when `position` is out of it.
+pub fn wall_distance(config: &SimulationConfig, position: Vec2, direction: Vec2) -> f32 {
+    let min = Vec2::new(config.left_boundary, config.bottom_boundary);
+    let max = Vec2::new(config.right_boundary, config.top_boundary);
+    let outside = (min - position).max(position - max).max(Vec2::ZERO);
+    if outside != Vec2::ZERO {
+        return -outside.length();
+    }
+    let mut walls = vec![
+        (min.x, max.x, direction.x, position.x),
+        (min.y, max.y, direction.y, position.y),
+    ];
+    if config.mirror_arena {
+        let center = center_x(config);
+        if position.x < center {
+            walls.push((f32::MIN, center, direction.x, position.x));
+        } else {
+            walls.push((center, f32::MAX, direction.x, position.x));
+        }
+    }
+    walls
+        .into_iter()
+        .filter_map(|(low, high, d, p)| {
+            if d > 0.0 {
+                Some((high - p) / d)
+            } else if d < 0.0 {
+                Some((low - p) / d)
+            } else {
+                None
+            }
+        })
+        .fold(obstacle_distance(config, position, direction), f32::min)
+}
+
+/// `position` brought back into the arena, out of the obstacles and, in a
+/// mirrored arena, onto the half `home` is on.
+fn confine(config: &SimulationConfig, position: Vec2, home: Vec2) -> Vec2 {
+    if is_torus(config) {
+        return push_out(config, wrap(config, position.extend(0.0)).truncate());
+    }
+    let mut min = Vec2::new(config.left_boundary, config.bottom_boundary);
+    let mut max = Vec2::new(config.right_boundary, config.top_boundary);
+    if config.mirror_arena {
+        // clear of the wall down the centerline
+        let center = center_x(config);
+        let half_wall = config.boundary_thickness / 2.0;
+        if home.x < center {
+            max.x = center - half_wall;
+        } else {
+            min.x = center + half_wall;
+        }
+    }
+    push_out(config, position.clamp(min, max))
+}
+
+/// A random spot within `CHILD_SPREAD` of `parent` to spawn a child at,
+/// never outside the arena even when the parent is against a wall.
+pub(crate) fn child_position(config: &SimulationConfig, rng: &mut impl Rng, parent: Vec3) -> Vec3 {
+    let offset =
+        Vec2::from_angle(rng.gen_range(0.0..2.0 * PI)) * CHILD_SPREAD * rng.gen::<f32>().sqrt();
+    let home = parent.truncate();
+    confine(config, home + offset, home).extend(parent.z)
+}
+
+/// The age a child is born at.
+pub(crate) fn child_age(rng: &mut impl Rng) -> Age {
+    Age(1 + rng.gen_range(0..=CHILD_AGE_JITTER))
+}
+
+/// Turns `direction` clockwise by `angle` radians, keeping it a unit vector.
+/// A direction too short to have a heading is left as it is.
+pub fn rotate_direction(direction: &mut Vec2, angle: f32) {
+    if let Some(rotated) = Vec2::from_angle(-angle).rotate(*direction).try_normalize() {
+        *direction = rotated;
+    }
+}
+
+pub(crate) fn _align_direction(direction: &mut Vec2, delta: &Vec2) {
+    let angle = direction.angle_between(*delta);
+    if !(0.5..=5.7).contains(&angle) {
+        let r = delta.length();
+        direction.x = delta.x / r;
+        direction.y = delta.y / r;
+    } else {
+        rotate_direction(direction, angle.clamp(-0.5, 0.5))
+    }
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub(crate) fn adjust_direction(
+    config: Res<SimulationConfig>,
+    fixed_time: Res<FixedTime>,
+    tick: Res<SimTick>,
+    mut timer: ResMut<SensoryTimer>,
+    mut organism_query: Query<
+        (
+            Entity,
+            &Transform,
+            &mut Direction,
+            &mut Speed,
+            &mut Energy,
+            &Age,
+            &Lifetime,
+            &GeneInfo,
+            &TimeSinceFood,
+            &mut FoodDetectionHistory,
+            (&mut FastMemory, &SlowMemory),
+            &mut SensoryInputs,
+            &mut LastBrainState,
+            (&Traits, &AgeGeneMultiplier, &EvaluationOffset),
+        ),
+        With<Organism>,
+    >,
+    food_query: Query<(&Transform, Option<&Poison>), With<Food>>,
+    neighbor_query: Query<(Entity, &Transform, &Traits), With<Organism>>,
+    burrowed_query: Query<&Burrowed>,
+    grid: Res<SpatialGrid>,
+    mut field: ResMut<PheromoneField>,
+    mut network: ResMut<PheromoneNetwork>,
+    shared: Res<SharedSensory>,
+) {
+    if timer.0.tick(fixed_time.period).just_finished() {
+        // only the organisms on this offset are evaluated, the network
+        // gathers the edges of a full round of them
+        let phase = (timer.1 % SENSORY_STAGGER_N as u64) as usize;
+        timer.1 += 1;
+        if phase == 0 {
+            network.edges.clear();
+        }
+        let lights = lights(neighbor_query.iter());
+        // emitted once every organism sensed the field as it was
+        let mut emissions = Vec::new();
+        for (
+            organism,
+            transform,
+            mut direction,
+            mut speed,
+            mut energy,
+            age,
+            lifetime,
+            gene,
+            time_since_food,
+            mut food_history,
+            (mut fast_memory, slow_memory),
+            mut sensory_inputs,
+            mut brain,
+            (traits, age_genes, offset),
+        ) in &mut organism_query
+        {
+            if offset.0 != phase {
+                continue;
+            }
+            // luminous organisms nearby light up the food around
+            let vision = traits.vision
+                * vision_multiplier(organism, transform.translation.truncate(), &lights);
+            // the vision sector (left, front or right) `target` is seen in
+            // from `origin`, and how strongly
+            let sense_from = |origin: Vec3, target: Vec3| -> Option<(usize, f32)> {
+                // the halves of a mirrored arena can't see each other
+                if config.mirror_arena
+                    && (target.x < center_x(&config)) != (origin.x < center_x(&config))
+                {
+                    return None;
+                }
+                let dir = displacement(&config, origin, target).truncate();
+                let dist = dir.length();
+                if dist >= vision {
+                    return None;
+                }
+                let alpha = dir.angle_between(**direction);
+                // things straight ahead are always seen, the side sectors
+                // widen with the vision angle
+                let half_angle = traits.vision_angle / 2.0;
+                let sector = if alpha > -0.1 && alpha < 0.1 {
+                    1
+                } else if alpha < half_angle && alpha > 0.1 {
+                    0
+                } else if alpha > -half_angle && alpha < -0.1 {
+                    2
+                } else {
+                    return None;
+                };
+                // nothing is seen through obstacles
+                if sight_blocked(&config, origin.truncate(), origin.truncate() + dir) {
+                    return None;
+                }
+                Some((sector, (vision * 0.5) / (vision + dist)))
+            };
+            let sense = |target: Vec3| sense_from(transform.translation, target);
+            // food is detected at the tip of the antenna, ahead of the body
+            let reach = traits.antenna_offset * ORGANISM_SIZE.x;
+            let antenna = transform.translation + direction.extend(0.0) * reach;
+            let in_sight = topology::query_circle(
+                &config,
+                &grid,
+                transform.translation.truncate(),
+                vision + reach,
+            );
+            // poisonous food looks like any other, only the fraction of it
+            // in every sector is sensed apart
+            let mut foods: [f32; 3] = [0.0, 0.0, 0.0];
+            let mut poisons: [f32; 3] = [0.0, 0.0, 0.0];
+            let mut nearest_food = None;
+            for (food_transform, poison) in food_query.iter_many(&in_sight) {
+                if let Some((sector, value)) = sense_from(antenna, food_transform.translation) {
+                    foods[sector] += value;
+                    if poison.is_some() {
+                        poisons[sector] += value;
+                        continue;
+                    }
+                    // the closer the food the stronger it is sensed
+                    if nearest_food.is_none_or(|(strongest, _)| value > strongest) {
+                        nearest_food = Some((value, food_transform.translation));
+                    }
+                }
+            }
+            let poison_fraction = |sector: usize| {
+                if foods[sector] > 0.0 {
+                    poisons[sector] / foods[sector]
+                } else {
+                    0.0
+                }
+            };
+            if let Some((_, position)) = nearest_food {
+                fast_memory.remember(position);
+            }
+            let origin = transform.translation;
+            let [fast_direction, fast_distance] = memory_inputs(
+                &config,
+                origin,
+                **direction,
+                vision,
+                fast_memory.positions.iter(),
+            );
+            let [slow_direction, slow_distance] = memory_inputs(
+                &config,
+                origin,
+                **direction,
+                vision,
+                slow_memory.positions.iter(),
+            );
+            // the pheromone of a cell counts as much as there is left of it,
+            // and the closer its color is to the organism's own the more it
+            // tells kin have been around
+            let color = gene.color();
+            let mut scent: [f32; 3] = [0.0, 0.0, 0.0];
+            let mut similarity = 0.0;
+            for cell in field.cells_around(&config, transform.translation.truncate(), vision) {
+                let center = field.center(&config, cell).extend(0.0);
+                if let Some((sector, value)) = sense(center) {
+                    if let Some(emitter) = field.emitters[cell] {
+                        if emitter != organism {
+                            network.edges.insert((emitter, organism));
+                        }
+                    }
+                    let intensity = field.cells[cell][0];
+                    scent[sector] += value * intensity;
+                    similarity += value * intensity * color_similarity(color, field.color(cell));
+                }
+            }
+            let total_scent: f32 = scent.iter().sum();
+            if total_scent > 0.0 {
+                similarity /= total_scent;
+            }
+            // carnivores barely notice camouflaged organisms, and not at
+            // all those underground
+            let carnivore = traits.diet >= config.carnivore_threshold;
+            let mut density: [f32; 3] = [0.0, 0.0, 0.0];
+            for (neighbor, neighbor_transform, neighbor_traits) in
+                neighbor_query.iter_many(&in_sight)
+            {
+                if neighbor == organism
+                    || (carnivore
+                        && burrowed_query
+                            .get(neighbor)
+                            .is_ok_and(|burrowed| burrowed.underground()))
+                {
+                    continue;
+                }
+                if let Some((sector, value)) = sense(neighbor_transform.translation) {
+                    let visibility = if carnivore {
+                        1.0 - neighbor_traits.camouflage
+                    } else {
+                        1.0
+                    };
+                    density[sector] += value * visibility;
+                }
+            }
+
+            food_history.record(&foods, tick.0);
+            let recency = food_history.recency(tick.0, traits.memory_decay);
+
+            let x_pos = transform.translation.x;
+            let y_pos = transform.translation.y;
+            let x_pos =
+                (x_pos - config.left_boundary) / (config.right_boundary - config.left_boundary);
+            let y_pos =
+                (y_pos - config.bottom_boundary) / (config.top_boundary - config.bottom_boundary);
+            // a torus has no walls to head to
+            let heading_out = !is_torus(&config)
+                && ((x_pos < 0.1 && direction.x < 0.0)
+                    || (x_pos > 0.9 && direction.x > 0.0)
+                    || (y_pos < 0.1 && direction.y < 0.0)
+                    || (y_pos > 0.9 && direction.y > 0.0));
+            let wall = if is_torus(&config) {
+                obstacle_distance(&config, transform.translation.truncate(), **direction)
+            } else {
+                wall_distance(&config, transform.translation.truncate(), **direction)
+            };
+            let age_fraction = (age.0 as f32 / lifetime.0 as f32).min(1.0);
+            let inputs: [f32; N_INPUTS] = [
+                speed.0 / traits.max_speed,
+                x_pos,
+                y_pos,
+                (energy.0 - config.organism_min_energy)
+                    / (config.organism_max_energy - config.organism_min_energy),
+                age_fraction,
+                foods[0].clamp(0.0, 1.0),
+                foods[1].clamp(0.0, 1.0),
+                foods[2].clamp(0.0, 1.0),
+                (time_since_food.0 as f32 / config.fertile_age as f32).min(1.0),
+                recency[0],
+                recency[1],
+                recency[2],
+                density[0].min(1.0),
+                density[1].min(1.0),
+                density[2].min(1.0),
+                (scent[0] * traits.pheromone_sensitivity).min(1.0),
+                (scent[1] * traits.pheromone_sensitivity).min(1.0),
+                (scent[2] * traits.pheromone_sensitivity).min(1.0),
+                similarity,
+                (wall / vision).clamp(-1.0, 1.0),
+                if heading_out { 1.0 } else { 0.0 },
+                fast_direction,
+                fast_distance,
+                slow_direction,
+                slow_distance,
+                poison_fraction(0),
+                poison_fraction(1),
+                poison_fraction(2),
+                shared.pool(organism) * traits.swarm_sensing,
+            ];
+            let output = gene.process(age_genes, age_fraction, &inputs);
+            sensory_inputs.0 = inputs;
+            let (turn, speed_change) =
+                brain.smooth(config.steering_smoothing, output[0], output[1]);
+            if config.movement == MovementMode::Steering {
+                rotate_direction(&mut direction, turn);
+            }
+            speed.0 = (speed.0 + speed_change).clamp(0.0, traits.max_speed);
+
+            // stronger pheromones cost more
+            let intensity = output[3];
+            if intensity > config.pheromone_threshold {
+                emissions.push((transform.translation.truncate(), color, intensity, organism));
+                energy.0 -= intensity * config.pheromone_energy_cost;
+            }
+        }
+        for (position, color, intensity, emitter) in emissions {
+            field.emit(&config, position, color, intensity, emitter);
+        }
+    }
+}
+
+/// In run-and-tumble mode organisms keep their direction until their run is
+/// over, then tumble to a random direction. Like bacteria following a
+/// gradient, they run on instead when they sense more food than when the
+/// run started.
+pub(crate) fn run_and_tumble(
+    config: Res<SimulationConfig>,
+    mut rng: ResMut<SimRng>,
+    mut query: Query<(&mut Direction, &mut RunState, &Traits, &SensoryInputs), With<Organism>>,
+) {
+    if config.movement != MovementMode::RunAndTumble {
+        return;
+    }
+    for (mut direction, mut run, traits, inputs) in &mut query {
+        if run.ticks_remaining > 0 {
+            run.ticks_remaining -= 1;
+            continue;
+        }
+        // food in the left, front and right sectors
+        let food: f32 = inputs.0[5..8].iter().sum();
+        if food <= run.food_signal {
+            **direction = random_direction(&mut rng.0);
+        }
+        run.ticks_remaining = traits.run_length;
+        run.food_signal = food;
+    }
+}
+
+pub(crate) fn apply_direction(
+    mut commands: Commands,
+    config: Res<SimulationConfig>,
+    mut query: Query<(
+        Entity,
+        &mut Transform,
+        &Direction,
+        &Speed,
+        &mut Energy,
+        &Traits,
+    )>,
+) {
+    let torus = is_torus(&config);
+    for (entity, mut transform, direction, speed, mut energy, traits) in &mut query {
+        if !torus
+            && (transform.translation.x < config.left_boundary
+                || transform.translation.x > config.right_boundary
+                || transform.translation.y < config.bottom_boundary
+                || transform.translation.y > config.top_boundary)
+        {
+            commands
+                .entity(entity)
+                .insert(Dead(DeathCause::OutOfBounds));
+            continue;
+        }
+        let delta = **direction * speed.0 * TIME_STEP * config.simulation_speed;
+        // low energy organisms can't move far no matter how fast they want to go
+        let delta = delta.clamp_length_max(energy.0.max(0.0) * config.max_speed_energy_coefficient);
+
+        transform.translation.x += delta.x;
+        transform.translation.y += delta.y;
+        if torus {
+            transform.translation = wrap(&config, transform.translation);
+        }
+
+        // propotional energy consumption based on size, longer lived organisms
+        // burn a bit more
+        let lifetime_ratio = traits.lifetime as f32 / config.organism_default_lifetime as f32;
+        energy.0 *= 1.0 - 0.001 * lifetime_ratio.sqrt();
+        // energy comsumption based on speed, organisms built to be faster
+        // pay more for the same distance
+        energy.0 -=
+            speed.0.powi(2) * (traits.max_speed / config.organism_default_speed) / 50000000.0;
+        // wider vision costs more
+        energy.0 -= traits.vision_angle * config.vision_angle_energy_cost;
+        energy.0 -= traits.vision * config.vision_range_energy_cost;
+        energy.0 -= traits.luminosity.powi(2) * config.luminosity_energy_cost;
+        energy.0 -= traits.diet * config.carnivore_metabolism;
+    }
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub(crate) fn grow_organism(
+    mut commands: Commands,
+    config: Res<SimulationConfig>,
+    mut organism_query: Query<
+        (
+            Entity,
+            &Transform,
+            &GeneInfo,
+            &mut Energy,
+            Option<&mut Pregnant>,
+            &mut PartnerGenes,
+            &OrganismId,
+            &mut CarbonProcessed,
+            &Poisoned,
+            &Generation,
+        ),
+        With<Organism>,
+    >,
+    mut rng: ResMut<SimRng>,
+    tick: Res<SimTick>,
+    islands: Res<GenomicIslands>,
+    ecosystem: Res<EcosystemServiceMetric>,
+    mut genealogy: ResMut<Genealogy>,
+    mut mutation_log: ResMut<MutationLog>,
+    mut stats: ResMut<SimStats>,
+    mut life_events: EventWriter<LifeEvent>,
+) {
+    let rates = config.mutation_rates();
+    for (
+        organism,
+        organism_transform,
+        gene_info,
+        mut organism_energy,
+        organism_pregnant,
+        mut partner_genes,
+        id,
+        mut carbon,
+        poisoned,
+        generation,
+    ) in &mut organism_query
+    {
+        if organism_energy.0 < config.organism_min_energy && poisoned.0 == Some(tick.0) {
+            commands.entity(organism).insert(Dead(DeathCause::Poison));
+        } else if organism_energy.0 < config.organism_min_energy
+            || organism_energy.0 > config.organism_max_energy
+        {
+            commands.entity(organism).insert(Dead(DeathCause::Energy));
+        } else if let Some(mut pregnant) = organism_pregnant {
+            if pregnant.0 < config.gestation_ticks {
+                pregnant.0 += 1;
+                continue;
+            }
+            commands.entity(organism).remove::<Pregnant>();
+            let partner = partner_genes.0.take();
+            let child_gene = |rng: &mut SimRng, mutations: Option<&mut Vec<Mutation>>| {
+                let parent = match &partner {
+                    Some(partner) => gene_info.crossover(partner, &mut rng.0),
+                    None => gene_info.clone(),
+                };
+                parent.mutate(&rates, &islands, &mut rng.0, mutations)
+            };
+            if let Some(eggs) = &config.eggs {
+                // eggs are provisioned from the parent's own energy
+                let passed = organism_energy.0 * eggs.energy_fraction;
+                carbon.0 -= passed * (1.0 - ecosystem.offspring_survival());
+                organism_energy.0 -= passed;
+                let count =
+                    ((passed / eggs.min_energy) as usize).clamp(1, config.children_per_pregnancy);
+                for _ in 0..count {
+                    let mut mutations = Vec::new();
+                    let gene = child_gene(&mut rng, config.log_mutations.then_some(&mut mutations));
+                    let egg = Egg {
+                        parent: *id,
+                        generation: Generation(generation.0 + 1),
+                        gene,
+                        mutations,
+                    };
+                    let position =
+                        child_position(&config, &mut rng.0, organism_transform.translation);
+                    commands.spawn(egg_bundle(eggs, egg, position, passed / count as f32));
+                }
+                life_events.send(LifeEvent {
+                    organism,
+                    tick: tick.0,
+                    kind: LifeEventKind::LaidEggs(count),
+                });
+                continue;
+            }
+            // what goes to offspring that die young is wasted
+            let passed = (organism_energy.0 - 1.0).max(0.0);
+            carbon.0 -= passed * (1.0 - ecosystem.offspring_survival());
+            organism_energy.0 = 1.0;
+            for _ in 0..config.children_per_pregnancy {
+                let mutations = config.log_mutations.then_some(&mut mutation_log.mutations);
+                let gene = child_gene(&mut rng, mutations);
+                let position = child_position(&config, &mut rng.0, organism_transform.translation);
+                let birth = genealogy.birth(Some(id.0), tick.0);
+                let child_id = birth.0;
+                commands
+                    .spawn((
+                        OrganismBundle::new(
+                            &config,
+                            gene,
+                            position,
+                            random_direction(&mut rng.0),
+                            0.5,
+                            tick.0,
+                        ),
+                        birth,
+                    ))
+                    .insert((child_age(&mut rng.0), Generation(generation.0 + 1)));
+                if config.log_mutations {
+                    mutation_log.record(config.mutation_log_capacity, tick.0, *id, child_id);
+                }
+                stats.births += 1;
+            }
+            life_events.send(LifeEvent {
+                organism,
+                tick: tick.0,
+                kind: LifeEventKind::GaveBirth(config.children_per_pregnancy),
+            });
+        }
+    }
+}
+
+pub(crate) fn accumulate_survivability(
+    mut query: Query<(&Energy, &Speed, &mut SurvivabilityScore), With<Organism>>,
+) {
+    for (energy, speed, mut score) in &mut query {
+        score.0 += energy.0 * speed.0 * TIME_STEP;
+    }
+}
+
+/// Hungry organisms eat from their stash, a food item's worth every tick.
+pub(crate) fn consume_stash(
+    config: Res<SimulationConfig>,
+    mut query: Query<(&mut Energy, &mut FoodStash), With<Organism>>,
+) {
+    for (mut energy, mut stash) in &mut query {
+        if energy.0 < config.organism_max_energy * 0.5 && stash.0 > 0.0 {
+            let eaten = stash.0.min(FOOD_ENERGY);
+            stash.0 -= eaten;
+            energy.0 += eaten;
+        }
+    }
+}
+
+/// Sets the interaction radius from the organism's energy, and the drawn
+/// size to match it.
+pub(crate) fn update_size(
+    mut query: Query<(&Energy, &mut InteractionRadius, &mut Transform), With<Organism>>,
+) {
+    for (energy, mut radius, mut transform) in &mut query {
+        *radius = InteractionRadius::from_energy(energy.0);
+        transform.scale = radius.scale();
+    }
+}
+
+/// Nudges overlapping organisms apart, so that clumps of them, like
+/// siblings just born, slowly disperse.
+pub(crate) fn separate_overlapping(
+    config: Res<SimulationConfig>,
+    grid: Res<SpatialGrid>,
+    mut query: Query<(Entity, &mut Transform, &InteractionRadius), With<Organism>>,
+) {
+    let largest = query
+        .iter()
+        .map(|(_, _, radius)| radius.0)
+        .fold(0.0, f32::max);
+    // every organism moves itself away from all the others it overlaps
+    let mut pushes = Vec::new();
+    for (organism, transform, radius) in &query {
+        let position = transform.translation;
+        let mut push = Vec2::ZERO;
+        let nearby =
+            topology::query_circle(&config, &grid, position.truncate(), radius.0 + largest);
+        for (neighbor, neighbor_transform, neighbor_radius) in query.iter_many(&nearby) {
+            if neighbor == organism {
+                continue;
+            }
+            let offset = displacement(&config, neighbor_transform.translation, position);
+            if let Some(contact) =
+                circle_circle(offset.truncate(), radius.0, Vec2::ZERO, neighbor_radius.0)
+            {
+                push += contact.normal * contact.depth * SEPARATION_RATE / 2.0;
+            }
+        }
+        if push != Vec2::ZERO {
+            pushes.push((organism, push));
+        }
+    }
+    for (organism, push) in pushes {
+        if let Ok((_, mut transform, _)) = query.get_mut(organism) {
+            let home = transform.translation.truncate();
+            transform.translation =
+                confine(&config, home + push, home).extend(transform.translation.z);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub(crate) fn check_for_collisions(
+    mut commands: Commands,
+    config: Res<SimulationConfig>,
+    mut organism_query: Query<
+        (
+            Entity,
+            &mut Direction,
+            &Transform,
+            &Age,
+            &mut Energy,
+            Option<&Pregnant>,
+            &mut TimeSinceFood,
+            &Traits,
+            &InteractionRadius,
+            &GeneInfo,
+            &mut PartnerGenes,
+            &mut FoodStash,
+            &mut FoodEaten,
+            (
+                &mut SlowMemory,
+                &mut CarbonProcessed,
+                &mut Poisoned,
+                &Speed,
+                &SensoryInputs,
+                &OrganismId,
+            ),
+        ),
+        With<Organism>,
+    >,
+    collider_query: Query<
+        (
+            Entity,
+            &Transform,
+            Option<&Food>,
+            Option<&Energy>,
+            Option<&Corpse>,
+            Option<&Poison>,
+            Option<&Egg>,
+        ),
+        (With<Collider>, Without<Organism>),
+    >,
+    grid: Res<SpatialGrid>,
+    mut rng: ResMut<SimRng>,
+    tick: Res<SimTick>,
+    mut collision_events: EventWriter<CollisionEvent>,
+    mut life_events: EventWriter<LifeEvent>,
+    strategy_frequency: Res<StrategyFrequency>,
+    competition: Res<StrategyCompetition>,
+    amplification: Res<SelectionAmplification>,
+    burrowed_query: Query<&Burrowed>,
+    mut stats: ResMut<SimStats>,
+) {
+    let underground = |organism| {
+        burrowed_query
+            .get(organism)
+            .is_ok_and(|burrowed| burrowed.underground())
+    };
+    // food touched by several organisms in the same frame is only eaten by
+    // the closest one, burrowed organisms eat nothing
+    let mut eaters: HashMap<Entity, (Entity, f32)> = HashMap::new();
+    for (organism, direction, transform, .., radius, _, _, _, _, (.., id)) in &organism_query {
+        if underground(organism) {
+            continue;
+        }
+        let position = transform.translation.truncate();
+        let touching = grid.query_aabb(position - radius.0, position + radius.0);
+        for (food, food_transform, maybe_food, .., egg) in collider_query.iter_many(&touching) {
+            // parents leave their own eggs alone
+            if maybe_food.is_none()
+                || egg.is_some_and(|egg| egg.parent == *id)
+                || circle_circle(
+                    position,
+                    radius.0,
+                    food_transform.translation.truncate(),
+                    food_transform.scale.x / 2.0,
+                )
+                .is_none()
+                || !in_mouth_arc(
+                    transform.translation,
+                    **direction,
+                    food_transform.translation,
+                    config.mouth_arc,
+                )
+            {
+                continue;
+            }
+            let distance = position.distance(food_transform.translation.truncate());
+            match eaters.get(&food) {
+                Some(&(_, closest)) if closest <= distance => {}
+                _ => {
+                    eaters.insert(food, (organism, distance));
+                }
+            }
+        }
+    }
+
+    for (
+        organism,
+        mut organism_direction,
+        organism_transform,
+        organism_age,
+        mut organism_energy,
+        organism_pregnant,
+        mut time_since_food,
+        traits,
+        radius,
+        _,
+        _,
+        mut stash,
+        mut food_eaten,
+        (mut slow_memory, mut carbon, mut poisoned, speed, inputs, _),
+    ) in &mut organism_query
+    {
+        let position = organism_transform.translation.truncate();
+        let touching = grid.query_aabb(position - radius.0, position + radius.0);
+        // the Pregnant marker only shows up once the tick is over
+        let mut pregnant = organism_pregnant.is_some();
+
+        for (collider_entity, transform, maybe_food, food_energy, corpse, poison, egg) in
+            collider_query.iter_many(&touching)
+        {
+            // food is drawn as a circle and walls as boxes
+            let contact = match maybe_food {
+                Some(_) => circle_circle(
+                    position,
+                    radius.0,
+                    transform.translation.truncate(),
+                    transform.scale.x / 2.0,
+                ),
+                None => circle_aabb(
+                    position,
+                    radius.0,
+                    transform.translation.truncate(),
+                    transform.scale.truncate() / 2.0,
+                ),
+            };
+            if let Some(contact) = contact {
+                if maybe_food.is_some() {
+                    if eaters.get(&collider_entity).map(|&(eater, _)| eater) != Some(organism) {
+                        continue;
+                    }
+                    commands
+                        .entity(collider_entity)
+                        .insert(Dead(DeathCause::Eaten));
+                    collision_events.send(CollisionEvent::Food);
+                    stats.food_eaten += 1;
+                    if poison.is_some() {
+                        // poison is never stashed, and only does harm
+                        organism_energy.0 += food_energy.map_or(0.0, |energy| energy.0.min(0.0));
+                        poisoned.0 = Some(tick.0);
+                        stats.poison_eaten += 1;
+                        continue;
+                    }
+                    // stale food is gone once eaten but gives nothing of its
+                    // own, rare strategies get a bonus on every meal and
+                    // crowded ones lose part of it
+                    let archetype =
+                        BehaviorArchetype::classify(speed.0, traits.max_speed, &inputs.0);
+                    let food_energy = (food_energy.map_or(0.0, |energy| energy.0.max(0.0))
+                        + strategy_frequency.rare_advantage(config.rare_advantage, archetype))
+                        * competition.food_factor(organism);
+                    // well fed organisms may keep the food for later
+                    if organism_energy.0 >= config.organism_max_energy * 0.9
+                        && stash.0 < MAX_STASH
+                        && rng.gen::<f32>() < traits.stash_probability
+                    {
+                        stash.0 = (stash.0 + food_energy).min(MAX_STASH);
+                    } else {
+                        organism_energy.0 += food_energy;
+                    }
+                    if corpse.is_some() {
+                        stats.corpses_eaten += 1;
+                    }
+                    if egg.is_some() {
+                        stats.eggs_eaten += 1;
+                    }
+                    food_eaten.0 += 1;
+                    carbon.0 += food_energy;
+                    slow_memory.remember(transform.translation);
+                    time_since_food.0 = 0;
+                    life_events.send(LifeEvent {
+                        organism,
+                        tick: tick.0,
+                        kind: LifeEventKind::Ate,
+                    });
+                    if !config.sexual_reproduction
+                        && !pregnant
+                        && organism_energy.0 > config.pregnancy_energy_minimum
+                        && organism_age.0 > traits.fertile_age(&config)
+                        && rng.gen::<f32>() < amplification.pregnancy(config.pregnant_probability)
+                    {
+                        pregnant = true;
+                        commands.entity(organism).insert(Pregnant(0));
+                        stats.conceptions += 1;
+                        life_events.send(LifeEvent {
+                            organism,
+                            tick: tick.0,
+                            kind: LifeEventKind::Conceived,
+                        });
+                    }
+                } else {
+                    // reflect the organism when it collides
+                    collision_events.send(CollisionEvent::Wall);
+                    // only reflect if the organism is heading into the wall,
+                    // otherwise it is already on its way out
+                    let into_wall = organism_direction.dot(contact.normal);
+                    if into_wall < 0.0 {
+                        **organism_direction -= 2.0 * into_wall * contact.normal;
+                    }
+                }
+            }
+        }
+    }
+
+    // carnivores eat the closest of the smaller, less carnivorous organisms
+    // they touch above ground; nobody eats or is eaten twice in a frame
+    let mut busy = HashSet::new();
+    let mut meals = Vec::new();
+    for (predator, _, transform, _, _, _, _, traits, radius, ..) in &organism_query {
+        if traits.diet < config.carnivore_threshold
+            || busy.contains(&predator)
+            || underground(predator)
+        {
+            continue;
+        }
+        let position = transform.translation;
+        // prey is smaller than its predator
+        let touching = topology::query_circle(&config, &grid, position.truncate(), 2.0 * radius.0);
+        let prey = organism_query
+            .iter_many(&touching)
+            .filter_map(
+                |(prey, _, prey_transform, _, prey_energy, _, _, prey_traits, prey_radius, ..)| {
+                    let offset = displacement(&config, position, prey_transform.translation);
+                    let edible = !busy.contains(&prey)
+                        && !underground(prey)
+                        && prey_radius.0 < radius.0
+                        && prey_traits.diet < traits.diet
+                        && circle_circle(Vec2::ZERO, radius.0, offset.truncate(), prey_radius.0)
+                            .is_some();
+                    edible.then(|| (prey, prey_energy.0, offset.truncate().length()))
+                },
+            )
+            .min_by(|a, b| a.2.total_cmp(&b.2));
+        if let Some((prey, prey_energy, _)) = prey {
+            busy.insert(predator);
+            busy.insert(prey);
+            meals.push((predator, prey, prey_energy));
+        }
+    }
+    for (predator, prey, prey_energy) in meals {
+        commands.entity(prey).insert(Dead(DeathCause::Predation));
+        stats.kills += 1;
+        if let Ok((_, _, _, _, mut energy, .., (_, mut carbon, ..))) =
+            organism_query.get_mut(predator)
+        {
+            let gained = config.predation_efficiency * prey_energy.max(0.0);
+            energy.0 += gained;
+            carbon.0 += gained;
+        }
+    }
+
+    if !config.sexual_reproduction {
+        return;
+    }
+    // fertile organisms that touch mate, the first one of the pair gets
+    // pregnant with the other's gene; nobody mates twice in a frame
+    let fertile = |energy: &Energy, age: &Age, pregnant: Option<&Pregnant>, traits: &Traits| {
+        pregnant.is_none()
+            && energy.0 > config.pregnancy_energy_minimum
+            && age.0 > traits.fertile_age(&config)
+    };
+    // pairs are taken in query order, the earlier organism first
+    let order: HashMap<Entity, usize> = organism_query
+        .iter()
+        .enumerate()
+        .map(|(index, (entity, ..))| (entity, index))
+        .collect();
+    let largest = organism_query
+        .iter()
+        .map(|(.., radius, _, _, _, _, _)| radius.0)
+        .fold(0.0, f32::max);
+    let mut mated = HashSet::new();
+    let mut matings = Vec::new();
+    for (entity_a, _, transform_a, age_a, energy_a, pregnant_a, _, traits_a, radius_a, ..) in
+        &organism_query
+    {
+        if mated.contains(&entity_a) || !fertile(energy_a, age_a, pregnant_a, traits_a) {
+            continue;
+        }
+        let position = transform_a.translation;
+        let mut nearby =
+            topology::query_circle(&config, &grid, position.truncate(), radius_a.0 + largest);
+        nearby.retain(|entity_b| order[entity_b] > order[&entity_a]);
+        nearby.sort_unstable_by_key(|entity_b| order[entity_b]);
+        for (entity_b, _, transform_b, age_b, energy_b, pregnant_b, _, traits_b, radius_b, ..) in
+            organism_query.iter_many(&nearby)
+        {
+            if mated.contains(&entity_b)
+                || !fertile(energy_b, age_b, pregnant_b, traits_b)
+                || displacement(&config, position, transform_b.translation).length()
+                    > radius_a.0 + radius_b.0
+            {
+                continue;
+            }
+            // conspicuous pairs are more likely to take to each other, the
+            // rng is only drawn when it can matter
+            let attraction = (traits_a.conspicuousness + traits_b.conspicuousness) / 2.0;
+            if attraction < 1.0 && rng.gen::<f32>() >= attraction {
+                continue;
+            }
+            mated.insert(entity_a);
+            mated.insert(entity_b);
+            matings.push((entity_a, entity_b));
+            break;
+        }
+    }
+    for (entity_a, entity_b) in matings {
+        let Ok((.., gene_b, _, _, _, _)) = organism_query.get(entity_b) else {
+            continue;
+        };
+        let gene_b = gene_b.clone();
+        if let Ok((.., mut partner, _, _, _)) = organism_query.get_mut(entity_a) {
+            partner.0 = Some(gene_b);
+        }
+        commands.entity(entity_a).insert(Pregnant(0));
+        stats.conceptions += 1;
+        life_events.send_batch([
+            LifeEvent {
+                organism: entity_a,
+                tick: tick.0,
+                kind: LifeEventKind::Conceived,
+            },
+            LifeEvent {
+                organism: entity_b,
+                tick: tick.0,
+                kind: LifeEventKind::Mated,
+            },
+        ]);
+    }
+}
+
+/// How alike two colors are, from 0 for opposite colors to 1 for the same.
+fn color_similarity(a: Color, b: Color) -> f32 {
+    1.0 - color_distance(a, b)
+}
+
+/// Whether food at `food_pos` lies within the frontal `mouth_arc` (in degrees)
+/// of an organism at `organism_pos` heading along `direction`. Angles are
+/// measured the same way as in the vision sectors of `adjust_direction`.
+pub(crate) fn in_mouth_arc(
+    organism_pos: Vec3,
+    direction: Vec2,
+    food_pos: Vec3,
+    mouth_arc: f32,
+) -> bool {
+    if mouth_arc >= 360.0 {
+        return true;
+    }
+    let to_food = (food_pos - organism_pos).truncate();
+    if to_food.length_squared() == 0.0 {
+        return true;
+    }
+    to_food.angle_between(direction).abs() <= mouth_arc.to_radians() / 2.0
+}
+
+pub(crate) fn logistic_survival_model(
+    config: Res<SimulationConfig>,
+    tick: Res<SimTick>,
+    mut model: ResMut<SurvivalModel>,
+    mut death_events: EventReader<DeathEvent>,
+    mut query: Query<(&GeneInfo, &mut SurvivalProbability), With<Organism>>,
+) {
+    for event in death_events.iter() {
+        let fertile_age = Traits::from_gene(&event.gene).fertile_age(&config);
+        model.train(&event.gene, event.age > fertile_age);
+    }
+    if tick.0.is_multiple_of(SURVIVAL_MODEL_INTERVAL) {
+        for (gene, mut probability) in &mut query {
+            probability.0 = model.predict(gene);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use crate::config::ObstacleConfig;
+    use crate::food::food_bundle;
+    use crate::plugin::reap_dead;
+    use crate::spatial::update_spatial_grid;
+
+    /// An app that only runs the collisions of a tick, between filling the
+    /// spatial grid and reaping the dead.
+    fn collision_app(config: SimulationConfig) -> App {
+        let mut app = App::new();
+        app.insert_resource(SpatialGrid::new(config.organism_vision / 2.0))
+            .insert_resource(config)
+            .insert_resource(SimRng(ChaCha8Rng::seed_from_u64(0)))
+            .init_resource::<SimTick>()
+            .init_resource::<StrategyFrequency>()
+            .init_resource::<StrategyCompetition>()
+            .init_resource::<SelectionAmplification>()
+            .init_resource::<SimStats>()
+            .init_resource::<Genealogy>()
+            .add_event::<CollisionEvent>()
+            .add_event::<LifeEvent>()
+            .add_event::<DeathEvent>()
+            .add_systems(
+                (
+                    update_spatial_grid,
+                    check_for_collisions,
+                    apply_system_buffers,
+                    reap_dead,
+                )
+                    .chain(),
+            );
+        app
+    }
+
+    #[test]
+    fn food_behind_is_outside_a_frontal_arc() {
+        let behind = Vec3::new(-10.0, 0.0, 0.0);
+        assert!(!in_mouth_arc(Vec3::ZERO, Vec2::X, behind, 90.0));
+        assert!(!in_mouth_arc(Vec3::ZERO, Vec2::X, behind, 359.0));
+        assert!(in_mouth_arc(Vec3::ZERO, Vec2::X, behind, 360.0));
+    }
+
+    #[test]
+    fn food_on_the_arc_boundary_is_eaten() {
+        let side = Vec3::new(0.0, 10.0, 0.0);
+        assert!(in_mouth_arc(Vec3::ZERO, Vec2::X, side, 180.0));
+        assert!(in_mouth_arc(Vec3::ZERO, Vec2::X, -side, 180.0));
+        assert!(!in_mouth_arc(Vec3::ZERO, Vec2::X, side, 179.0));
+    }
+
+    #[test]
+    fn rotate_direction_turns_clockwise() {
+        let mut direction = Vec2::X;
+        rotate_direction(&mut direction, PI / 2.0);
+        assert!(direction.abs_diff_eq(Vec2::NEG_Y, 1e-6));
+        rotate_direction(&mut direction, -PI);
+        assert!(direction.abs_diff_eq(Vec2::Y, 1e-6));
+    }
+
+    #[test]
+    fn rotating_back_gives_the_original_direction() {
+        for (angle, start) in [
+            (0.3, Vec2::X),
+            (-2.0, Vec2::new(0.6, 0.8)),
+            (PI, Vec2::new(-0.28, 0.96)),
+            (10.0, Vec2::NEG_Y),
+        ] {
+            let mut direction = start;
+            rotate_direction(&mut direction, angle);
+            rotate_direction(&mut direction, -angle);
+            assert!(
+                direction.abs_diff_eq(start, 1e-6),
+                "{start} came back as {direction} after turning by {angle}"
+            );
+        }
+    }
+
+    #[test]
+    fn many_small_rotations_keep_a_unit_direction() {
+        let mut direction = Vec2::new(0.6, 0.8);
+        for _ in 0..10_000 {
+            rotate_direction(&mut direction, 0.001);
+        }
+        assert!((direction.length() - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn rotate_direction_leaves_a_zero_direction_alone() {
+        let mut direction = Vec2::ZERO;
+        rotate_direction(&mut direction, 1.0);
+        assert_eq!(direction, Vec2::ZERO);
+    }
+
+    #[test]
+    fn smoothing_follows_the_exponential_step_response() {
+        let k: f32 = 0.8;
+        let mut brain = LastBrainState::default();
+        for n in 1..=50 {
+            let (turn, speed) = brain.smooth(k, 1.0, -0.5);
+            let expected = 1.0 - k.powi(n);
+            assert!((turn - expected).abs() < 1e-5, "turn {turn} at step {n}");
+            assert!(
+                (speed + 0.5 * expected).abs() < 1e-5,
+                "speed {speed} at step {n}"
+            );
+        }
+    }
+
+    #[test]
+    fn no_smoothing_passes_outputs_through_unchanged() {
+        let mut brain = LastBrainState::default();
+        for output in [0.3, -1.0, 1.0, 1e-7, -0.0, 0.123_456_79] {
+            let (turn, speed) = brain.smooth(0.0, output, -output);
+            assert_eq!(turn.to_bits(), output.to_bits());
+            assert_eq!(speed.to_bits(), (-output).to_bits());
+        }
+    }
+
+    #[test]
+    fn interaction_radius_grows_with_the_square_root_of_energy() {
+        assert_eq!(InteractionRadius::from_energy(1.0).0, ORGANISM_BASE_RADIUS);
+        assert_eq!(
+            InteractionRadius::from_energy(4.0).0,
+            2.0 * ORGANISM_BASE_RADIUS
+        );
+        assert_eq!(
+            InteractionRadius::from_energy(0.25).0,
+            0.5 * ORGANISM_BASE_RADIUS
+        );
+        assert_eq!(InteractionRadius::from_energy(0.0).0, 0.0);
+        assert_eq!(InteractionRadius::from_energy(-1.0).0, 0.0);
+    }
+
+    #[test]
+    fn food_touched_by_two_organisms_is_eaten_once_by_the_closest() {
+        let config = SimulationConfig::default();
+        let mut app = collision_app(config.clone());
+        let food = app
+            .world
+            .spawn(food_bundle(&config, Vec3::new(10.0, 0.0, 0.0)))
+            .id();
+        let mut organism = |x: f32, id: u64| {
+            app.world
+                .spawn((
+                    OrganismBundle::new(
+                        &config,
+                        GeneInfo::planned(&config),
+                        Vec3::new(x, 0.0, 0.0),
+                        Vec2::X,
+                        1.0,
+                        0,
+                    ),
+                    OrganismId(id),
+                ))
+                .id()
+        };
+        // the farther one comes first in query order
+        let farther = organism(14.0, 0);
+        let closer = organism(8.0, 1);
+        app.update();
+
+        assert!(app.world.get_entity(food).is_none());
+        assert_eq!(app.world.resource::<SimStats>().food_eaten, 1);
+        let events = app.world.resource::<Events<CollisionEvent>>();
+        let meals = events
+            .get_reader()
+            .iter(events)
+            .filter(|event| matches!(event, CollisionEvent::Food))
+            .count();
+        assert_eq!(meals, 1);
+
+        let gained = |organism| app.world.get::<Energy>(organism).unwrap().0 - 1.0;
+        assert!((gained(closer) + gained(farther) - FOOD_ENERGY).abs() < 1e-6);
+        assert!((gained(closer) - FOOD_ENERGY).abs() < 1e-6);
+        assert_eq!(app.world.get::<FoodEaten>(closer).unwrap().0, 1);
+        assert_eq!(app.world.get::<FoodEaten>(farther).unwrap().0, 0);
+    }
+
+    #[test]
+    fn children_stay_in_the_arena_and_out_of_obstacles() {
+        let mut config = SimulationConfig {
+            obstacles: vec![ObstacleConfig {
+                center: [200.0, 0.0],
+                size: [100.0, 100.0],
+            }],
+            ..default()
+        };
+        let center = center_x(&config);
+        let parents = [
+            Vec2::new(config.left_boundary, 0.0),
+            Vec2::new(config.right_boundary, 0.0),
+            Vec2::new(-300.0, config.bottom_boundary),
+            Vec2::new(-300.0, config.top_boundary),
+            Vec2::new(config.left_boundary, config.top_boundary),
+            Vec2::new(center, 0.0),
+            Vec2::new(145.0, 0.0),
+            Vec2::new(200.0, 55.0),
+        ];
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        for mirror_arena in [false, true] {
+            config.mirror_arena = mirror_arena;
+            for parent in parents {
+                for _ in 0..1000 {
+                    let child = child_position(&config, &mut rng, parent.extend(0.0)).truncate();
+                    assert!(
+                        (config.left_boundary..=config.right_boundary).contains(&child.x)
+                            && (config.bottom_boundary..=config.top_boundary).contains(&child.y),
+                        "child of {parent} at {child} is outside the arena"
+                    );
+                    for obstacle in &config.obstacles {
+                        let offset = (child - Vec2::from(obstacle.center)).abs();
+                        assert!(
+                            !offset.cmplt(Vec2::from(obstacle.size) / 2.0).all(),
+                            "child of {parent} at {child} is inside an obstacle"
+                        );
+                    }
+                    if mirror_arena {
+                        assert!(
+                            (child.x - center).abs() >= config.boundary_thickness / 2.0,
+                            "child of {parent} at {child} is in the centerline wall"
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn the_mutation_log_keeps_the_newest_mutations_up_to_its_capacity() {
+        let mut log = MutationLog::default();
+        for child in 0..5 {
+            log.mutations.push(Mutation {
+                locus: child as usize,
+                old: 0.0,
+                new: 1.0,
+            });
+            log.record(3, 0, OrganismId(0), OrganismId(child));
+        }
+        assert!(log.mutations.is_empty());
+        assert_eq!(log.dropped, 2);
+        let children: Vec<u64> = log.events.iter().map(|event| event.child.0).collect();
+        assert_eq!(children, [2, 3, 4]);
+    }
+}
@@ -0,0 +1,97 @@
+//! Patchy food: food grows in Gaussian blobs around a few patch centers that
+//! drift around the arena and now and then move somewhere else altogether.
+
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::config::{FoodDistribution, SimulationConfig};
+use crate::founders::gaussian;
+use crate::topology::{is_torus, wrap};
+use crate::{random_position, SimRng};
+
+/// Chance a patch moves to a random place on a tick.
+const RELOCATION_CHANCE: f64 = 0.001;
+/// Standard deviation of the change of the heading of a patch on a tick, in
+/// radians.
+const HEADING_JITTER: f32 = 0.1;
+
+#[derive(Clone, Debug)]
+pub struct FoodPatch {
+    pub center: Vec2,
+    /// Direction the patch drifts in.
+    pub heading: Vec2,
+}
+
+impl FoodPatch {
+    fn random(config: &SimulationConfig, rng: &mut impl Rng) -> Self {
+        Self {
+            center: random_position(config, rng).truncate(),
+            heading: Vec2::from_angle(rng.gen_range(0.0..std::f32::consts::TAU)),
+        }
+    }
+}
+
+/// Centers of the food patches in `FoodDistribution::Patchy` mode.
+#[derive(Resource, Default)]
+pub struct FoodPatches(pub Vec<FoodPatch>);
+
+impl FoodPatches {
+    /// A position around a random patch, normally distributed with standard
+    /// deviation `radius` and kept inside the arena.
+    pub fn sample_position(
+        &self,
+        config: &SimulationConfig,
+        radius: f32,
+        rng: &mut impl Rng,
+    ) -> Vec3 {
+        let patch = &self.0[rng.gen_range(0..self.0.len())];
+        let position = (patch.center + gaussian(rng) * radius).extend(0.0);
+        if is_torus(config) {
+            return wrap(config, position);
+        }
+        let min = Vec3::new(config.left_boundary, config.bottom_boundary, 0.0);
+        let max = Vec3::new(config.right_boundary, config.top_boundary, 0.0);
+        position.clamp(min, max)
+    }
+}
+
+fn in_arena(config: &SimulationConfig, position: Vec2) -> bool {
+    (config.left_boundary..=config.right_boundary).contains(&position.x)
+        && (config.bottom_boundary..=config.top_boundary).contains(&position.y)
+}
+
+/// Places the patches on the first tick, then moves every patch
+/// `drift_speed` along its slowly turning heading. A patch that drifts out of
+/// the arena, or relocates by chance, starts again at a random place.
+pub(crate) fn drift_food_patches(
+    config: Res<SimulationConfig>,
+    mut rng: ResMut<SimRng>,
+    mut patches: ResMut<FoodPatches>,
+) {
+    let FoodDistribution::Patchy {
+        patch_count,
+        drift_speed,
+        ..
+    } = config.food_distribution
+    else {
+        return;
+    };
+    let rng = &mut rng.0;
+    if patches.0.len() != patch_count {
+        patches.0 = (0..patch_count)
+            .map(|_| FoodPatch::random(&config, rng))
+            .collect();
+        return;
+    }
+    for patch in &mut patches.0 {
+        let turn = gaussian(rng).x * HEADING_JITTER;
+        patch.heading = Vec2::from_angle(turn).rotate(patch.heading);
+        patch.center += patch.heading * drift_speed;
+        if is_torus(&config) {
+            patch.center = wrap(&config, patch.center.extend(0.0)).truncate();
+        }
+        if !in_arena(&config, patch.center) || rng.gen_bool(RELOCATION_CHANCE) {
+            *patch = FoodPatch::random(&config, rng);
+        }
+    }
+}
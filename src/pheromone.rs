@@ -0,0 +1,250 @@
+//! The pheromone field: a grid over the arena the organisms leave scent in
+//! as they move, which fades every tick and optionally spreads to the
+//! neighboring cells. It is drawn as a single texture under the arena.
+
+use bevy::{
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
+
+use crate::config::SimulationConfig;
+use crate::topology::is_torus;
+
+/// Intensity, then the red, green and blue of the emitters weighted by the
+/// intensity they emitted with.
+pub const PHEROMONE_CHANNELS: usize = 4;
+/// Cells with less pheromone than this are not sensed nor drawn.
+const MIN_INTENSITY: f32 = 0.001;
+
+/// Pheromone in every cell of a grid over the arena, row by row from the
+/// bottom left corner, and the organism that last emitted in each cell.
+/// Cells restored from a save have no emitter, since the organisms are
+/// respawned as new entities.
+#[derive(Resource)]
+pub struct PheromoneField {
+    pub cell_size: f32,
+    pub columns: usize,
+    pub rows: usize,
+    pub cells: Vec<[f32; PHEROMONE_CHANNELS]>,
+    pub emitters: Vec<Option<Entity>>,
+}
+
+impl FromWorld for PheromoneField {
+    fn from_world(world: &mut World) -> Self {
+        let config = world.resource::<SimulationConfig>();
+        let cell_size = config.pheromone_cell_size;
+        let columns = ((config.right_boundary - config.left_boundary) / cell_size).ceil();
+        let rows = ((config.top_boundary - config.bottom_boundary) / cell_size).ceil();
+        let (columns, rows) = ((columns as usize).max(1), (rows as usize).max(1));
+        Self {
+            cell_size,
+            columns,
+            rows,
+            cells: vec![[0.0; PHEROMONE_CHANNELS]; columns * rows],
+            emitters: vec![None; columns * rows],
+        }
+    }
+}
+
+impl PheromoneField {
+    /// The column and row `position` is in, the nearest one when it is out
+    /// of the arena.
+    fn column_row(&self, config: &SimulationConfig, position: Vec2) -> (usize, usize) {
+        let min = Vec2::new(config.left_boundary, config.bottom_boundary);
+        let cell = ((position - min) / self.cell_size).floor();
+        let column = (cell.x.max(0.0) as usize).min(self.columns - 1);
+        let row = (cell.y.max(0.0) as usize).min(self.rows - 1);
+        (column, row)
+    }
+
+    pub fn center(&self, config: &SimulationConfig, cell: usize) -> Vec2 {
+        let min = Vec2::new(config.left_boundary, config.bottom_boundary);
+        let column_row = Vec2::new((cell % self.columns) as f32, (cell / self.columns) as f32);
+        min + (column_row + 0.5) * self.cell_size
+    }
+
+    /// Adds pheromone of `color` at `intensity` to the cell of `position`.
+    pub fn emit(
+        &mut self,
+        config: &SimulationConfig,
+        position: Vec2,
+        color: Color,
+        intensity: f32,
+        emitter: Entity,
+    ) {
+        let (column, row) = self.column_row(config, position);
+        let cell = row * self.columns + column;
+        let [r, g, b, _] = color.as_rgba_f32();
+        for (channel, value) in self.cells[cell].iter_mut().zip([1.0, r, g, b]) {
+            *channel += value * intensity;
+        }
+        self.emitters[cell] = Some(emitter);
+    }
+
+    /// The cells with pheromone that overlap the square of side
+    /// `2 * radius` around `position`, including the ones across the edges
+    /// in torus mode.
+    pub fn cells_around(
+        &self,
+        config: &SimulationConfig,
+        position: Vec2,
+        radius: f32,
+    ) -> Vec<usize> {
+        let torus = is_torus(config);
+        let min = Vec2::new(config.left_boundary, config.bottom_boundary);
+        let low = ((position - radius - min) / self.cell_size)
+            .floor()
+            .as_ivec2();
+        let high = ((position + radius - min) / self.cell_size)
+            .floor()
+            .as_ivec2();
+        // a cell is never visited twice, even when the square is wider
+        // than a torus
+        let span = |low: i32, high: i32, side: usize| {
+            if torus {
+                low..=high.min(low + side as i32 - 1)
+            } else {
+                low.max(0)..=high.min(side as i32 - 1)
+            }
+        };
+        let mut cells = Vec::new();
+        for row in span(low.y, high.y, self.rows) {
+            for column in span(low.x, high.x, self.columns) {
+                let row = row.rem_euclid(self.rows as i32) as usize;
+                let column = column.rem_euclid(self.columns as i32) as usize;
+                let cell = row * self.columns + column;
+                if self.cells[cell][0] > MIN_INTENSITY {
+                    cells.push(cell);
+                }
+            }
+        }
+        cells
+    }
+
+    /// The mean color of the pheromone in `cell`, weighted by intensity.
+    pub fn color(&self, cell: usize) -> Color {
+        let [intensity, r, g, b] = self.cells[cell];
+        if intensity <= 0.0 {
+            return Color::NONE;
+        }
+        Color::rgb(r / intensity, g / intensity, b / intensity)
+    }
+
+    pub fn total(&self) -> f32 {
+        self.cells.iter().map(|cell| cell[0]).sum()
+    }
+}
+
+/// Fades the pheromone of every cell and spreads it to the four neighbors.
+/// Nothing flows out of the arena, except across the edges of a torus.
+pub(crate) fn update_pheromone_field(
+    config: Res<SimulationConfig>,
+    mut field: ResMut<PheromoneField>,
+) {
+    let field = &mut *field;
+    for cell in &mut field.cells {
+        for channel in cell {
+            *channel *= config.pheromone_decay;
+        }
+    }
+    if config.pheromone_diffusion <= 0.0 {
+        return;
+    }
+    let torus = is_torus(&config);
+    let previous = field.cells.clone();
+    let step = |i: usize, delta: isize, side: usize| -> usize {
+        let moved = i as isize + delta;
+        if torus {
+            moved.rem_euclid(side as isize) as usize
+        } else {
+            // the edge cells are their own neighbors beyond the walls
+            moved.clamp(0, side as isize - 1) as usize
+        }
+    };
+    let (columns, rows) = (field.columns, field.rows);
+    for y in 0..rows {
+        for x in 0..columns {
+            let neighbors = [
+                y * columns + step(x, -1, columns),
+                y * columns + step(x, 1, columns),
+                step(y, -1, rows) * columns + x,
+                step(y, 1, rows) * columns + x,
+            ];
+            let here = previous[y * columns + x];
+            for channel in 0..PHEROMONE_CHANNELS {
+                let laplacian = neighbors
+                    .iter()
+                    .map(|&neighbor| previous[neighbor][channel])
+                    .sum::<f32>()
+                    - 4.0 * here[channel];
+                field.cells[y * columns + x][channel] =
+                    here[channel] + config.pheromone_diffusion * laplacian;
+            }
+        }
+    }
+}
+
+/// The texture the pheromone field is drawn to.
+#[derive(Component)]
+pub(crate) struct PheromoneLayer;
+
+pub(crate) fn setup_pheromone_layer(
+    mut commands: Commands,
+    config: Res<SimulationConfig>,
+    field: Res<PheromoneField>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let image = Image::new_fill(
+        Extent3d {
+            width: field.columns as u32,
+            height: field.rows as u32,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &[0, 0, 0, 0],
+        TextureFormat::Rgba8UnormSrgb,
+    );
+    // the texture covers the whole grid, which may reach past the top and
+    // right of the arena
+    let size = Vec2::new(field.columns as f32, field.rows as f32) * field.cell_size;
+    let corner = Vec2::new(config.left_boundary, config.bottom_boundary);
+    commands.spawn((
+        PheromoneLayer,
+        SpriteBundle {
+            sprite: Sprite {
+                custom_size: Some(size),
+                ..default()
+            },
+            texture: images.add(image),
+            transform: Transform::from_translation((corner + size / 2.0).extend(-0.5)),
+            ..default()
+        },
+    ));
+}
+
+/// Copies the pheromone field to its texture, at the opacity of the
+/// intensity in each cell.
+pub(crate) fn draw_pheromones(
+    field: Res<PheromoneField>,
+    mut images: ResMut<Assets<Image>>,
+    layer_query: Query<&Handle<Image>, With<PheromoneLayer>>,
+) {
+    let Ok(handle) = layer_query.get_single() else {
+        return;
+    };
+    let Some(image) = images.get_mut(handle) else {
+        return;
+    };
+    // the rows of the texture go from the top down
+    for (cell, pixel) in image.data.chunks_exact_mut(4).enumerate() {
+        let (column, row) = (cell % field.columns, cell / field.columns);
+        let cell = (field.rows - 1 - row) * field.columns + column;
+        let intensity = field.cells[cell][0];
+        if intensity <= MIN_INTENSITY {
+            pixel.copy_from_slice(&[0, 0, 0, 0]);
+            continue;
+        }
+        let color = field.color(cell).with_a(intensity.min(1.0));
+        pixel.copy_from_slice(&color.as_rgba_f32().map(|c| (c * 255.0) as u8));
+    }
+}
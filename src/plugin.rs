@@ -0,0 +1,1008 @@
+//! The plugins that put the simulation and its visuals together.
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::time::Duration;
+
+use bevy::{
+    log::LogPlugin,
+    prelude::*,
+    sprite::Mesh2dHandle,
+    time::{TimeSystem, TimeUpdateStrategy},
+};
+use bevy_egui::EguiPlugin;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+use crate::behavior::{
+    correlate_repertoire, log_competition, measure_competition, record_behavior,
+    BehaviorRepertoire, RepertoireLifespan, StrategyCompetition, StrategyFrequency,
+};
+use crate::boundary::{BoundaryBundle, BoundaryLocation};
+use crate::burrow::burrow;
+use crate::camera::{camera_controls, follow_camera, CameraFollow};
+use crate::census::{take_census, Census};
+use crate::community::{update_community_stability, CommunityStability};
+use crate::config::SimulationConfig;
+use crate::diversity::{update_diversity, DiversityAlert, DiversityMetrics};
+use crate::ecosystem::{update_ecosystem_service, EcosystemServiceMetric};
+use crate::egg::{hatch_eggs, Egg, EGG_COLOR};
+use crate::elite::{
+    dump_elite_archive, respawn_from_hall_of_fame, update_elite_archive, update_hall_of_fame,
+    EliteArchive, HallOfFame,
+};
+use crate::energy_bar::{add_energy_bars, update_energy_bars, EnergyBarsShown};
+use crate::food::{
+    control_food_supply, decay_food, drop_corpses, food_fade, generate_food, log_environment,
+    Corpse, Food, FoodController, FoodPatchiness, FoodRate, FoodTimer, Poison, CORPSE_COLOR,
+    FOOD_COLOR, POISON_COLOR,
+};
+use crate::food_source::{control_food_source, feed_from_source, log_food_source_alignment};
+use crate::founders::founder_positions;
+use crate::genealogy::{export_genealogy, Genealogy, OrganismId};
+use crate::generation::{update_generation_stats, GenerationStats};
+use crate::genes::{
+    update_genomic_islands, Conservation, GeneInfo, GenomicIslands, Traits, GENE_LENGTH,
+};
+use crate::genome_plot::{
+    setup_genome_plot, update_genome_plot, update_genome_projection, GenomeProjection,
+};
+use crate::heading::{
+    add_heading_ticks, add_pregnancy_outlines, remove_pregnancy_outlines, sync_orientation,
+};
+use crate::hud::{setup_hud, update_hud};
+use crate::inspector::{
+    select_organism, setup_inspector, update_inspector, update_interaction_circle,
+    update_selection_ring, update_vision_circle,
+};
+use crate::library::{genome_library_window, update_genome_library, GenomeLibrary};
+use crate::luminosity::{add_glow, update_glow};
+use crate::mirror::{center_x, left_half, log_mirror_halves, mirror_position};
+use crate::network::{analyze_network, NetworkTopology, PheromoneNetwork};
+use crate::nutrient::{diffuse_nutrients, return_nutrients, SoilNutrient};
+use crate::obstacle::push_out;
+use crate::organism::{
+    accumulate_survivability, adjust_direction, apply_direction, check_for_collisions,
+    consume_stash, grow_organism, logistic_survival_model, random_direction, run_and_tumble,
+    separate_overlapping, update_size, write_mutation_log, CarbonProcessed, CollisionEvent,
+    DeathEvent, Direction, EnergyRecord, FoodEaten, Generation, LifeEvent, MutationLog, Organism,
+    OrganismBundle, SelectionAmplification, SensoryTimer, Speed, SurvivabilityScore, SurvivalModel,
+    TimeSinceFood, SENSORY_STAGGER_N,
+};
+use crate::patches::{drift_food_patches, FoodPatches};
+use crate::pheromone::{
+    draw_pheromones, setup_pheromone_layer, update_pheromone_field, PheromoneField,
+};
+use crate::profiler::{checkpoint, finish_tick, start_tick, TimestepProfiler};
+use crate::reaction_diffusion::{update_reaction_diffusion, RDGrid};
+use crate::save::{request_save, restore_world, save_world, SaveRequested, SavedWorld};
+use crate::signal::compete;
+use crate::snapshots::write_genome_snapshot;
+use crate::spatial::{update_spatial_grid, SpatialGrid};
+use crate::stats::{
+    log_deaths, log_memory, log_pheromone_sensitivity, log_population, update_growth_curve,
+    update_sim_stats, GrowthCurve, SimStats,
+};
+use crate::swarm::{log_swarm, share_sensory, SharedSensory};
+use crate::timeline::{setup_timeline, update_timeline, update_timeline_panel, Timeline};
+use crate::topology::is_torus;
+use crate::trap::{spawn_traps, spring_traps, DeathTrap, TRAP_COLOR};
+use crate::{Age, Dead, DeathCause, Energy, Lifetime, SimRng, SimTick, TIME_STEP};
+
+const BACKGROUND_COLOR: Color = Color::rgb(0.1, 0.1, 0.1);
+
+#[derive(Resource)]
+pub(crate) struct LogTimer(pub(crate) Timer);
+
+#[derive(Resource)]
+pub(crate) struct AgeTimer(pub(crate) Timer);
+
+#[derive(Resource)]
+pub(crate) struct CollisionSound(pub(crate) Handle<AudioSource>);
+
+#[derive(Resource)]
+pub(crate) struct FeedingSound(pub(crate) Handle<AudioSource>);
+
+/// Levels the red, green and blue channels of a material are rounded to, so
+/// that organisms and fading food of about the same color share one.
+const COLOR_LEVELS: f32 = 16.0;
+/// Levels the alpha channel of a material is rounded to.
+const ALPHA_LEVELS: f32 = 10.0;
+
+/// The circle mesh everything is drawn with and the materials created so
+/// far, by quantized color. Sharing them keeps the asset stores from growing
+/// with every entity spawned.
+#[derive(Resource)]
+pub(crate) struct CircleAssets {
+    pub(crate) mesh: Mesh2dHandle,
+    food: Handle<ColorMaterial>,
+    materials: HashMap<[u8; 4], Handle<ColorMaterial>>,
+}
+
+impl CircleAssets {
+    pub(crate) fn new(meshes: &mut Assets<Mesh>, materials: &mut Assets<ColorMaterial>) -> Self {
+        CircleAssets {
+            mesh: Mesh2dHandle(meshes.add(shape::Circle::default().into())),
+            food: materials.add(ColorMaterial::from(FOOD_COLOR)),
+            materials: HashMap::new(),
+        }
+    }
+
+    /// The material of `color` rounded to [`COLOR_LEVELS`] and
+    /// [`ALPHA_LEVELS`], created the first time it is asked for.
+    pub(crate) fn material(
+        &mut self,
+        materials: &mut Assets<ColorMaterial>,
+        color: Color,
+    ) -> Handle<ColorMaterial> {
+        let quantize = |c: f32, levels: f32| (c.clamp(0.0, 1.0) * (levels - 1.0)).round() as u8;
+        let key = [
+            quantize(color.r(), COLOR_LEVELS),
+            quantize(color.g(), COLOR_LEVELS),
+            quantize(color.b(), COLOR_LEVELS),
+            quantize(color.a(), ALPHA_LEVELS),
+        ];
+        self.materials
+            .entry(key)
+            .or_insert_with(|| {
+                let channel = |k: u8, levels: f32| k as f32 / (levels - 1.0);
+                materials.add(ColorMaterial::from(Color::rgba(
+                    channel(key[0], COLOR_LEVELS),
+                    channel(key[1], COLOR_LEVELS),
+                    channel(key[2], COLOR_LEVELS),
+                    channel(key[3], ALPHA_LEVELS),
+                )))
+            })
+            .clone()
+    }
+}
+
+pub(crate) fn setup_visuals(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    // Sound
+    let collision_sound = asset_server.load("sounds/collision.ogg");
+    commands.insert_resource(CollisionSound(collision_sound));
+    let feeding_sound = asset_server.load("sounds/feeding.ogg");
+    commands.insert_resource(FeedingSound(feeding_sound));
+
+    commands.insert_resource(CircleAssets::new(&mut meshes, &mut materials));
+
+    commands.spawn(Camera2dBundle::default());
+}
+
+/// Gives newly spawned organisms, food and traps a circle to be drawn with.
+#[allow(clippy::type_complexity)]
+pub(crate) fn add_visuals(
+    mut commands: Commands,
+    mut circles: ResMut<CircleAssets>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    organism_query: Query<(Entity, &GeneInfo, &Traits), Added<Organism>>,
+    food_query: Query<(Entity, Option<&Corpse>, Option<&Poison>, Option<&Egg>), Added<Food>>,
+    trap_query: Query<Entity, Added<DeathTrap>>,
+) {
+    let mesh = circles.mesh.clone();
+    let mut add_circle = |entity: Entity, material: Handle<ColorMaterial>| {
+        commands.entity(entity).insert((
+            mesh.clone(),
+            material,
+            GlobalTransform::default(),
+            VisibilityBundle::default(),
+        ));
+    };
+    for (entity, corpse, poison, egg) in &food_query {
+        let material = if egg.is_some() {
+            circles.material(&mut materials, EGG_COLOR)
+        } else if corpse.is_some() {
+            circles.material(&mut materials, CORPSE_COLOR)
+        } else if poison.is_some() {
+            circles.material(&mut materials, POISON_COLOR)
+        } else {
+            circles.food.clone()
+        };
+        add_circle(entity, material);
+    }
+    for (entity, gene, traits) in &organism_query {
+        let color = traits.diet_tint(gene.color());
+        add_circle(entity, circles.material(&mut materials, color));
+    }
+    for entity in &trap_query {
+        add_circle(entity, circles.material(&mut materials, TRAP_COLOR));
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub(crate) fn age_progression(
+    fixed_time: Res<FixedTime>,
+    mut timer: ResMut<AgeTimer>,
+    mut commands: Commands,
+    mut query: Query<(
+        Entity,
+        &mut Age,
+        &Lifetime,
+        Option<(&Energy, &mut EnergyRecord, &mut TimeSinceFood)>,
+    )>,
+) {
+    if timer.0.tick(fixed_time.period).just_finished() {
+        for (entity, mut age, lifetime, organism) in &mut query {
+            if age.0 > lifetime.0 {
+                commands.entity(entity).insert(Dead(DeathCause::Expired));
+            } else {
+                age.0 += 1;
+                if let Some((energy, mut record, mut time_since_food)) = organism {
+                    record.0 += energy.0;
+                    time_since_food.0 += 1;
+                }
+            }
+        }
+    }
+}
+
+/// Despawns everything marked [`Dead`] during the tick, the only place
+/// simulated entities are despawned so that none is despawned twice.
+#[allow(clippy::type_complexity)]
+pub(crate) fn reap_dead(
+    mut commands: Commands,
+    tick: Res<SimTick>,
+    mut genealogy: ResMut<Genealogy>,
+    query: Query<(
+        Entity,
+        &Dead,
+        Option<(
+            &GeneInfo,
+            &Age,
+            &Energy,
+            &EnergyRecord,
+            &FoodEaten,
+            &SurvivabilityScore,
+            &OrganismId,
+            &BehaviorRepertoire,
+            &CarbonProcessed,
+            &Generation,
+        )>,
+    )>,
+    mut death_events: EventWriter<DeathEvent>,
+) {
+    for (entity, dead, organism) in &query {
+        // taking the energy bars of organisms with them
+        commands.entity(entity).despawn_recursive();
+        if let Some((
+            gene,
+            age,
+            energy,
+            record,
+            food_eaten,
+            survivability,
+            id,
+            repertoire,
+            carbon,
+            generation,
+        )) = organism
+        {
+            genealogy.death(*id, tick.0, dead.0);
+            death_events.send(DeathEvent {
+                organism: entity,
+                id: *id,
+                cause: dead.0,
+                gene: gene.clone(),
+                age: age.0,
+                mean_energy: record.mean(age.0),
+                energy: energy.0,
+                food_eaten: food_eaten.0,
+                survivability: survivability.0,
+                repertoire: repertoire.strategies_used.len(),
+                carbon_processed: carbon.0,
+                generation: generation.0 as usize,
+            });
+        }
+    }
+}
+
+/// Ticks the timer of the periodic logs and appends a row for every
+/// organism to the organism log every `organism_log.interval` ticks.
+#[allow(clippy::type_complexity)]
+pub(crate) fn log_things(
+    config: Res<SimulationConfig>,
+    fixed_time: Res<FixedTime>,
+    tick: Res<SimTick>,
+    mut timer: ResMut<LogTimer>,
+    mut log: Local<Option<std::io::BufWriter<std::fs::File>>>,
+    query: Query<
+        (
+            &OrganismId,
+            &Transform,
+            &GeneInfo,
+            &Direction,
+            &Speed,
+            &Energy,
+            &Age,
+            &FoodEaten,
+        ),
+        With<Organism>,
+    >,
+) {
+    timer.0.tick(fixed_time.period);
+    if !tick.0.is_multiple_of(config.organism_log.interval) {
+        return;
+    }
+    let log = log.get_or_insert_with(|| {
+        let file = std::fs::File::create(&config.organism_log.path).unwrap();
+        let mut file = std::io::BufWriter::new(file);
+        write!(
+            file,
+            "tick,id,x,y,direction_x,direction_y,speed,energy,age,food_eaten"
+        )
+        .unwrap();
+        for locus in 0..GENE_LENGTH {
+            write!(file, ",gene_{locus}").unwrap();
+        }
+        writeln!(file).unwrap();
+        file
+    });
+    for (id, transform, gene, direction, speed, energy, age, food_eaten) in &query {
+        write!(
+            log,
+            "{},{},{},{},{},{},{},{},{},{}",
+            tick.0,
+            id.0,
+            transform.translation.x,
+            transform.translation.y,
+            direction.x,
+            direction.y,
+            speed.0,
+            energy.0,
+            age.0,
+            food_eaten.0
+        )
+        .unwrap();
+        for g in gene.0 {
+            write!(log, ",{g}").unwrap();
+        }
+        writeln!(log).unwrap();
+    }
+    // flushed every time so that nothing is lost when the window is closed
+    log.flush().unwrap();
+}
+
+pub(crate) fn startup(
+    mut commands: Commands,
+    config: Res<SimulationConfig>,
+    mut rng: ResMut<SimRng>,
+    mut genealogy: ResMut<Genealogy>,
+    saved: Option<Res<SavedWorld>>,
+) {
+    // Boundarys, none on a torus so nothing collides with them
+    if !is_torus(&config) {
+        commands.spawn(BoundaryBundle::new(BoundaryLocation::Left, &config));
+        commands.spawn(BoundaryBundle::new(BoundaryLocation::Right, &config));
+        commands.spawn(BoundaryBundle::new(BoundaryLocation::Bottom, &config));
+        commands.spawn(BoundaryBundle::new(BoundaryLocation::Top, &config));
+    }
+    if config.mirror_arena {
+        commands.spawn(BoundaryBundle::new(BoundaryLocation::Center, &config));
+    }
+    for obstacle in &config.obstacles {
+        let location = BoundaryLocation::Obstacle {
+            center: Vec2::from(obstacle.center),
+            size: Vec2::from(obstacle.size),
+        };
+        commands.spawn(BoundaryBundle::new(location, &config));
+    }
+    // the organisms of a saved world are spawned by restore_world instead
+    if saved.is_some() {
+        return;
+    }
+
+    // Organism
+    let founder = |position, direction| {
+        OrganismBundle::new(
+            &config,
+            GeneInfo::planned(&config),
+            position,
+            direction,
+            1.0,
+            0,
+        )
+    };
+    let min = Vec2::new(config.left_boundary, config.bottom_boundary);
+    let max = Vec2::new(config.right_boundary, config.top_boundary);
+    if config.mirror_arena {
+        // founders come in mirrored pairs so that both halves start out alike
+        let positions = founder_positions(
+            &config.founder_layout,
+            min,
+            Vec2::new(center_x(&config), max.y),
+            config.initial_population / 2,
+            &mut rng.0,
+        );
+        for position in positions {
+            let position = left_half(&config, position);
+            let direction = random_direction(&mut rng.0);
+            commands.spawn((founder(position, direction), genealogy.birth(None, 0)));
+            commands.spawn((
+                founder(
+                    mirror_position(&config, position),
+                    Vec2::new(-direction.x, direction.y),
+                ),
+                genealogy.birth(None, 0),
+            ));
+        }
+    } else {
+        let positions = founder_positions(
+            &config.founder_layout,
+            min,
+            max,
+            config.initial_population,
+            &mut rng.0,
+        );
+        for position in positions {
+            // founders laid out over an obstacle start at its edge
+            let position = push_out(&config, position.truncate()).extend(position.z);
+            commands.spawn((
+                founder(position, random_direction(&mut rng.0)),
+                genealogy.birth(None, 0),
+            ));
+        }
+    }
+}
+
+/// Whether the simulation is advancing, toggled with Space.
+#[derive(States, Default, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SimState {
+    #[default]
+    Running,
+    Paused,
+}
+
+/// Set to run exactly one fixed tick while paused.
+#[derive(Resource, Default)]
+pub struct SingleStep(pub bool);
+
+/// All the systems that advance the simulation by a fixed tick.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SimulationSet;
+
+pub(crate) fn simulation_running(state: Res<State<SimState>>, step: Res<SingleStep>) -> bool {
+    state.0 == SimState::Running || step.0
+}
+
+pub(crate) fn finish_step(mut step: ResMut<SingleStep>) {
+    step.0 = false;
+}
+
+/// Space pauses and resumes the simulation, `.` steps a single tick while
+/// paused.
+pub(crate) fn pause_controls(
+    keys: Res<Input<KeyCode>>,
+    state: Res<State<SimState>>,
+    mut next_state: ResMut<NextState<SimState>>,
+    mut step: ResMut<SingleStep>,
+) {
+    if keys.just_pressed(KeyCode::Space) {
+        next_state.set(match state.0 {
+            SimState::Running => SimState::Paused,
+            SimState::Paused => SimState::Running,
+        });
+    }
+    if keys.just_pressed(KeyCode::Period) && state.0 == SimState::Paused {
+        step.0 = true;
+    }
+}
+
+/// How many times faster than real time fixed ticks are run, changed at
+/// runtime with `+`/`-` and the number keys.
+///
+/// This only changes how often ticks happen, not what a tick does, so a run
+/// follows the same trajectory at any speed.
+#[derive(Resource)]
+pub struct SimulationSpeed(pub f32);
+
+impl Default for SimulationSpeed {
+    fn default() -> Self {
+        Self(1.0)
+    }
+}
+
+const SPEED_PRESETS: [f32; 5] = [1.0, 2.0, 5.0, 10.0, 20.0];
+
+pub(crate) fn apply_simulation_speed(speed: Res<SimulationSpeed>, mut time: ResMut<Time>) {
+    if speed.is_changed() {
+        time.set_relative_speed(speed.0);
+    }
+}
+
+pub(crate) fn speed_controls(keys: Res<Input<KeyCode>>, mut speed: ResMut<SimulationSpeed>) {
+    let preset_keys = [
+        KeyCode::Key1,
+        KeyCode::Key2,
+        KeyCode::Key3,
+        KeyCode::Key4,
+        KeyCode::Key5,
+    ];
+    let new_speed = if keys.any_just_pressed([KeyCode::Equals, KeyCode::NumpadAdd]) {
+        speed.0 * 2.0
+    } else if keys.any_just_pressed([KeyCode::Minus, KeyCode::NumpadSubtract]) {
+        speed.0 / 2.0
+    } else if let Some(i) = preset_keys.iter().position(|k| keys.just_pressed(*k)) {
+        SPEED_PRESETS[i]
+    } else {
+        return;
+    };
+    speed.0 = new_speed.clamp(SPEED_PRESETS[0] / 4.0, SPEED_PRESETS[4]);
+    info!("simulation speed: {}x", speed.0);
+}
+
+pub(crate) fn advance_tick(mut tick: ResMut<SimTick>) {
+    tick.0 += 1;
+}
+
+pub(crate) fn play_collision_sound(
+    mut collision_events: EventReader<CollisionEvent>,
+    audio: Res<Audio>,
+    collision: Res<CollisionSound>,
+    feeding: Res<FeedingSound>,
+) {
+    if !collision_events.is_empty() {
+        for event in &mut collision_events {
+            match event {
+                CollisionEvent::Food => {
+                    audio.play(feeding.0.clone());
+                }
+                CollisionEvent::Wall => {
+                    audio.play(collision.0.clone());
+                }
+            };
+        }
+        collision_events.clear();
+    }
+}
+
+/// Systems that only exist to draw or play the simulation, left out in
+/// headless mode.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct VisualsSet;
+
+pub struct VisualsPlugin;
+
+impl Plugin for VisualsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ClearColor(BACKGROUND_COLOR))
+            .add_startup_system(setup_visuals)
+            .add_startup_system(setup_inspector)
+            .add_startup_system(setup_hud)
+            .add_startup_system(setup_timeline)
+            .add_startup_system(setup_genome_plot)
+            .add_startup_system(setup_pheromone_layer)
+            .init_resource::<Timeline>()
+            .init_resource::<CameraFollow>()
+            .init_resource::<GenomeProjection>()
+            .init_resource::<GenomeLibrary>()
+            .init_resource::<EnergyBarsShown>()
+            .add_plugin(EguiPlugin)
+            .add_systems(
+                (
+                    add_visuals,
+                    draw_pheromones,
+                    food_fade
+                        .after(add_visuals)
+                        .run_if(in_state(SimState::Running)),
+                    play_collision_sound.run_if(|config: Res<SimulationConfig>| config.play_sounds),
+                    dump_elite_archive,
+                    pause_controls,
+                    speed_controls,
+                    select_organism,
+                    update_selection_ring.after(select_organism),
+                    update_vision_circle.after(select_organism),
+                    update_interaction_circle.after(select_organism),
+                    update_inspector.after(select_organism),
+                    update_hud,
+                    add_glow,
+                    update_glow.after(add_glow),
+                )
+                    .in_set(VisualsSet),
+            )
+            .add_systems(
+                (
+                    update_timeline.after(select_organism),
+                    update_timeline_panel.after(update_timeline),
+                    update_genome_plot,
+                    respawn_from_hall_of_fame,
+                    request_save,
+                    export_genealogy,
+                    camera_controls,
+                    follow_camera.after(camera_controls).after(select_organism),
+                    control_food_source,
+                    genome_library_window,
+                    add_energy_bars,
+                    update_energy_bars.after(add_energy_bars),
+                    add_heading_ticks,
+                    sync_orientation.after(add_heading_ticks),
+                )
+                    .in_set(VisualsSet),
+            )
+            .add_systems(
+                (
+                    remove_pregnancy_outlines,
+                    add_pregnancy_outlines.after(remove_pregnancy_outlines),
+                )
+                    .in_set(VisualsSet),
+            )
+            .add_systems(
+                (
+                    update_genome_projection.after(log_things),
+                    feed_from_source.after(generate_food),
+                    log_food_source_alignment.after(log_things),
+                    update_genome_library.after(log_things),
+                )
+                    .in_set(SimulationSet)
+                    .distributive_run_if(simulation_running)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            );
+    }
+}
+
+pub struct EvolutionPlugin;
+
+impl Plugin for EvolutionPlugin {
+    fn build(&self, app: &mut App) {
+        let config = app
+            .world
+            .get_resource_or_insert_with(SimulationConfig::default)
+            .clone();
+        let seed = config.seed.unwrap_or_else(rand::random);
+        info!("random seed: {seed}");
+        app.insert_resource(SimRng(ChaCha8Rng::seed_from_u64(seed)))
+            .insert_resource(SelectionAmplification(config.selection_amplification))
+            .insert_resource(FoodTimer(Timer::from_seconds(
+                0.2 / config.simulation_speed,
+                TimerMode::Repeating,
+            )))
+            .insert_resource(SensoryTimer(
+                Timer::from_seconds(
+                    0.5 / config.simulation_speed / SENSORY_STAGGER_N as f32,
+                    TimerMode::Repeating,
+                ),
+                0,
+            ))
+            .insert_resource(AgeTimer(Timer::from_seconds(
+                1.0 / config.simulation_speed,
+                TimerMode::Repeating,
+            )))
+            .insert_resource(LogTimer(Timer::from_seconds(
+                10.0 / config.simulation_speed,
+                TimerMode::Repeating,
+            )))
+            .add_startup_system(startup)
+            .add_startup_system(restore_world.after(startup))
+            .add_startup_system(spawn_traps)
+            .init_resource::<SaveRequested>()
+            .insert_resource(FoodRate(config.food_per_timestep))
+            .init_resource::<FoodController>()
+            .init_resource::<SimTick>()
+            .init_resource::<SurvivalModel>()
+            .init_resource::<MutationLog>()
+            .init_resource::<FoodPatchiness>()
+            .init_resource::<SimStats>()
+            .init_resource::<TimestepProfiler>()
+            .init_resource::<GrowthCurve>()
+            .init_resource::<Census>()
+            .init_resource::<DiversityMetrics>()
+            .init_resource::<CommunityStability>()
+            .init_resource::<PheromoneNetwork>()
+            .init_resource::<Genealogy>()
+            .init_resource::<RepertoireLifespan>()
+            .init_resource::<StrategyFrequency>()
+            .init_resource::<StrategyCompetition>()
+            .init_resource::<SharedSensory>()
+            .init_resource::<GenerationStats>()
+            .init_resource::<EcosystemServiceMetric>()
+            .init_resource::<NetworkTopology>()
+            .add_event::<DiversityAlert>()
+            .init_resource::<GenomicIslands>()
+            .init_resource::<Conservation>()
+            .init_resource::<SpatialGrid>()
+            .init_resource::<RDGrid>()
+            .init_resource::<SoilNutrient>()
+            .init_resource::<FoodPatches>()
+            .init_resource::<PheromoneField>()
+            .insert_resource(EliteArchive::new(config.elite_archive_capacity))
+            .insert_resource(HallOfFame(EliteArchive::new(config.hall_of_fame_capacity)))
+            .add_event::<CollisionEvent>()
+            // deaths are sent at the end of a tick and read during the next
+            // one, so they are kept for two ticks instead of two frames
+            .init_resource::<Events<DeathEvent>>()
+            .add_system(
+                Events::<DeathEvent>::update_system
+                    .before(SimulationSet)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            )
+            .add_event::<LifeEvent>()
+            .add_state::<SimState>()
+            .init_resource::<SingleStep>()
+            .init_resource::<SimulationSpeed>()
+            .add_system(apply_simulation_speed)
+            .add_systems(
+                (
+                    log_things,
+                    log_mirror_halves.after(log_things),
+                    log_environment.after(log_things),
+                    log_memory.after(log_things).after(adjust_direction),
+                    update_growth_curve.after(log_things).after(apply_direction),
+                    take_census
+                        .after(update_sim_stats)
+                        .after(update_growth_curve),
+                    update_genomic_islands.after(grow_organism),
+                    update_diversity.after(log_things).after(grow_organism),
+                    write_genome_snapshot.after(grow_organism),
+                    update_reaction_diffusion.before(generate_food),
+                    spring_traps
+                        .after(apply_direction)
+                        .before(check_for_collisions),
+                    update_hall_of_fame.after(update_elite_archive),
+                    accumulate_survivability.after(grow_organism),
+                    log_deaths.after(update_elite_archive),
+                    compete
+                        .after(update_spatial_grid)
+                        .after(consume_stash)
+                        .before(grow_organism),
+                )
+                    .in_set(SimulationSet)
+                    .distributive_run_if(simulation_running)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            )
+            .add_systems(
+                (
+                    update_sim_stats
+                        .after(grow_organism)
+                        .after(generate_food)
+                        .after(adjust_direction),
+                    generate_food.after(grow_organism),
+                    age_progression.after(apply_direction),
+                    // organisms share energy, ages and the rng, so these run
+                    // in a fixed order to keep runs reproducible
+                    check_for_collisions
+                        .after(apply_direction)
+                        .after(age_progression),
+                    apply_direction.before(adjust_direction),
+                    update_spatial_grid
+                        .after(apply_direction)
+                        .before(check_for_collisions)
+                        .before(adjust_direction),
+                    consume_stash.after(check_for_collisions),
+                    grow_organism.after(consume_stash),
+                    update_size.after(grow_organism),
+                    control_food_supply
+                        .after(age_progression)
+                        .before(generate_food),
+                    advance_tick.before(logistic_survival_model),
+                    logistic_survival_model
+                        .after(apply_direction)
+                        .after(age_progression)
+                        .after(grow_organism),
+                    adjust_direction.after(check_for_collisions),
+                    separate_overlapping
+                        .after(adjust_direction)
+                        .after(update_size),
+                    update_elite_archive
+                        .after(apply_direction)
+                        .after(age_progression)
+                        .after(grow_organism),
+                )
+                    .in_set(SimulationSet)
+                    .distributive_run_if(simulation_running)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            )
+            .add_systems(
+                (
+                    update_community_stability.after(grow_organism),
+                    analyze_network.after(adjust_direction),
+                    record_behavior.after(adjust_direction),
+                    measure_competition
+                        .after(update_spatial_grid)
+                        .before(check_for_collisions),
+                    log_competition.after(log_things).after(measure_competition),
+                    share_sensory
+                        .after(adjust_direction)
+                        .before(separate_overlapping),
+                    log_swarm.after(log_things).after(share_sensory),
+                    update_generation_stats.after(log_things),
+                    correlate_repertoire,
+                    update_ecosystem_service
+                        .after(log_things)
+                        .before(grow_organism),
+                    diffuse_nutrients.before(generate_food),
+                    // the rng is drawn from in a fixed order
+                    drift_food_patches
+                        .after(grow_organism)
+                        .before(generate_food),
+                    // draws from the rng before everything else in the tick
+                    run_and_tumble.before(apply_direction),
+                    decay_food
+                        .after(age_progression)
+                        .before(check_for_collisions),
+                    update_pheromone_field
+                        .after(adjust_direction)
+                        .before(update_sim_stats),
+                )
+                    .in_set(SimulationSet)
+                    .distributive_run_if(simulation_running)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            )
+            .add_system(
+                burrow
+                    .after(update_spatial_grid)
+                    .before(check_for_collisions)
+                    .in_set(SimulationSet)
+                    .run_if(simulation_running)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            )
+            .add_system(
+                log_population
+                    .after(log_things)
+                    .after(update_sim_stats)
+                    .in_set(SimulationSet)
+                    .run_if(simulation_running)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            )
+            .add_system(
+                log_pheromone_sensitivity
+                    .after(log_things)
+                    .in_set(SimulationSet)
+                    .run_if(simulation_running)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            )
+            .add_system(
+                start_tick
+                    .before(SimulationSet)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            )
+            .add_systems(
+                (
+                    checkpoint("update_spatial_grid").after(update_spatial_grid),
+                    checkpoint("check_for_collisions").after(check_for_collisions),
+                    checkpoint("adjust_direction").after(adjust_direction),
+                    checkpoint("grow_organism").after(grow_organism),
+                    checkpoint("generate_food").after(generate_food),
+                    checkpoint("update_reaction_diffusion").after(update_reaction_diffusion),
+                    checkpoint("logistic_survival_model").after(logistic_survival_model),
+                    checkpoint("update_diversity").after(update_diversity),
+                    checkpoint("write_genome_snapshot").after(write_genome_snapshot),
+                    checkpoint("take_census").after(take_census),
+                )
+                    .in_set(SimulationSet)
+                    .distributive_run_if(simulation_running)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            )
+            // the Dead markers inserted during the tick have to be applied
+            // for reap_dead to see them
+            .add_systems(
+                (
+                    apply_system_buffers,
+                    hatch_eggs,
+                    write_mutation_log,
+                    return_nutrients,
+                    drop_corpses,
+                    reap_dead,
+                    save_world,
+                    finish_step,
+                    finish_tick,
+                )
+                    .chain()
+                    .after(SimulationSet)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            )
+            .insert_resource(FixedTime::new_from_secs(TIME_STEP));
+    }
+}
+
+/// Moves the clock exactly one fixed step past its last update, however
+/// long the update took. `TimeUpdateStrategy::ManualDuration` would add the
+/// step to the current instant instead.
+fn step_clock(time: Res<Time>, mut strategy: ResMut<TimeUpdateStrategy>) {
+    let last_update = time.last_update().unwrap_or_else(|| time.startup());
+    *strategy = TimeUpdateStrategy::ManualInstant(last_update + Duration::from_secs_f32(TIME_STEP));
+}
+
+/// An app without a window whose every update runs one tick as fast as
+/// possible. The logger can only be set up by the first app of the process.
+pub fn headless_app(config: SimulationConfig, saved: Option<SavedWorld>, log: bool) -> App {
+    let mut app = App::new();
+    app.insert_resource(config);
+    if let Some(saved) = saved {
+        app.insert_resource(saved);
+    }
+    app.add_plugins(MinimalPlugins);
+    if log {
+        app.add_plugin(LogPlugin::default());
+    }
+    app.add_system(step_clock.in_base_set(CoreSet::First).before(TimeSystem))
+        .add_plugin(EvolutionPlugin);
+    app
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Once;
+
+    use super::*;
+    use crate::config::FoodSupply;
+
+    /// A headless app that also gives everything its circle, as the visuals
+    /// would, with the simulation's logs going to a scratch directory.
+    fn app_with_circles(config: SimulationConfig) -> App {
+        static SCRATCH: Once = Once::new();
+        SCRATCH.call_once(|| {
+            let dir = std::env::temp_dir().join("evolution-game-tests");
+            std::fs::create_dir_all(&dir).unwrap();
+            std::env::set_current_dir(dir).unwrap();
+        });
+        let mut app = headless_app(config, None, false);
+        app.add_plugin(AssetPlugin::default())
+            .add_asset::<Mesh>()
+            .add_asset::<ColorMaterial>()
+            .add_systems(
+                (
+                    add_visuals,
+                    food_fade
+                        .after(add_visuals)
+                        .run_if(in_state(SimState::Running)),
+                )
+                    .in_set(VisualsSet),
+            );
+        let circles = app
+            .world
+            .resource_scope(|world, mut meshes: Mut<Assets<Mesh>>| {
+                CircleAssets::new(
+                    &mut meshes,
+                    &mut world.resource_mut::<Assets<ColorMaterial>>(),
+                )
+            });
+        app.insert_resource(circles);
+        app.setup();
+        app
+    }
+
+    /// Every entity drawn with the one circle mesh and a cached material, so
+    /// that over a 20k tick run the material store stays far smaller than
+    /// the number of organisms and food spawned.
+    #[test]
+    #[ignore = "takes about a minute"]
+    fn assets_are_shared_in_a_long_run() {
+        let mut app = app_with_circles(SimulationConfig {
+            seed: Some(2),
+            poison_probability: 0.1,
+            food_supply: FoodSupply::Controller {
+                target_ratio: 10.0,
+                kp: 0.5,
+                ki: 0.05,
+                min_food_per_timestep: 2,
+                max_food_per_timestep: 4,
+            },
+            ..default()
+        });
+        for k in 1..=20 {
+            while app.world.resource::<SimTick>().0 < k * 1000 {
+                app.update();
+            }
+            let materials = app.world.resource::<Assets<ColorMaterial>>().len();
+            let cached = app.world.resource::<CircleAssets>().materials.len();
+            assert_eq!(app.world.resource::<Assets<Mesh>>().len(), 1);
+            // the cache plus the plain food material
+            assert_eq!(materials, cached + 1);
+        }
+        assert!(
+            app.world.query::<&Organism>().iter(&app.world).count() > 0,
+            "the population died out"
+        );
+        let materials = app.world.resource::<Assets<ColorMaterial>>().len() as u64;
+        let stats = app.world.resource::<SimStats>();
+        let spawned = stats.births + stats.food_eaten;
+        assert!(
+            materials * 10 < spawned,
+            "{materials} materials for {spawned} organisms and food"
+        );
+    }
+}
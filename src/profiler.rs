@@ -0,0 +1,138 @@
+//! Wall clock timing of the fixed ticks, to find which systems become the
+//! bottleneck as the population grows.
+
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+use bevy::prelude::*;
+
+use crate::food::FoodTimer;
+use crate::organism::SensoryTimer;
+use crate::plugin::{AgeTimer, LogTimer};
+use crate::stats::SimStats;
+use crate::{SimTick, TIME_STEP};
+
+/// Ticks that take longer than this many `TIME_STEP`s of wall time are
+/// logged.
+const SLOW_TICK_STEPS: f32 = 2.0;
+
+/// When the tick being simulated started, and when the systems followed by a
+/// [`checkpoint`] finished since.
+#[derive(Resource, Default)]
+pub struct TimestepProfiler {
+    start: Option<Instant>,
+    checkpoints: Vec<(&'static str, Duration)>,
+    /// Ticks slower than `SLOW_TICK_STEPS` time steps so far.
+    pub slow_ticks: u64,
+    pub slowest: Duration,
+}
+
+impl TimestepProfiler {
+    /// Time spent on every checkpointed system, estimated as the time since
+    /// the checkpoint that finished before it. Systems run in parallel, so
+    /// this blames the one that held up the tick rather than the one that
+    /// did the most work.
+    pub fn estimates(&self) -> Vec<(&'static str, Duration)> {
+        let mut checkpoints = self.checkpoints.clone();
+        checkpoints.sort_by_key(|&(_, finished)| finished);
+        let mut previous = Duration::ZERO;
+        checkpoints
+            .into_iter()
+            .map(|(system, finished)| {
+                let estimate = finished - previous;
+                previous = finished;
+                (system, estimate)
+            })
+            .collect()
+    }
+}
+
+pub(crate) fn start_tick(mut profiler: ResMut<TimestepProfiler>) {
+    profiler.start = Some(Instant::now());
+    profiler.checkpoints.clear();
+}
+
+/// A system that records when `system`, which it is ordered after, finished.
+pub(crate) fn checkpoint(system: &'static str) -> impl FnMut(ResMut<TimestepProfiler>) {
+    move |mut profiler: ResMut<TimestepProfiler>| {
+        if let Some(start) = profiler.start {
+            profiler.checkpoints.push((system, start.elapsed()));
+        }
+    }
+}
+
+/// Logs the tick if it was slow, with the timers that fired during it since
+/// those decide which systems had real work to do.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn finish_tick(
+    tick: Res<SimTick>,
+    stats: Res<SimStats>,
+    mut profiler: ResMut<TimestepProfiler>,
+    mut log: Local<Option<std::io::BufWriter<std::fs::File>>>,
+    sensory_timer: Res<SensoryTimer>,
+    food_timer: Res<FoodTimer>,
+    age_timer: Res<AgeTimer>,
+    log_timer: Res<LogTimer>,
+) {
+    let Some(start) = profiler.start.take() else {
+        return;
+    };
+    // nothing was simulated while paused
+    if profiler.checkpoints.is_empty() {
+        return;
+    }
+    let elapsed = start.elapsed();
+    profiler.slowest = profiler.slowest.max(elapsed);
+    if elapsed.as_secs_f32() <= SLOW_TICK_STEPS * TIME_STEP {
+        return;
+    }
+    profiler.slow_ticks += 1;
+
+    let timers: Vec<&str> = [
+        ("sensory", &sensory_timer.0),
+        ("food", &food_timer.0),
+        ("age", &age_timer.0),
+        ("log", &log_timer.0),
+    ]
+    .into_iter()
+    .filter(|(_, timer)| timer.just_finished())
+    .map(|(name, _)| name)
+    .collect();
+    let estimates = profiler.estimates();
+    let slowest = estimates
+        .iter()
+        .max_by_key(|&&(_, estimate)| estimate)
+        .map_or("none", |&(system, _)| system);
+    warn!(
+        "tick {} took {:.1} ms with {} organisms, mostly in {slowest}, timers fired: {}",
+        tick.0,
+        elapsed.as_secs_f64() * 1000.0,
+        stats.organisms,
+        if timers.is_empty() {
+            "none".to_string()
+        } else {
+            timers.join(" ")
+        },
+    );
+
+    let log = log.get_or_insert_with(|| {
+        let file = std::fs::File::create("slow_ticks.csv").unwrap();
+        let mut file = std::io::BufWriter::new(file);
+        file.write_all(b"tick,organisms,tick_ms,timers,system,estimated_ms\n")
+            .unwrap();
+        file
+    });
+    for (system, estimate) in estimates {
+        writeln!(
+            log,
+            "{},{},{},{},{},{}",
+            tick.0,
+            stats.organisms,
+            elapsed.as_secs_f64() * 1000.0,
+            timers.join(" "),
+            system,
+            estimate.as_secs_f64() * 1000.0
+        )
+        .unwrap();
+    }
+}
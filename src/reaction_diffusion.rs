@@ -0,0 +1,107 @@
+//! A Gray-Scott reaction-diffusion model over the arena whose V chemical
+//! sets where food spawns, so that food follows the spots and stripes the
+//! model forms as they drift and split.
+
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::config::SimulationConfig;
+use crate::random_position;
+use crate::SimRng;
+
+/// Cells along each side of the grid.
+pub const GRID_SIDE: usize = 64;
+pub const GRID_SIZE: usize = GRID_SIDE * GRID_SIDE;
+/// Squares of V the grid starts with, to seed the patterns.
+const SEEDS: usize = 10;
+/// Side in cells of every seed square.
+const SEED_SIDE: usize = 4;
+
+/// U and V concentrations of every grid cell, row by row from the bottom
+/// left corner of the arena. The grid wraps around at its edges.
+#[derive(Resource)]
+pub struct RDGrid(pub [[f32; 2]; GRID_SIZE]);
+
+impl FromWorld for RDGrid {
+    fn from_world(world: &mut World) -> Self {
+        let mut grid = [[1.0, 0.0]; GRID_SIZE];
+        // only draw from the rng when the model is used, so that runs
+        // without it stay the same
+        if world
+            .resource::<SimulationConfig>()
+            .reaction_diffusion
+            .is_some()
+        {
+            let rng = &mut world.resource_mut::<SimRng>().0;
+            for _ in 0..SEEDS {
+                let x = rng.gen_range(0..GRID_SIDE);
+                let y = rng.gen_range(0..GRID_SIDE);
+                for dy in 0..SEED_SIDE {
+                    for dx in 0..SEED_SIDE {
+                        let cell = (y + dy) % GRID_SIDE * GRID_SIDE + (x + dx) % GRID_SIDE;
+                        grid[cell] = [0.5, 0.25];
+                    }
+                }
+            }
+        }
+        Self(grid)
+    }
+}
+
+impl RDGrid {
+    /// A random position in the arena, picked with a probability
+    /// proportional to the V concentration there. Uniform while there is
+    /// no V at all.
+    pub fn sample_position(&self, config: &SimulationConfig, rng: &mut impl Rng) -> Vec3 {
+        let total: f32 = self.0.iter().map(|[_, v]| v.max(0.0)).sum();
+        if total <= 0.0 {
+            return random_position(config, rng);
+        }
+        let mut target = rng.gen::<f32>() * total;
+        let cell = self
+            .0
+            .iter()
+            .position(|[_, v]| {
+                target -= v.max(0.0);
+                target < 0.0
+            })
+            .unwrap_or(GRID_SIZE - 1);
+        let (x, y): (f32, f32) = rng.gen();
+        let cell_size = Vec2::new(
+            config.right_boundary - config.left_boundary,
+            config.top_boundary - config.bottom_boundary,
+        ) / GRID_SIDE as f32;
+        Vec3::new(
+            config.left_boundary + ((cell % GRID_SIDE) as f32 + x) * cell_size.x,
+            config.bottom_boundary + ((cell / GRID_SIDE) as f32 + y) * cell_size.y,
+            0.0,
+        )
+    }
+}
+
+/// Advances the model by one step of the explicit Euler method.
+pub(crate) fn update_reaction_diffusion(config: Res<SimulationConfig>, mut grid: ResMut<RDGrid>) {
+    let Some(rd) = &config.reaction_diffusion else {
+        return;
+    };
+    let previous = grid.0;
+    let at = |x: usize, y: usize| previous[y % GRID_SIDE * GRID_SIDE + x % GRID_SIDE];
+    for y in 0..GRID_SIDE {
+        for x in 0..GRID_SIDE {
+            let [u, v] = at(x, y);
+            let neighbors = [
+                at(x + GRID_SIDE - 1, y),
+                at(x + 1, y),
+                at(x, y + GRID_SIDE - 1),
+                at(x, y + 1),
+            ];
+            let laplacian =
+                |c: usize| neighbors.iter().map(|n| n[c]).sum::<f32>() - 4.0 * [u, v][c];
+            let reaction = u * v * v;
+            grid.0[y * GRID_SIDE + x] = [
+                u + rd.diffusion_u * laplacian(0) - reaction + rd.feed * (1.0 - u),
+                v + rd.diffusion_v * laplacian(1) + reaction - (rd.feed + rd.kill) * v,
+            ];
+        }
+    }
+}
@@ -0,0 +1,661 @@
+//! Saving the whole simulation to a JSON file, on F5 and every
+//! `saves.autosave_interval` ticks, and starting a run from one with
+//! `--load <file>`.
+
+use std::time::Duration;
+
+use bevy::prelude::*;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::behavior::{BehaviorArchetype, BehaviorRepertoire, StrategyFrequency};
+use crate::burrow::Burrowed;
+use crate::config::{FoodDistribution, SimulationConfig};
+use crate::egg::{Egg, EGG_SIZE};
+use crate::food::{Corpse, Food, FoodController, FoodRate, FoodTimer, Poison, FOOD_SIZE};
+use crate::genealogy::{Genealogy, OrganismId, ParentId};
+use crate::genes::{Conservation, GeneInfo, GenomicIslands, GENE_LENGTH, GENE_VERSION, N_INPUTS};
+use crate::nutrient::SoilNutrient;
+use crate::organism::{
+    BirthTick, CarbonProcessed, Direction, EnergyRecord, EvaluationOffset, FastMemory,
+    FoodDetectionHistory, FoodEaten, FoodStash, Generation, InteractionRadius, LastBrainState,
+    Organism, OrganismBundle, PartnerGenes, Poisoned, Pregnant, RunState, SensoryInputs,
+    SensoryTimer, SlowMemory, Speed, SurvivabilityScore, SurvivalModel, SurvivalProbability,
+    TimeSinceFood,
+};
+use crate::patches::{FoodPatch, FoodPatches};
+use crate::pheromone::{PheromoneField, PHEROMONE_CHANNELS};
+use crate::plugin::{AgeTimer, LogTimer};
+use crate::reaction_diffusion::RDGrid;
+use crate::stats::SimStats;
+use crate::swarm::SharedSensory;
+use crate::{Age, Collider, Dead, Energy, Lifetime, SimRng, SimTick};
+
+/// Everything that decides how the simulation goes on from a tick. The
+/// config is not part of it, a save is loaded with the config it was made
+/// with.
+#[derive(Resource, Serialize, Deserialize)]
+pub struct SavedWorld {
+    pub gene_version: u32,
+    pub tick: u64,
+    pub rng: SavedRng,
+    /// Elapsed seconds of the food, sensory, age and log timers.
+    pub timers: [f32; 4],
+    /// Times the sensory timer fired, to keep evaluating the same organisms
+    /// on the same sensory ticks.
+    pub sensory_ticks: u64,
+    pub food_rate: usize,
+    pub food_controller_integral: f32,
+    /// Only kept when food follows the reaction-diffusion model.
+    pub rd_grid: Option<Vec<[f32; 2]>>,
+    /// Only kept with nutrient cycling.
+    pub soil: Option<Vec<f32>>,
+    /// Center and heading of every food patch, only kept with patchy food.
+    pub food_patches: Option<Vec<[[f32; 2]; 2]>>,
+    pub conceptions: u64,
+    pub births: u64,
+    pub deaths: u64,
+    pub trap_deaths: u64,
+    pub poison_deaths: u64,
+    pub food_eaten: u64,
+    pub kills: u64,
+    pub corpses_eaten: u64,
+    pub poison_eaten: u64,
+    pub eggs_eaten: u64,
+    pub genealogy: Genealogy,
+    /// Loci protected from mutation.
+    pub genomic_islands: Vec<bool>,
+    /// Whether every locus ever varied and the ticks in a row it stayed
+    /// conserved.
+    pub conservation: (Vec<bool>, Vec<usize>),
+    /// Fraction of the organisms that followed each strategy on the last
+    /// tick, by strategy.
+    pub strategy_frequency: Vec<(BehaviorArchetype, f32)>,
+    /// Weights and bias of the survival model.
+    pub survival_model: (Vec<f32>, f32),
+    /// Mean pool of the organisms in a swarm on the last sensory tick.
+    pub swarm_food_signal: f32,
+    /// The organisms and the food in the order the systems went through
+    /// them, so that they are restored in the same order.
+    pub organisms: Vec<SavedOrganism>,
+    pub food: Vec<SavedFood>,
+    /// The channels of every cell of the pheromone field.
+    pub pheromone_field: Vec<[f32; PHEROMONE_CHANNELS]>,
+}
+
+/// Where the [`SimRng`] stream is at.
+#[derive(Serialize, Deserialize)]
+pub struct SavedRng {
+    pub seed: [u8; 32],
+    pub stream: u64,
+    pub word_pos: u128,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SavedOrganism {
+    /// The [`OrganismId`], its parent is in the saved genealogy.
+    pub id: u64,
+    pub gene: GeneInfo,
+    pub position: [f32; 3],
+    pub direction: [f32; 2],
+    pub speed: f32,
+    pub energy: f32,
+    /// Interaction radius, as of the last time it followed the energy.
+    pub radius: f32,
+    pub age: usize,
+    pub lifetime: usize,
+    /// Ticks pregnant so far, none when not pregnant.
+    pub pregnant: Option<u64>,
+    pub partner_gene: Option<GeneInfo>,
+    pub energy_record: f32,
+    pub time_since_food: usize,
+    /// Last tick food was seen on the left, front and right.
+    pub food_history: [u64; 3],
+    /// Smoothed turn and speed outputs.
+    pub brain: [f32; 2],
+    /// Network inputs of the last sensory tick.
+    pub inputs: [f32; N_INPUTS],
+    /// Tick poisonous food was last eaten on.
+    pub poisoned: Option<u64>,
+    /// Neighbors in swarm range and the swarm's pooled food signal on the
+    /// last sensory tick, when swarm sensing is on.
+    pub swarm: (Option<usize>, Option<f32>),
+    pub survival: f32,
+    pub stash: f32,
+    pub birth_tick: u64,
+    pub food_eaten: u32,
+    pub carbon_processed: f32,
+    pub survivability: f32,
+    /// Ticks left in the current run and the food sensed when it started.
+    pub run: (usize, f32),
+    /// Ticks left underground.
+    pub burrowed: usize,
+    /// Food sightings in the fast memory, oldest first.
+    pub fast_memory: Vec<[f32; 3]>,
+    /// Meals in the slow memory, oldest first.
+    pub slow_memory: Vec<[f32; 3]>,
+    pub repertoire: Vec<BehaviorArchetype>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SavedFood {
+    pub position: [f32; 3],
+    pub age: usize,
+    pub lifetime: usize,
+    pub energy: f32,
+    /// Energy the food had when it was dropped, for corpse food.
+    pub corpse: Option<f32>,
+    pub poison: bool,
+    /// Parent id and genes of an egg.
+    pub egg: Option<(u64, GeneInfo)>,
+}
+
+impl SavedWorld {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let file =
+            std::fs::File::open(path).map_err(|e| format!("could not read save {path}: {e}"))?;
+        let saved: Self = serde_json::from_reader(std::io::BufReader::new(file))
+            .map_err(|e| format!("invalid save {path}: {e}"))?;
+        if saved.gene_version != GENE_VERSION {
+            return Err(format!(
+                "save {path} has genes of version {}, this build uses version {GENE_VERSION}",
+                saved.gene_version
+            ));
+        }
+        if [
+            saved.genomic_islands.len(),
+            saved.conservation.0.len(),
+            saved.conservation.1.len(),
+            saved.survival_model.0.len(),
+        ]
+        .iter()
+        .any(|&len| len != GENE_LENGTH)
+        {
+            return Err(format!(
+                "save {path} has per locus state of the wrong length"
+            ));
+        }
+        Ok(saved)
+    }
+}
+
+/// Set to save the simulation at the end of the current tick.
+#[derive(Resource, Default)]
+pub struct SaveRequested(pub bool);
+
+pub(crate) fn request_save(keys: Res<Input<KeyCode>>, mut requested: ResMut<SaveRequested>) {
+    if keys.just_pressed(KeyCode::F5) {
+        requested.0 = true;
+    }
+}
+
+/// Writes the world to `saves.path` when asked to or when an autosave is
+/// due, after the dead of the tick are gone.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub(crate) fn save_world(
+    config: Res<SimulationConfig>,
+    tick: Res<SimTick>,
+    rng: Res<SimRng>,
+    stats: Res<SimStats>,
+    timers: (
+        Res<FoodTimer>,
+        Res<SensoryTimer>,
+        Res<AgeTimer>,
+        Res<LogTimer>,
+    ),
+    food_rate: Res<FoodRate>,
+    controller: Res<FoodController>,
+    (rd_grid, soil, patches, field): (
+        Res<RDGrid>,
+        Res<SoilNutrient>,
+        Res<FoodPatches>,
+        Res<PheromoneField>,
+    ),
+    genealogy: Res<Genealogy>,
+    (islands, conservation, frequency, survival_model, shared): (
+        Res<GenomicIslands>,
+        Res<Conservation>,
+        Res<StrategyFrequency>,
+        Res<SurvivalModel>,
+        Res<SharedSensory>,
+    ),
+    mut requested: ResMut<SaveRequested>,
+    organism_query: Query<
+        (
+            (
+                &GeneInfo,
+                &Transform,
+                &Direction,
+                &Speed,
+                &Energy,
+                &InteractionRadius,
+                &Age,
+                &Lifetime,
+                Option<&Pregnant>,
+                &PartnerGenes,
+                &EnergyRecord,
+                &Burrowed,
+            ),
+            (
+                &TimeSinceFood,
+                &FoodDetectionHistory,
+                &LastBrainState,
+                &SurvivalProbability,
+                &FoodStash,
+                &BirthTick,
+                &FoodEaten,
+                &SurvivabilityScore,
+                &OrganismId,
+                &BehaviorRepertoire,
+                &RunState,
+                (&FastMemory, &SlowMemory),
+                &CarbonProcessed,
+                (Entity, &SensoryInputs, &Poisoned),
+            ),
+        ),
+        (With<Organism>, Without<Dead>),
+    >,
+    food_query: Query<
+        (
+            &Transform,
+            &Age,
+            &Lifetime,
+            &Energy,
+            Option<&Corpse>,
+            Option<&Poison>,
+            Option<&Egg>,
+        ),
+        (With<Food>, Without<Dead>),
+    >,
+) {
+    let autosave = config
+        .saves
+        .autosave_interval
+        .is_some_and(|interval| tick.0.is_multiple_of(interval));
+    if !(requested.0 || autosave) {
+        return;
+    }
+    requested.0 = false;
+
+    let organisms = organism_query
+        .iter()
+        .map(
+            |(
+                (
+                    gene,
+                    transform,
+                    direction,
+                    speed,
+                    energy,
+                    radius,
+                    age,
+                    lifetime,
+                    pregnant,
+                    partner,
+                    record,
+                    burrowed,
+                ),
+                (
+                    since_food,
+                    history,
+                    brain,
+                    survival,
+                    stash,
+                    birth,
+                    eaten,
+                    survivability,
+                    id,
+                    repertoire,
+                    run,
+                    (fast_memory, slow_memory),
+                    carbon,
+                    (entity, inputs, poisoned),
+                ),
+            )| SavedOrganism {
+                id: id.0,
+                gene: gene.clone(),
+                position: transform.translation.to_array(),
+                direction: direction.to_array(),
+                speed: speed.0,
+                energy: energy.0,
+                radius: radius.0,
+                age: age.0,
+                lifetime: lifetime.0,
+                pregnant: pregnant.map(|pregnant| pregnant.0),
+                partner_gene: partner.0.clone(),
+                energy_record: record.0,
+                time_since_food: since_food.0,
+                food_history: [history.left, history.front, history.right],
+                brain: [brain.turn, brain.speed],
+                inputs: inputs.0,
+                poisoned: poisoned.0,
+                swarm: (
+                    shared.neighbors.get(&entity).copied(),
+                    shared.pools.get(&entity).copied(),
+                ),
+                survival: survival.0,
+                stash: stash.0,
+                birth_tick: birth.0,
+                food_eaten: eaten.0,
+                carbon_processed: carbon.0,
+                survivability: survivability.0,
+                run: (run.ticks_remaining, run.food_signal),
+                burrowed: burrowed.0,
+                fast_memory: fast_memory.positions.iter().map(|p| p.to_array()).collect(),
+                slow_memory: slow_memory.positions.iter().map(|p| p.to_array()).collect(),
+                // sorted so that saves of the same world are the same
+                repertoire: {
+                    let mut used: Vec<_> = repertoire.strategies_used.iter().copied().collect();
+                    used.sort_by_key(|&archetype| archetype as u8);
+                    used
+                },
+            },
+        )
+        .collect();
+    let food = food_query
+        .iter()
+        .map(
+            |(transform, age, lifetime, energy, corpse, poison, egg)| SavedFood {
+                position: transform.translation.to_array(),
+                age: age.0,
+                lifetime: lifetime.0,
+                energy: energy.0,
+                corpse: corpse.map(|corpse| corpse.0),
+                poison: poison.is_some(),
+                egg: egg.map(|egg| (egg.parent.0, egg.gene.clone())),
+            },
+        )
+        .collect();
+    let (food_timer, sensory_timer, age_timer, log_timer) = timers;
+    let saved = SavedWorld {
+        gene_version: GENE_VERSION,
+        tick: tick.0,
+        rng: SavedRng {
+            seed: rng.0.get_seed(),
+            stream: rng.0.get_stream(),
+            word_pos: rng.0.get_word_pos(),
+        },
+        timers: [
+            food_timer.0.elapsed_secs(),
+            sensory_timer.0.elapsed_secs(),
+            age_timer.0.elapsed_secs(),
+            log_timer.0.elapsed_secs(),
+        ],
+        sensory_ticks: sensory_timer.1,
+        food_rate: food_rate.0,
+        food_controller_integral: controller.integral,
+        rd_grid: config
+            .reaction_diffusion
+            .as_ref()
+            .map(|_| rd_grid.0.to_vec()),
+        soil: config.nutrient_cycling.as_ref().map(|_| soil.0.to_vec()),
+        food_patches: match config.food_distribution {
+            FoodDistribution::Uniform => None,
+            FoodDistribution::Patchy { .. } => Some(
+                patches
+                    .0
+                    .iter()
+                    .map(|patch| [patch.center.to_array(), patch.heading.to_array()])
+                    .collect(),
+            ),
+        },
+        conceptions: stats.conceptions,
+        births: stats.births,
+        deaths: stats.deaths,
+        trap_deaths: stats.trap_deaths,
+        poison_deaths: stats.poison_deaths,
+        food_eaten: stats.food_eaten,
+        kills: stats.kills,
+        corpses_eaten: stats.corpses_eaten,
+        poison_eaten: stats.poison_eaten,
+        eggs_eaten: stats.eggs_eaten,
+        genealogy: genealogy.clone(),
+        genomic_islands: islands.0.to_vec(),
+        conservation: (conservation.varied.to_vec(), conservation.ticks.to_vec()),
+        strategy_frequency: {
+            let mut frequency: Vec<_> = frequency.0.iter().map(|(&a, &f)| (a, f)).collect();
+            frequency.sort_by_key(|&(archetype, _)| archetype as u8);
+            frequency
+        },
+        survival_model: (survival_model.weights.to_vec(), survival_model.bias),
+        swarm_food_signal: shared.food_signal,
+        organisms,
+        food,
+        pheromone_field: field.cells.clone(),
+    };
+
+    let path = &config.saves.path;
+    let result = std::fs::File::create(path)
+        .map_err(|e| e.to_string())
+        .and_then(|file| {
+            serde_json::to_writer(std::io::BufWriter::new(file), &saved).map_err(|e| e.to_string())
+        });
+    match result {
+        Ok(()) => info!("saved tick {} to {}", tick.0, path.display()),
+        Err(e) => error!("could not save {}: {e}", path.display()),
+    }
+}
+
+/// Rebuilds the world from the [`SavedWorld`] given to the app, in place of
+/// the founders `startup` would have spawned.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub(crate) fn restore_world(
+    mut commands: Commands,
+    saved: Option<Res<SavedWorld>>,
+    config: Res<SimulationConfig>,
+    mut tick: ResMut<SimTick>,
+    mut rng: ResMut<SimRng>,
+    mut stats: ResMut<SimStats>,
+    mut timers: (
+        ResMut<FoodTimer>,
+        ResMut<SensoryTimer>,
+        ResMut<AgeTimer>,
+        ResMut<LogTimer>,
+    ),
+    mut food_rate: ResMut<FoodRate>,
+    mut controller: ResMut<FoodController>,
+    (mut rd_grid, mut soil, mut patches, mut field): (
+        ResMut<RDGrid>,
+        ResMut<SoilNutrient>,
+        ResMut<FoodPatches>,
+        ResMut<PheromoneField>,
+    ),
+    mut genealogy: ResMut<Genealogy>,
+    (mut islands, mut conservation, mut frequency, mut survival_model, mut shared): (
+        ResMut<GenomicIslands>,
+        ResMut<Conservation>,
+        ResMut<StrategyFrequency>,
+        ResMut<SurvivalModel>,
+        ResMut<SharedSensory>,
+    ),
+) {
+    let Some(saved) = saved else {
+        return;
+    };
+    tick.0 = saved.tick;
+    let mut restored = ChaCha8Rng::from_seed(saved.rng.seed);
+    restored.set_stream(saved.rng.stream);
+    restored.set_word_pos(saved.rng.word_pos);
+    rng.0 = restored;
+    for (timer, elapsed) in [
+        &mut timers.0 .0,
+        &mut timers.1 .0,
+        &mut timers.2 .0,
+        &mut timers.3 .0,
+    ]
+    .into_iter()
+    .zip(saved.timers)
+    {
+        timer.set_elapsed(Duration::from_secs_f32(elapsed));
+    }
+    timers.1 .1 = saved.sensory_ticks;
+    food_rate.0 = saved.food_rate;
+    controller.integral = saved.food_controller_integral;
+    if let Some(grid) = &saved.rd_grid {
+        if grid.len() == rd_grid.0.len() {
+            rd_grid.0.copy_from_slice(grid);
+        } else {
+            warn!("ignoring a saved reaction-diffusion grid of the wrong size");
+        }
+    }
+    if let Some(grid) = &saved.soil {
+        if grid.len() == soil.0.len() {
+            soil.0.copy_from_slice(grid);
+        } else {
+            warn!("ignoring a saved soil nutrient grid of the wrong size");
+        }
+    }
+    if saved.pheromone_field.len() == field.cells.len() {
+        field.cells.copy_from_slice(&saved.pheromone_field);
+    } else {
+        warn!("ignoring a saved pheromone field of the wrong size");
+    }
+    if let Some(saved_patches) = &saved.food_patches {
+        patches.0 = saved_patches
+            .iter()
+            .map(|&[center, heading]| FoodPatch {
+                center: Vec2::from_array(center),
+                heading: Vec2::from_array(heading),
+            })
+            .collect();
+    }
+    stats.conceptions = saved.conceptions;
+    stats.births = saved.births;
+    stats.deaths = saved.deaths;
+    stats.trap_deaths = saved.trap_deaths;
+    stats.poison_deaths = saved.poison_deaths;
+    stats.food_eaten = saved.food_eaten;
+    stats.kills = saved.kills;
+    stats.corpses_eaten = saved.corpses_eaten;
+    stats.poison_eaten = saved.poison_eaten;
+    stats.eggs_eaten = saved.eggs_eaten;
+    *genealogy = saved.genealogy.clone();
+    // the lengths were checked by `SavedWorld::load`
+    islands.0.copy_from_slice(&saved.genomic_islands);
+    conservation.varied.copy_from_slice(&saved.conservation.0);
+    conservation.ticks.copy_from_slice(&saved.conservation.1);
+    frequency.0 = saved.strategy_frequency.iter().copied().collect();
+    survival_model
+        .weights
+        .copy_from_slice(&saved.survival_model.0);
+    survival_model.bias = saved.survival_model.1;
+    shared.food_signal = saved.swarm_food_signal;
+
+    for organism in &saved.organisms {
+        let [left, front, right] = organism.food_history;
+        let [turn, speed] = organism.brain;
+        let radius = InteractionRadius(organism.radius);
+        let entity = commands
+            .spawn(OrganismBundle::new(
+                &config,
+                organism.gene.clone(),
+                Vec3::from_array(organism.position),
+                Vec2::from_array(organism.direction),
+                organism.energy,
+                organism.birth_tick,
+            ))
+            .insert((
+                Speed(organism.speed),
+                Age(organism.age),
+                Lifetime(organism.lifetime),
+                PartnerGenes(organism.partner_gene.clone()),
+                EnergyRecord(organism.energy_record),
+                TimeSinceFood(organism.time_since_food),
+                FoodDetectionHistory { left, front, right },
+                LastBrainState { turn, speed },
+                SurvivalProbability(organism.survival),
+                FoodStash(organism.stash),
+                FoodEaten(organism.food_eaten),
+                CarbonProcessed(organism.carbon_processed),
+                SurvivabilityScore(organism.survivability),
+                BehaviorRepertoire {
+                    strategies_used: organism.repertoire.iter().copied().collect(),
+                },
+            ))
+            .insert((
+                // the grid is built from the size before it is updated
+                Transform::from_translation(Vec3::from_array(organism.position))
+                    .with_scale(radius.scale()),
+                radius,
+                OrganismId(organism.id),
+                EvaluationOffset::from_id(OrganismId(organism.id)),
+                Generation(genealogy.generation(OrganismId(organism.id)) as u32),
+                RunState {
+                    ticks_remaining: organism.run.0,
+                    food_signal: organism.run.1,
+                },
+                Burrowed(organism.burrowed),
+                FastMemory {
+                    positions: organism
+                        .fast_memory
+                        .iter()
+                        .copied()
+                        .map(Vec3::from)
+                        .collect(),
+                    ..default()
+                },
+                SlowMemory {
+                    positions: organism
+                        .slow_memory
+                        .iter()
+                        .copied()
+                        .map(Vec3::from)
+                        .collect(),
+                    ..default()
+                },
+                ParentId(
+                    genealogy
+                        .records
+                        .get(organism.id as usize)
+                        .and_then(|record| record.parent),
+                ),
+                SensoryInputs(organism.inputs),
+                Poisoned(organism.poisoned),
+            ))
+            .id();
+        if let Some(ticks) = organism.pregnant {
+            commands.entity(entity).insert(Pregnant(ticks));
+        }
+        let (neighbors, pool) = organism.swarm;
+        if let Some(neighbors) = neighbors {
+            shared.neighbors.insert(entity, neighbors);
+        }
+        if let Some(pool) = pool {
+            shared.pools.insert(entity, pool);
+        }
+    }
+    for food in &saved.food {
+        let mut entity = commands.spawn((
+            Transform::from_translation(Vec3::from_array(food.position)).with_scale(FOOD_SIZE),
+            Food,
+            Age(food.age),
+            Lifetime(food.lifetime),
+            Energy(food.energy),
+            Collider,
+        ));
+        if let Some(initial) = food.corpse {
+            entity.insert(Corpse(initial));
+        }
+        if food.poison {
+            entity.insert(Poison);
+        }
+        if let Some((parent, gene)) = &food.egg {
+            entity.insert((
+                Transform::from_translation(Vec3::from_array(food.position)).with_scale(EGG_SIZE),
+                Egg {
+                    parent: OrganismId(*parent),
+                    generation: Generation(genealogy.generation(OrganismId(*parent)) as u32 + 1),
+                    gene: gene.clone(),
+                    mutations: Vec::new(),
+                },
+            ));
+        }
+    }
+    info!(
+        "restored tick {} with {} organisms",
+        saved.tick,
+        saved.organisms.len()
+    );
+    commands.remove_resource::<SavedWorld>();
+}
@@ -0,0 +1,263 @@
+//! Scenarios: a config to run headless along with assertions on how the run
+//! has to go, to catch changes that break the default ecology.
+
+use serde::Deserialize;
+
+use crate::config::SimulationConfig;
+use crate::stats::SimStats;
+
+/// A scenario file, a `[config]` table with the usual config fields and any
+/// number of `[[assertions]]`.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Scenario {
+    /// Fixed ticks to run for, the tick of the last assertion when not given.
+    pub ticks: Option<u64>,
+    pub config: SimulationConfig,
+    pub assertions: Vec<Assertion>,
+}
+
+/// Something that has to hold in a headless run of a scenario.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Assertion {
+    /// The number of organisms at `tick` is between `min` and `max`.
+    Population { tick: u64, min: usize, max: usize },
+    /// There is at least one organism on every tick before `tick`.
+    NoExtinctionBefore { tick: u64 },
+    /// The mean energy of the organisms at `tick` is at least `min`.
+    MeanEnergy { tick: u64, min: f32 },
+}
+
+impl Assertion {
+    /// Last tick the assertion looks at.
+    pub fn tick(&self) -> u64 {
+        match *self {
+            Assertion::Population { tick, .. }
+            | Assertion::NoExtinctionBefore { tick }
+            | Assertion::MeanEnergy { tick, .. } => tick,
+        }
+    }
+
+    /// Checks the assertion against the statistics of fixed tick `tick`.
+    /// `None` while it is still undecided, otherwise whether it held, with
+    /// a description of the failure when it didn't.
+    pub fn check(&self, tick: u64, stats: &SimStats) -> Option<Result<(), String>> {
+        match *self {
+            Assertion::Population { tick: at, min, max } if tick == at => {
+                Some(if (min..=max).contains(&stats.organisms) {
+                    Ok(())
+                } else {
+                    Err(format!(
+                        "population at tick {at} should be between {min} and {max}, was {}",
+                        stats.organisms
+                    ))
+                })
+            }
+            Assertion::NoExtinctionBefore { tick: before } if tick < before => {
+                if stats.organisms == 0 {
+                    Some(Err(format!(
+                        "population went extinct at tick {tick}, before tick {before}"
+                    )))
+                } else {
+                    None
+                }
+            }
+            Assertion::NoExtinctionBefore { .. } => Some(Ok(())),
+            Assertion::MeanEnergy { tick: at, min } if tick == at => {
+                Some(if stats.mean_energy >= min {
+                    Ok(())
+                } else {
+                    Err(format!(
+                        "mean energy at tick {at} should be at least {min}, was {}",
+                        stats.mean_energy
+                    ))
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Outcomes of a list of assertions over a run, checked tick by tick.
+pub struct AssertionResults<'a> {
+    assertions: &'a [Assertion],
+    results: Vec<Option<Result<(), String>>>,
+}
+
+impl<'a> AssertionResults<'a> {
+    pub fn new(assertions: &'a [Assertion]) -> Self {
+        Self {
+            assertions,
+            results: vec![None; assertions.len()],
+        }
+    }
+
+    /// Checks the assertions still undecided against the statistics of
+    /// fixed tick `tick`.
+    pub fn check(&mut self, tick: u64, stats: &SimStats) {
+        for (assertion, result) in self.assertions.iter().zip(&mut self.results) {
+            if result.is_none() {
+                *result = assertion.check(tick, stats);
+            }
+        }
+    }
+
+    /// Why every assertion that didn't hold failed, including the ones the
+    /// run ended too early to decide.
+    pub fn failures(&self) -> Vec<String> {
+        self.assertions
+            .iter()
+            .zip(&self.results)
+            .filter_map(|(assertion, result)| match result {
+                Some(Ok(())) => None,
+                Some(Err(e)) => Some(format!("assertion failed: {e}")),
+                None => Some(format!(
+                    "assertion not checked, the run ended first: {assertion:?}"
+                )),
+            })
+            .collect()
+    }
+}
+
+impl Scenario {
+    /// Reads and validates the scenario file at `path`.
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("could not read scenario file {path}: {e}"))?;
+        Self::parse(&text).map_err(|e| format!("invalid scenario file {path}: {e}"))
+    }
+
+    /// Parses and validates the text of a scenario file.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let scenario: Self = toml::from_str(text).map_err(|e| e.to_string())?;
+        scenario.config.validate()?;
+        for assertion in &scenario.assertions {
+            if let Assertion::Population { min, max, .. } = assertion {
+                if min > max {
+                    return Err(format!(
+                        "population assertion has min {min} greater than max {max}"
+                    ));
+                }
+            }
+        }
+        if let Some(ticks) = scenario.ticks {
+            if let Some(assertion) = scenario.assertions.iter().find(|a| a.tick() > ticks) {
+                return Err(format!(
+                    "assertion {assertion:?} is after the last tick {ticks}"
+                ));
+            }
+        }
+        Ok(scenario)
+    }
+
+    /// Number of fixed ticks to run the scenario for.
+    pub fn ticks(&self) -> Option<u64> {
+        self.ticks
+            .or_else(|| self.assertions.iter().map(Assertion::tick).max())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(organisms: usize, mean_energy: f32) -> SimStats {
+        let mut stats = SimStats::default();
+        stats.organisms = organisms;
+        stats.mean_energy = mean_energy;
+        stats
+    }
+
+    #[test]
+    fn population_is_checked_on_its_tick_only() {
+        let assertion = Assertion::Population {
+            tick: 10,
+            min: 5,
+            max: 20,
+        };
+        assert_eq!(assertion.check(9, &stats(0, 0.0)), None);
+        assert_eq!(assertion.check(10, &stats(5, 0.0)), Some(Ok(())));
+        assert_eq!(assertion.check(10, &stats(20, 0.0)), Some(Ok(())));
+        assert!(matches!(assertion.check(10, &stats(21, 0.0)), Some(Err(_))));
+        assert!(matches!(assertion.check(10, &stats(4, 0.0)), Some(Err(_))));
+    }
+
+    #[test]
+    fn extinction_fails_only_before_the_tick() {
+        let assertion = Assertion::NoExtinctionBefore { tick: 10 };
+        assert_eq!(assertion.check(5, &stats(3, 0.0)), None);
+        assert!(matches!(assertion.check(9, &stats(0, 0.0)), Some(Err(_))));
+        assert_eq!(assertion.check(10, &stats(0, 0.0)), Some(Ok(())));
+    }
+
+    #[test]
+    fn mean_energy_has_to_reach_the_minimum() {
+        let assertion = Assertion::MeanEnergy { tick: 3, min: 0.5 };
+        assert_eq!(assertion.check(2, &stats(1, 0.0)), None);
+        assert_eq!(assertion.check(3, &stats(1, 0.5)), Some(Ok(())));
+        let failure = assertion.check(3, &stats(1, 0.25));
+        assert_eq!(
+            failure,
+            Some(Err(
+                "mean energy at tick 3 should be at least 0.5, was 0.25".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn results_keep_the_first_outcome_and_report_undecided_ones() {
+        let assertions = [
+            Assertion::NoExtinctionBefore { tick: 100 },
+            Assertion::Population {
+                tick: 2,
+                min: 1,
+                max: 10,
+            },
+            Assertion::MeanEnergy { tick: 50, min: 0.1 },
+        ];
+        let mut results = AssertionResults::new(&assertions);
+        results.check(1, &stats(3, 1.0));
+        results.check(2, &stats(3, 1.0));
+        results.check(3, &stats(0, 0.0));
+        // a population coming back doesn't undo the extinction
+        results.check(4, &stats(3, 1.0));
+        assert_eq!(
+            results.failures(),
+            [
+                "assertion failed: population went extinct at tick 3, before tick 100".to_string(),
+                "assertion not checked, the run ended first: MeanEnergy { tick: 50, min: 0.1 }"
+                    .to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn all_assertions_holding_means_no_failures() {
+        let assertions = [Assertion::Population {
+            tick: 1,
+            min: 1,
+            max: 1,
+        }];
+        let mut results = AssertionResults::new(&assertions);
+        results.check(1, &stats(1, 0.0));
+        assert!(results.failures().is_empty());
+    }
+
+    #[test]
+    fn scenarios_are_validated() {
+        let scenario =
+            Scenario::parse("[[assertions]]\nkind = \"population\"\ntick = 30\nmin = 1\nmax = 2\n")
+                .unwrap();
+        assert_eq!(scenario.ticks(), Some(30));
+        assert!(Scenario::parse(
+            "[[assertions]]\nkind = \"population\"\ntick = 30\nmin = 3\nmax = 2\n"
+        )
+        .is_err());
+        assert!(Scenario::parse(
+            "ticks = 10\n[[assertions]]\nkind = \"no_extinction_before\"\ntick = 30\n"
+        )
+        .is_err());
+        assert!(Scenario::parse("[config]\nmouth_arc = 0.0\n").is_err());
+    }
+}
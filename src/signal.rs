@@ -0,0 +1,72 @@
+//! Visual signals organisms tell each other apart by. Touching organisms of
+//! about the same color are conspecifics and compete less for room than
+//! ones of diverging colors, which puts selection on appearance itself.
+
+use bevy::prelude::*;
+
+use crate::config::SimulationConfig;
+use crate::organism::{InteractionRadius, Organism};
+use crate::spatial::SpatialGrid;
+use crate::Energy;
+
+/// Largest [`color_distance`] at which two organisms are conspecifics.
+pub const COLOR_DISTANCE_THRESHOLD: f32 = 0.1;
+
+/// The color an organism shows to others, that of its gene.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct VisualSignal(pub Color);
+
+impl VisualSignal {
+    pub fn is_conspecific(&self, other: &VisualSignal) -> bool {
+        color_distance(self.0, other.0) <= COLOR_DISTANCE_THRESHOLD
+    }
+}
+
+/// Mean difference of the red, green and blue channels, from 0 for the same
+/// color to 1 for opposite ones.
+pub fn color_distance(a: Color, b: Color) -> f32 {
+    ((a.r() - b.r()).abs() + (a.g() - b.g()).abs() + (a.b() - b.b()).abs()) / 3.0
+}
+
+/// Drains the energy of touching organisms, by `competition_cost` for every
+/// heterospecific one touched and by `conspecific_competition` times that
+/// for every conspecific.
+pub(crate) fn compete(
+    config: Res<SimulationConfig>,
+    grid: Res<SpatialGrid>,
+    signal_query: Query<(Entity, &Transform, &InteractionRadius, &VisualSignal), With<Organism>>,
+    mut energy_query: Query<&mut Energy, With<Organism>>,
+) {
+    if config.competition_cost <= 0.0 {
+        return;
+    }
+    // drains are summed first since every pair is seen from both sides
+    let mut drains = Vec::new();
+    for (organism, transform, radius, signal) in &signal_query {
+        let position = transform.translation.truncate();
+        let mut drain = 0.0;
+        let nearby = grid.query_circle(position, radius.0);
+        for (other, other_transform, other_radius, other_signal) in signal_query.iter_many(&nearby)
+        {
+            if other == organism
+                || position.distance(other_transform.translation.truncate())
+                    >= radius.0 + other_radius.0
+            {
+                continue;
+            }
+            drain += if signal.is_conspecific(other_signal) {
+                config.competition_cost * config.conspecific_competition
+            } else {
+                config.competition_cost
+            };
+        }
+        if drain > 0.0 {
+            drains.push((organism, drain));
+        }
+    }
+    for (organism, drain) in drains {
+        if let Ok(mut energy) = energy_query.get_mut(organism) {
+            energy.0 -= drain;
+        }
+    }
+}
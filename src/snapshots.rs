@@ -0,0 +1,285 @@
+//! Genome snapshots of the whole population written to
+//! genome_snapshots.bin, and a 2D PCA projection of them for plotting
+//! evolutionary trajectories.
+//!
+//! The file starts with the magic `GSNP`, the gene version and the gene
+//! length as little endian `u32`s. Each snapshot follows as the tick (`u64`),
+//! the number of organisms (`u32`) and, for every organism, its id (`u64`)
+//! and its gene (`f32`s).
+
+use std::io::{BufReader, BufWriter, Read, Write};
+
+use bevy::prelude::*;
+
+use crate::config::SimulationConfig;
+use crate::genealogy::OrganismId;
+use crate::genes::{GeneInfo, GENE_LENGTH, GENE_VERSION};
+use crate::organism::Organism;
+use crate::{Dead, SimTick};
+
+pub const SNAPSHOT_FILE: &str = "genome_snapshots.bin";
+const MAGIC: &[u8; 4] = b"GSNP";
+/// Power iteration steps used to find each principal component.
+const POWER_ITERATIONS: usize = 100;
+
+/// The genes of the population at one tick.
+pub struct Snapshot {
+    pub tick: u64,
+    /// Id and gene of every organism.
+    pub organisms: Vec<(u64, Vec<f32>)>,
+}
+
+#[derive(Default)]
+pub(crate) struct SnapshotWriter {
+    file: Option<BufWriter<std::fs::File>>,
+    written: usize,
+}
+
+#[allow(clippy::type_complexity)]
+pub(crate) fn write_genome_snapshot(
+    config: Res<SimulationConfig>,
+    tick: Res<SimTick>,
+    mut writer: Local<SnapshotWriter>,
+    query: Query<(&OrganismId, &GeneInfo), (With<Organism>, Without<Dead>)>,
+) {
+    let Some(interval) = config.genome_snapshots.interval else {
+        return;
+    };
+    if !tick.0.is_multiple_of(interval) || writer.written >= config.genome_snapshots.max_snapshots {
+        return;
+    }
+    let writer = &mut *writer;
+    let file = writer.file.get_or_insert_with(|| {
+        let file = std::fs::File::create(SNAPSHOT_FILE).unwrap();
+        let mut file = BufWriter::new(file);
+        file.write_all(MAGIC).unwrap();
+        file.write_all(&GENE_VERSION.to_le_bytes()).unwrap();
+        file.write_all(&(GENE_LENGTH as u32).to_le_bytes()).unwrap();
+        file
+    });
+    file.write_all(&tick.0.to_le_bytes()).unwrap();
+    file.write_all(&(query.iter().count() as u32).to_le_bytes())
+        .unwrap();
+    for (id, gene) in &query {
+        file.write_all(&id.0.to_le_bytes()).unwrap();
+        for g in gene.0 {
+            file.write_all(&g.to_le_bytes()).unwrap();
+        }
+    }
+    writer.written += 1;
+    if writer.written == config.genome_snapshots.max_snapshots {
+        file.flush().unwrap();
+        info!("wrote the last of {} genome snapshots", writer.written);
+    }
+}
+
+fn read_u32(reader: &mut impl Read) -> std::io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(reader: &mut impl Read) -> std::io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+/// Reads every snapshot in the file at `path`.
+pub fn read_snapshots(path: &str) -> Result<Vec<Snapshot>, String> {
+    let file = std::fs::File::open(path).map_err(|e| format!("could not open {path}: {e}"))?;
+    let mut reader = BufReader::new(file);
+    let invalid = |e: std::io::Error| format!("invalid snapshot file {path}: {e}");
+
+    let mut magic = [0; 4];
+    reader.read_exact(&mut magic).map_err(invalid)?;
+    if &magic != MAGIC {
+        return Err(format!("{path} is not a genome snapshot file"));
+    }
+    // the projection doesn't care what the loci mean, only how many there are
+    let _version = read_u32(&mut reader).map_err(invalid)?;
+    let gene_length = read_u32(&mut reader).map_err(invalid)? as usize;
+
+    let mut snapshots = Vec::new();
+    loop {
+        let tick = match read_u64(&mut reader) {
+            Ok(tick) => tick,
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(invalid(e)),
+        };
+        let count = read_u32(&mut reader).map_err(invalid)?;
+        let mut organisms = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let id = read_u64(&mut reader).map_err(invalid)?;
+            let mut gene = Vec::with_capacity(gene_length);
+            for _ in 0..gene_length {
+                let g = read_u32(&mut reader).map_err(invalid)?;
+                gene.push(f32::from_bits(g));
+            }
+            organisms.push((id, gene));
+        }
+        snapshots.push(Snapshot { tick, organisms });
+    }
+    Ok(snapshots)
+}
+
+/// Projects `points` onto their first two principal components, found by
+/// power iteration on the covariance matrix.
+pub fn pca_2d(points: &[Vec<f32>]) -> Vec<[f32; 2]> {
+    PcaSolver::default().project(points, POWER_ITERATIONS)
+}
+
+/// The first two principal components found by the last projection, so that
+/// projecting a population that changed a little only takes a few power
+/// iterations and the axes keep their direction from one projection to the
+/// next.
+#[derive(Default)]
+pub struct PcaSolver {
+    components: Vec<Vec<f64>>,
+}
+
+impl PcaSolver {
+    /// Projects `points` onto their first two principal components, running
+    /// `iterations` steps of power iteration for each starting from the
+    /// previous components.
+    pub fn project(&mut self, points: &[Vec<f32>], iterations: usize) -> Vec<[f32; 2]> {
+        let Some(dim) = points.first().map(Vec::len) else {
+            return Vec::new();
+        };
+        let n = points.len() as f64;
+        let mut mean = vec![0.0; dim];
+        for point in points {
+            for (m, x) in mean.iter_mut().zip(point) {
+                *m += *x as f64 / n;
+            }
+        }
+        let centered: Vec<Vec<f64>> = points
+            .iter()
+            .map(|p| p.iter().zip(&mean).map(|(x, m)| *x as f64 - m).collect())
+            .collect();
+        let mut covariance = vec![vec![0.0; dim]; dim];
+        for point in &centered {
+            for i in 0..dim {
+                for j in 0..dim {
+                    covariance[i][j] += point[i] * point[j] / n;
+                }
+            }
+        }
+
+        let lost = |c: &Vec<f64>| c.len() != dim || c.iter().all(|x| *x == 0.0);
+        if self.components.len() != 2 || self.components.iter().any(lost) {
+            // start off the axes so no eigenvector is orthogonal to the start
+            self.components = vec![(0..dim).map(|i| 1.0 + i as f64 / dim as f64).collect(); 2];
+        }
+        for component in &mut self.components {
+            *component = dominant_eigenvector(&covariance, component, iterations);
+            let eigenvalue = quadratic_form(&covariance, component);
+            // deflate so the next power iteration finds the next component
+            for i in 0..dim {
+                for j in 0..dim {
+                    covariance[i][j] -= eigenvalue * component[i] * component[j];
+                }
+            }
+        }
+        centered
+            .iter()
+            .map(|p| [0, 1].map(|c| dot(p, &self.components[c]) as f32))
+            .collect()
+    }
+}
+
+fn dot(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+fn quadratic_form(matrix: &[Vec<f64>], v: &[f64]) -> f64 {
+    matrix.iter().zip(v).map(|(row, x)| x * dot(row, v)).sum()
+}
+
+fn dominant_eigenvector(matrix: &[Vec<f64>], start: &[f64], iterations: usize) -> Vec<f64> {
+    let dim = matrix.len();
+    let mut v = start.to_vec();
+    for _ in 0..iterations {
+        let next: Vec<f64> = matrix.iter().map(|row| dot(row, &v)).collect();
+        let norm = dot(&next, &next).sqrt();
+        if norm == 0.0 {
+            return vec![0.0; dim];
+        }
+        v = next.into_iter().map(|x| x / norm).collect();
+    }
+    v
+}
+
+/// Reads the snapshots at `snapshots_path`, projects every gene in them onto
+/// the first two principal components of all of them and writes the
+/// projection to `embedding_path` as CSV. Returns the number of genes
+/// projected.
+pub fn export_embedding(snapshots_path: &str, embedding_path: &str) -> Result<usize, String> {
+    let snapshots = read_snapshots(snapshots_path)?;
+    let genes: Vec<Vec<f32>> = snapshots
+        .iter()
+        .flat_map(|s| s.organisms.iter().map(|(_, gene)| gene.clone()))
+        .collect();
+    let projection = pca_2d(&genes);
+
+    let file = std::fs::File::create(embedding_path)
+        .map_err(|e| format!("could not create {embedding_path}: {e}"))?;
+    let mut file = BufWriter::new(file);
+    let rows = snapshots
+        .iter()
+        .flat_map(|s| s.organisms.iter().map(move |(id, _)| (s.tick, id)));
+    let write = || -> std::io::Result<()> {
+        writeln!(file, "tick,id,pc1,pc2")?;
+        for ((tick, id), [pc1, pc2]) in rows.zip(&projection) {
+            writeln!(file, "{tick},{id},{pc1},{pc2}")?;
+        }
+        file.flush()
+    };
+    write().map_err(|e| format!("could not write {embedding_path}: {e}"))?;
+    Ok(projection.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pca_finds_the_axes_of_a_known_example() {
+        // a cross along the diagonals of the xy plane, three times longer
+        // along x = y, shifted off the origin
+        let u = [1.0, 1.0, 0.0].map(|x: f32| x / 2.0f32.sqrt());
+        let v = [1.0, -1.0, 0.0].map(|x: f32| x / 2.0f32.sqrt());
+        let offset = [5.0, -2.0, 1.0];
+        let point = |a: f32, b: f32| -> Vec<f32> {
+            (0..3).map(|i| offset[i] + a * u[i] + b * v[i]).collect()
+        };
+        let points = [
+            point(3.0, 0.0),
+            point(-3.0, 0.0),
+            point(0.0, 1.0),
+            point(0.0, -1.0),
+        ];
+        let projected = pca_2d(&points);
+        let expected = [[3.0, 0.0], [-3.0, 0.0], [0.0, 1.0], [0.0, -1.0]];
+        // the components can come out either way round
+        let signs = [0, 1].map(|c| projected[2 * c][c].signum());
+        for (p, e) in projected.iter().zip(expected) {
+            for c in 0..2 {
+                assert!((p[c] - signs[c] * e[c]).abs() < 1e-4, "{projected:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn solver_keeps_the_direction_of_its_axes() {
+        let points: Vec<Vec<f32>> = (0..20)
+            .map(|i| vec![i as f32, 0.5 * i as f32, (i % 3) as f32])
+            .collect();
+        let mut solver = PcaSolver::default();
+        let first = solver.project(&points, POWER_ITERATIONS);
+        let again = solver.project(&points, 5);
+        for (a, b) in first.iter().zip(&again) {
+            assert!((a[0] - b[0]).abs() < 1e-3);
+        }
+    }
+}
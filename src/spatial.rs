@@ -0,0 +1,174 @@
+//! A uniform grid over the arena, so that systems looking for what is near
+//! an organism only go through the entities in the cells around it instead
+//! of every entity.
+
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+
+use crate::config::SimulationConfig;
+use crate::food::Food;
+use crate::organism::Organism;
+use crate::Collider;
+
+/// The entities that can be looked up in a [`SpatialGrid`].
+type Indexed = Or<(With<Organism>, With<Food>, With<Collider>)>;
+
+/// Organisms, food and boundaries by the grid cells their
+/// bounding boxes overlap, rebuilt every fixed tick once organisms moved.
+/// Lookups give the entities in the order they were inserted in, which a
+/// loaded save reproduces where the [`Entity`] ids themselves differ.
+#[derive(Resource)]
+pub struct SpatialGrid {
+    cell_size: f32,
+    cells: HashMap<IVec2, Vec<(Entity, Rect)>>,
+    /// Insertion rank of every entity.
+    ranks: HashMap<Entity, usize>,
+}
+
+impl SpatialGrid {
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size,
+            cells: HashMap::new(),
+            ranks: HashMap::new(),
+        }
+    }
+
+    fn cell(&self, position: Vec2) -> IVec2 {
+        (position / self.cell_size).floor().as_ivec2()
+    }
+
+    /// Removes every entity, keeping the cells allocated for the next
+    /// rebuild.
+    pub fn clear(&mut self) {
+        for cell in self.cells.values_mut() {
+            cell.clear();
+        }
+        self.ranks.clear();
+    }
+
+    /// Adds `entity` with the bounding box `bounds`.
+    pub fn insert(&mut self, entity: Entity, bounds: Rect) {
+        let rank = self.ranks.len();
+        self.ranks.entry(entity).or_insert(rank);
+        let (min, max) = (self.cell(bounds.min), self.cell(bounds.max));
+        for x in min.x..=max.x {
+            for y in min.y..=max.y {
+                self.cells
+                    .entry(IVec2::new(x, y))
+                    .or_default()
+                    .push((entity, bounds));
+            }
+        }
+    }
+
+    /// Every entity whose bounding box satisfies `overlaps` in the cells from
+    /// `min` to `max`, sorted so that the order doesn't depend on the cells.
+    fn query(&self, min: Vec2, max: Vec2, overlaps: impl Fn(Rect) -> bool) -> Vec<Entity> {
+        let (min, max) = (self.cell(min), self.cell(max));
+        let mut entities = Vec::new();
+        for x in min.x..=max.x {
+            for y in min.y..=max.y {
+                let Some(cell) = self.cells.get(&IVec2::new(x, y)) else {
+                    continue;
+                };
+                entities.extend(
+                    cell.iter()
+                        .filter(|(_, bounds)| overlaps(*bounds))
+                        .map(|(entity, _)| *entity),
+                );
+            }
+        }
+        // entities spanning several cells are found once in each
+        self.sort(&mut entities);
+        entities
+    }
+
+    /// Puts `entities` in the order they were inserted in and drops the
+    /// repeated ones.
+    pub fn sort(&self, entities: &mut Vec<Entity>) {
+        entities.sort_unstable_by_key(|entity| self.ranks[entity]);
+        entities.dedup();
+    }
+
+    /// Every entity whose bounding box overlaps the circle of `radius`
+    /// around `center`.
+    pub fn query_circle(&self, center: Vec2, radius: f32) -> Vec<Entity> {
+        let extent = Vec2::splat(radius);
+        self.query(center - extent, center + extent, |bounds| {
+            center
+                .clamp(bounds.min, bounds.max)
+                .distance_squared(center)
+                <= radius * radius
+        })
+    }
+
+    /// Every entity whose bounding box overlaps the box from `min` to `max`.
+    pub fn query_aabb(&self, min: Vec2, max: Vec2) -> Vec<Entity> {
+        self.query(min, max, |bounds| {
+            bounds.min.cmple(max).all() && bounds.max.cmpge(min).all()
+        })
+    }
+}
+
+impl FromWorld for SpatialGrid {
+    fn from_world(world: &mut World) -> Self {
+        let config = world.resource::<SimulationConfig>();
+        Self::new(config.organism_vision / 2.0)
+    }
+}
+
+pub(crate) fn update_spatial_grid(
+    mut grid: ResMut<SpatialGrid>,
+    query: Query<(Entity, &Transform), Indexed>,
+) {
+    grid.clear();
+    for (entity, transform) in &query {
+        let center = transform.translation.truncate();
+        let extent = transform.scale.truncate().abs() / 2.0;
+        grid.insert(entity, Rect::from_corners(center - extent, center + extent));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+
+    fn overlaps_circle(bounds: Rect, center: Vec2, radius: f32) -> bool {
+        center.clamp(bounds.min, bounds.max).distance(center) <= radius
+    }
+
+    #[test]
+    fn query_circle_matches_brute_force() {
+        let mut rng = ChaCha8Rng::seed_from_u64(3);
+        for _ in 0..20 {
+            let mut grid = SpatialGrid::new(rng.gen_range(5.0..60.0));
+            let boxes: Vec<(Entity, Rect)> = (0..200)
+                .map(|index| {
+                    let center =
+                        Vec2::new(rng.gen_range(-600.0..600.0), rng.gen_range(-300.0..300.0));
+                    let extent = Vec2::new(rng.gen_range(0.0..40.0), rng.gen_range(0.0..40.0));
+                    let bounds = Rect::from_corners(center - extent, center + extent);
+                    (Entity::from_raw(index), bounds)
+                })
+                .collect();
+            for &(entity, bounds) in &boxes {
+                grid.insert(entity, bounds);
+            }
+            for _ in 0..50 {
+                let center = Vec2::new(rng.gen_range(-700.0..700.0), rng.gen_range(-400.0..400.0));
+                let radius = rng.gen_range(0.0..150.0);
+                let expected: Vec<Entity> = boxes
+                    .iter()
+                    .filter(|(_, bounds)| overlaps_circle(*bounds, center, radius))
+                    .map(|(entity, _)| *entity)
+                    .collect();
+                assert_eq!(grid.query_circle(center, radius), expected);
+            }
+        }
+    }
+}
@@ -0,0 +1,318 @@
+//! Population statistics, updated every fixed tick.
+
+use std::collections::VecDeque;
+use std::io::Write;
+
+use bevy::prelude::*;
+
+use crate::food::Food;
+use crate::genealogy::{Genealogy, OrganismId};
+use crate::genes::{Traits, GENE_LENGTH, LIFETIME_RANGE};
+use crate::organism::{DeathEvent, FoodDetectionHistory, Organism, Pregnant, TimeSinceFood};
+use crate::pheromone::PheromoneField;
+use crate::plugin::LogTimer;
+use crate::{Age, DeathCause, Energy, SimTick, TIME_STEP};
+
+/// Summary of the simulation as of the last fixed tick.
+#[derive(Resource, Default, Clone, Debug, PartialEq)]
+pub struct SimStats {
+    pub organisms: usize,
+    pub food: usize,
+    /// Pheromone intensity summed over the whole field.
+    pub pheromones: f32,
+    pub mean_energy: f32,
+    pub mean_age: f32,
+    pub max_age: usize,
+    /// Organisms pregnant now.
+    pub pregnant: usize,
+    pub mean_time_since_food: f32,
+    /// Pregnancies since the start of the run.
+    pub conceptions: u64,
+    /// Organisms born since the start of the run.
+    pub births: u64,
+    /// Organisms died since the start of the run.
+    pub deaths: u64,
+    /// Organisms killed by a death trap since the start of the run, also
+    /// counted in `deaths`.
+    pub trap_deaths: u64,
+    /// Organisms killed by poisonous food since the start of the run, also
+    /// counted in `deaths`.
+    pub poison_deaths: u64,
+    /// Food items eaten since the start of the run.
+    pub food_eaten: u64,
+    /// Organisms eaten by carnivores since the start of the run, also
+    /// counted in `deaths`.
+    pub kills: u64,
+    /// Corpse food items eaten since the start of the run, also counted in
+    /// `food_eaten`.
+    pub corpses_eaten: u64,
+    /// Poisonous food items eaten since the start of the run, also counted
+    /// in `food_eaten`.
+    pub poison_eaten: u64,
+    /// Eggs eaten since the start of the run, also counted in `food_eaten`.
+    pub eggs_eaten: u64,
+    /// Founders with descendants still alive.
+    pub lineages: usize,
+    /// Pregnancies over the last second of simulated time.
+    pub recent_conceptions: u64,
+    /// Births over the last second of simulated time.
+    pub recent_births: u64,
+    /// Deaths over the last second of simulated time.
+    pub recent_deaths: u64,
+    /// Conception, birth and death totals of the ticks in the last second.
+    history: VecDeque<(u64, u64, u64)>,
+}
+
+/// Organism energy summed by age since the last log tick, to see how energy
+/// typically changes over a life.
+#[derive(Resource, Default)]
+pub struct GrowthCurve {
+    /// Sum of energies and number of samples for every age.
+    pub bins: Vec<(f32, usize)>,
+}
+
+impl SimStats {
+    /// Number of ticks kept to compute the recent births and deaths.
+    pub fn history_len(&self) -> usize {
+        self.history.len()
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub(crate) fn update_sim_stats(
+    mut stats: ResMut<SimStats>,
+    mut death_events: EventReader<DeathEvent>,
+    genealogy: Res<Genealogy>,
+    organism_query: Query<
+        (
+            &Energy,
+            &Age,
+            &TimeSinceFood,
+            &OrganismId,
+            Option<&Pregnant>,
+        ),
+        With<Organism>,
+    >,
+    food_query: Query<(), With<Food>>,
+    field: Res<PheromoneField>,
+) {
+    for event in death_events.iter() {
+        stats.deaths += 1;
+        match event.cause {
+            DeathCause::Trap => stats.trap_deaths += 1,
+            DeathCause::Poison => stats.poison_deaths += 1,
+            _ => {}
+        }
+    }
+
+    let mut energy = 0.0;
+    let mut age = 0;
+    let mut max_age = 0;
+    let mut pregnant = 0;
+    let mut time_since_food = 0;
+    let mut organisms = 0;
+    for (e, a, t, _, p) in &organism_query {
+        organisms += 1;
+        energy += e.0;
+        age += a.0;
+        max_age = max_age.max(a.0);
+        pregnant += p.is_some() as usize;
+        time_since_food += t.0;
+    }
+    let count = organisms.max(1) as f32;
+    stats.organisms = organisms;
+    stats.food = food_query.iter().count();
+    stats.pheromones = field.total();
+    stats.mean_energy = energy / count;
+    stats.mean_age = age as f32 / count;
+    stats.max_age = max_age;
+    stats.pregnant = pregnant;
+    stats.mean_time_since_food = time_since_food as f32 / count;
+    stats.lineages = genealogy.lineages(organism_query.iter().map(|(.., id, _)| id));
+
+    let totals = (stats.conceptions, stats.births, stats.deaths);
+    stats.history.push_back(totals);
+    if stats.history.len() > (1.0 / TIME_STEP).round() as usize {
+        stats.history.pop_front();
+    }
+    let (conceptions, births, deaths) = stats.history.front().copied().unwrap_or(totals);
+    stats.recent_conceptions = totals.0 - conceptions;
+    stats.recent_births = totals.1 - births;
+    stats.recent_deaths = totals.2 - deaths;
+}
+
+pub(crate) fn update_growth_curve(
+    tick: Res<SimTick>,
+    timer: Res<LogTimer>,
+    mut curve: ResMut<GrowthCurve>,
+    mut log: Local<Option<std::io::BufWriter<std::fs::File>>>,
+    query: Query<(&Energy, &Age), With<Organism>>,
+) {
+    let bins = LIFETIME_RANGE.1 as usize + 2;
+    if curve.bins.len() != bins {
+        curve.bins = vec![(0.0, 0); bins];
+    }
+    for (energy, age) in &query {
+        let bin = &mut curve.bins[age.0.min(bins - 1)];
+        bin.0 += energy.0;
+        bin.1 += 1;
+    }
+    if !timer.0.just_finished() {
+        return;
+    }
+
+    let log = log.get_or_insert_with(|| {
+        let file = std::fs::File::create("growth_curve.csv").unwrap();
+        let mut file = std::io::BufWriter::new(file);
+        file.write_all(b"tick,age,mean_energy,samples\n").unwrap();
+        file
+    });
+    for (age, (energy, samples)) in curve.bins.iter_mut().enumerate() {
+        if *samples > 0 {
+            writeln!(
+                log,
+                "{},{},{},{}",
+                tick.0,
+                age,
+                *energy / *samples as f32,
+                samples
+            )
+            .unwrap();
+        }
+        *energy = 0.0;
+        *samples = 0;
+    }
+}
+
+/// Logs the population with how many organisms are pregnant and how many
+/// conceived since the last row to population.csv.
+pub(crate) fn log_population(
+    tick: Res<SimTick>,
+    timer: Res<LogTimer>,
+    stats: Res<SimStats>,
+    mut last_conceptions: Local<Option<u64>>,
+    mut log: Local<Option<std::io::BufWriter<std::fs::File>>>,
+) {
+    // a loaded run starts from the conceptions in the save
+    let last_conceptions = last_conceptions.get_or_insert(stats.conceptions);
+    if !timer.0.just_finished() {
+        return;
+    }
+    let log = log.get_or_insert_with(|| {
+        let file = std::fs::File::create("population.csv").unwrap();
+        let mut file = std::io::BufWriter::new(file);
+        file.write_all(b"tick,organisms,pregnant,conceptions\n")
+            .unwrap();
+        file
+    });
+    writeln!(
+        log,
+        "{},{},{},{}",
+        tick.0,
+        stats.organisms,
+        stats.pregnant,
+        stats.conceptions - *last_conceptions
+    )
+    .unwrap();
+    *last_conceptions = stats.conceptions;
+}
+
+/// Logs how much the population remembers of where it saw food, the summed
+/// weight of the sightings of every organism, along with how fast it forgets.
+pub(crate) fn log_memory(
+    tick: Res<SimTick>,
+    timer: Res<LogTimer>,
+    mut log: Local<Option<std::io::BufWriter<std::fs::File>>>,
+    query: Query<(&FoodDetectionHistory, &Traits), With<Organism>>,
+) {
+    if !timer.0.just_finished() {
+        return;
+    }
+    let mut capacity = 0.0;
+    let mut decay = 0.0;
+    for (history, traits) in &query {
+        capacity += history
+            .weights(tick.0, traits.memory_decay)
+            .iter()
+            .sum::<f32>();
+        decay += traits.memory_decay;
+    }
+    let count = query.iter().count().max(1) as f32;
+
+    let log = log.get_or_insert_with(|| {
+        let file = std::fs::File::create("memory.csv").unwrap();
+        let mut file = std::io::BufWriter::new(file);
+        file.write_all(b"tick,mean_memory,mean_memory_decay\n")
+            .unwrap();
+        file
+    });
+    writeln!(log, "{},{},{}", tick.0, capacity / count, decay / count).unwrap();
+}
+
+/// Logs the mean gain of the population on its pheromone inputs.
+pub(crate) fn log_pheromone_sensitivity(
+    tick: Res<SimTick>,
+    timer: Res<LogTimer>,
+    mut log: Local<Option<std::io::BufWriter<std::fs::File>>>,
+    query: Query<&Traits, With<Organism>>,
+) {
+    if !timer.0.just_finished() {
+        return;
+    }
+    let sensitivity: f32 = query
+        .iter()
+        .map(|traits| traits.pheromone_sensitivity)
+        .sum();
+    let count = query.iter().count().max(1) as f32;
+
+    let log = log.get_or_insert_with(|| {
+        let file = std::fs::File::create("pheromone_sensitivity.csv").unwrap();
+        let mut file = std::io::BufWriter::new(file);
+        file.write_all(b"tick,mean_pheromone_sensitivity\n")
+            .unwrap();
+        file
+    });
+    writeln!(log, "{},{}", tick.0, sensitivity / count).unwrap();
+}
+
+/// Logs every organism that died with how well it did and its gene.
+pub(crate) fn log_deaths(
+    tick: Res<SimTick>,
+    mut log: Local<Option<std::io::BufWriter<std::fs::File>>>,
+    mut death_events: EventReader<DeathEvent>,
+) {
+    for event in death_events.iter() {
+        let log = log.get_or_insert_with(|| {
+            let file = std::fs::File::create("deaths.csv").unwrap();
+            let mut file = std::io::BufWriter::new(file);
+            write!(
+                file,
+                "tick,id,cause,age,mean_energy,food_eaten,survivability,repertoire,ecosystem_service"
+            )
+            .unwrap();
+            for locus in 0..GENE_LENGTH {
+                write!(file, ",gene_{locus}").unwrap();
+            }
+            writeln!(file).unwrap();
+            file
+        });
+        write!(
+            log,
+            "{},{},{:?},{},{},{},{},{},{}",
+            tick.0,
+            event.id.0,
+            event.cause,
+            event.age,
+            event.mean_energy,
+            event.food_eaten,
+            event.survivability,
+            event.repertoire,
+            event.ecosystem_service()
+        )
+        .unwrap();
+        for g in event.gene.0 {
+            write!(log, ",{g}").unwrap();
+        }
+        writeln!(log).unwrap();
+    }
+}
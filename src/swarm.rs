@@ -0,0 +1,142 @@
+//! Swarm sensing: organisms close together pool what they see of the food
+//! in front of them, and each takes in as much of the pool as its swarm
+//! sensing gene lets it. Logs whether swarming organisms cluster tighter as
+//! the gene evolves.
+
+use std::collections::HashMap;
+use std::io::Write;
+
+use bevy::prelude::*;
+
+use crate::config::SimulationConfig;
+use crate::genes::Traits;
+use crate::organism::{Organism, SensoryInputs, SensoryTimer};
+use crate::plugin::LogTimer;
+use crate::spatial::SpatialGrid;
+use crate::topology::{self, displacement};
+use crate::SimTick;
+
+/// Index of the frontal food signal in [`SensoryInputs`].
+const FRONT_FOOD_INPUT: usize = 6;
+
+/// The food signals pooled by swarms on the last sensory tick.
+#[derive(Resource, Default)]
+pub struct SharedSensory {
+    /// Mean frontal food signal of every organism's swarm, itself included.
+    /// Organisms with nobody near are in no swarm.
+    pub(crate) pools: HashMap<Entity, f32>,
+    /// Neighbors within swarm range of every organism.
+    pub(crate) neighbors: HashMap<Entity, usize>,
+    /// Mean pool of the organisms in a swarm.
+    pub food_signal: f32,
+}
+
+impl SharedSensory {
+    /// The pooled food signal of the swarm `organism` is in, 0 for none.
+    pub fn pool(&self, organism: Entity) -> f32 {
+        self.pools.get(&organism).copied().unwrap_or(0.0)
+    }
+}
+
+/// How close organisms have to be to share what they sense.
+fn swarm_range(config: &SimulationConfig) -> f32 {
+    config.organism_vision / 2.0
+}
+
+/// Pools the frontal food signals of the organisms within swarm range of
+/// each other, once they were all sensed on the sensory tick.
+pub(crate) fn share_sensory(
+    config: Res<SimulationConfig>,
+    grid: Res<SpatialGrid>,
+    timer: Res<SensoryTimer>,
+    mut shared: ResMut<SharedSensory>,
+    query: Query<(Entity, &Transform, &SensoryInputs), With<Organism>>,
+) {
+    if !config.swarm_sensing || !timer.0.just_finished() {
+        return;
+    }
+    let range = swarm_range(&config);
+    shared.pools.clear();
+    shared.neighbors.clear();
+    let mut total = 0.0;
+    for (organism, transform, inputs) in &query {
+        let position = transform.translation;
+        let mut sum = inputs.0[FRONT_FOOD_INPUT];
+        let mut members = 1;
+        let nearby = topology::query_circle(&config, &grid, position.truncate(), range);
+        for (other, other_transform, other_inputs) in query.iter_many(&nearby) {
+            if other == organism
+                || displacement(&config, position, other_transform.translation).length() >= range
+            {
+                continue;
+            }
+            sum += other_inputs.0[FRONT_FOOD_INPUT];
+            members += 1;
+        }
+        shared.neighbors.insert(organism, members - 1);
+        if members > 1 {
+            let pool = sum / members as f32;
+            shared.pools.insert(organism, pool);
+            total += pool;
+        }
+    }
+    shared.food_signal = if shared.pools.is_empty() {
+        0.0
+    } else {
+        total / shared.pools.len() as f32
+    };
+}
+
+/// Writes the mean swarm sensing gene, how clustered the organisms are and
+/// how the two correlate across organisms to swarm.csv on every log tick.
+pub(crate) fn log_swarm(
+    config: Res<SimulationConfig>,
+    tick: Res<SimTick>,
+    timer: Res<LogTimer>,
+    shared: Res<SharedSensory>,
+    mut log: Local<Option<std::io::BufWriter<std::fs::File>>>,
+    query: Query<(Entity, &Traits), With<Organism>>,
+) {
+    if !config.swarm_sensing || !timer.0.just_finished() {
+        return;
+    }
+    let samples: Vec<(f64, f64)> = query
+        .iter()
+        .filter_map(|(organism, traits)| {
+            let neighbors = *shared.neighbors.get(&organism)?;
+            Some((traits.swarm_sensing as f64, neighbors as f64))
+        })
+        .collect();
+    if samples.is_empty() {
+        return;
+    }
+    let n = samples.len() as f64;
+    let mean_gene = samples.iter().map(|(g, _)| g).sum::<f64>() / n;
+    let mean_neighbors = samples.iter().map(|(_, c)| c).sum::<f64>() / n;
+    let (mut cov, mut var_gene, mut var_neighbors) = (0.0, 0.0, 0.0);
+    for (g, c) in &samples {
+        cov += (g - mean_gene) * (c - mean_neighbors);
+        var_gene += (g - mean_gene).powi(2);
+        var_neighbors += (c - mean_neighbors).powi(2);
+    }
+    // empty until both have varied
+    let correlation = if var_gene > 0.0 && var_neighbors > 0.0 {
+        (cov / (var_gene * var_neighbors).sqrt()).to_string()
+    } else {
+        String::new()
+    };
+
+    let log = log.get_or_insert_with(|| {
+        let file = std::fs::File::create("swarm.csv").unwrap();
+        let mut file = std::io::BufWriter::new(file);
+        file.write_all(b"tick,mean_swarm_sensing,mean_neighbors,correlation,food_signal\n")
+            .unwrap();
+        file
+    });
+    writeln!(
+        log,
+        "{},{},{},{},{}",
+        tick.0, mean_gene, mean_neighbors, correlation, shared.food_signal
+    )
+    .unwrap();
+}
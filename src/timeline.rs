@@ -0,0 +1,266 @@
+//! A timeline of the notable events in the selected organism's life, shown
+//! newest first in a panel under the inspector.
+
+use std::collections::VecDeque;
+use std::fmt::Write;
+
+use bevy::prelude::*;
+
+use crate::hud::UI_FONT;
+use crate::inspector::Selected;
+use crate::organism::{BirthTick, LifeEvent, LifeEventKind, Organism};
+use crate::SimTick;
+
+/// Most entries kept, the oldest ones are dropped first.
+pub const TIMELINE_LENGTH: usize = 20;
+
+/// One or more events of the same kind in a row.
+#[derive(Clone, Copy, Debug)]
+pub struct TimelineEntry {
+    /// Tick of the first of the events.
+    pub tick: u64,
+    pub kind: LifeEventKind,
+    pub count: usize,
+}
+
+/// The events of the organism selected last, only kept for one organism at
+/// a time so that it stays small.
+#[derive(Resource, Default)]
+pub struct Timeline {
+    pub organism: Option<Entity>,
+    /// Newest first.
+    pub entries: VecDeque<TimelineEntry>,
+    /// Set once the organism died, after which the timeline no longer
+    /// changes until another organism is selected.
+    pub frozen: bool,
+}
+
+impl Timeline {
+    /// Starts the timeline of `organism`, born on `birth_tick`.
+    pub fn start(&mut self, organism: Entity, birth_tick: u64) {
+        *self = Self {
+            organism: Some(organism),
+            ..default()
+        };
+        self.push(birth_tick, LifeEventKind::Born);
+    }
+
+    /// Adds an event, merging meals in a row into a single entry.
+    pub fn push(&mut self, tick: u64, kind: LifeEventKind) {
+        if self.frozen {
+            return;
+        }
+        if let Some(newest) = self.entries.front_mut() {
+            if kind == LifeEventKind::Ate && newest.kind == kind {
+                newest.count += 1;
+                return;
+            }
+        }
+        self.entries.push_front(TimelineEntry {
+            tick,
+            kind,
+            count: 1,
+        });
+        self.entries.truncate(TIMELINE_LENGTH);
+        if kind == LifeEventKind::Died {
+            self.frozen = true;
+        }
+    }
+}
+
+#[derive(Component)]
+pub(crate) struct TimelineText;
+
+pub(crate) fn setup_timeline(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn((
+        TimelineText,
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: asset_server.load(UI_FONT),
+                font_size: 14.0,
+                color: Color::WHITE,
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                bottom: Val::Px(10.0),
+                right: Val::Px(10.0),
+                ..default()
+            },
+            ..default()
+        }),
+    ));
+}
+
+/// Records the events of the selected organism, starting over whenever
+/// another one is selected.
+pub(crate) fn update_timeline(
+    tick: Res<SimTick>,
+    mut timeline: ResMut<Timeline>,
+    mut life_events: EventReader<LifeEvent>,
+    selected_query: Query<(Entity, &BirthTick), With<Selected>>,
+    organism_query: Query<(), With<Organism>>,
+) {
+    for event in life_events.iter() {
+        if timeline.organism == Some(event.organism) {
+            timeline.push(event.tick, event.kind);
+        }
+    }
+    // a dead organism is no longer selected, so this goes before the
+    // selection is looked at
+    if let Some(organism) = timeline.organism {
+        if !timeline.frozen && organism_query.get(organism).is_err() {
+            timeline.push(tick.0, LifeEventKind::Died);
+        }
+    }
+    match selected_query.get_single() {
+        Ok((organism, birth)) if timeline.organism != Some(organism) => {
+            timeline.start(organism, birth.0);
+        }
+        Ok(_) => {}
+        Err(_) if timeline.organism.is_some() && !timeline.frozen => {
+            *timeline = Timeline::default();
+        }
+        Err(_) => {}
+    }
+}
+
+pub(crate) fn update_timeline_panel(
+    timeline: Res<Timeline>,
+    mut text_query: Query<&mut Text, With<TimelineText>>,
+) {
+    if !timeline.is_changed() {
+        return;
+    }
+    let Ok(mut text) = text_query.get_single_mut() else {
+        return;
+    };
+    let mut panel = String::new();
+    if timeline.organism.is_some() {
+        writeln!(
+            panel,
+            "timeline{}:",
+            if timeline.frozen { " (dead)" } else { "" }
+        )
+        .unwrap();
+    }
+    for entry in &timeline.entries {
+        let event = match entry.kind {
+            LifeEventKind::Born => "born".to_string(),
+            LifeEventKind::Ate if entry.count > 1 => format!("ate x{}", entry.count),
+            LifeEventKind::Ate => "ate".to_string(),
+            LifeEventKind::Conceived => "got pregnant".to_string(),
+            LifeEventKind::Mated => "mated".to_string(),
+            LifeEventKind::GaveBirth(children) => format!("gave birth to {children}"),
+            LifeEventKind::LaidEggs(eggs) => format!("laid {eggs} eggs"),
+            LifeEventKind::Died => "died".to_string(),
+        };
+        writeln!(panel, "{:>8}  {event}", entry.tick).unwrap();
+    }
+    text.sections[0].value = panel;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An app keeping the timeline of a selected organism born on tick 5.
+    fn app_with_selected() -> (App, Entity) {
+        let mut app = App::new();
+        app.init_resource::<SimTick>()
+            .init_resource::<Timeline>()
+            .add_event::<LifeEvent>()
+            .add_system(update_timeline);
+        let organism = app.world.spawn((Organism, Selected, BirthTick(5))).id();
+        app.update();
+        (app, organism)
+    }
+
+    fn send(app: &mut App, organism: Entity, tick: u64, kind: LifeEventKind) {
+        app.world.send_event(LifeEvent {
+            organism,
+            tick,
+            kind,
+        });
+    }
+
+    /// The timeline oldest first, as (tick, kind, count).
+    fn history(app: &App) -> Vec<(u64, LifeEventKind, usize)> {
+        let timeline = app.world.resource::<Timeline>();
+        timeline
+            .entries
+            .iter()
+            .rev()
+            .map(|entry| (entry.tick, entry.kind, entry.count))
+            .collect()
+    }
+
+    #[test]
+    fn events_are_kept_in_order_with_meals_merged() {
+        let (mut app, organism) = app_with_selected();
+        send(&mut app, organism, 10, LifeEventKind::Ate);
+        send(&mut app, organism, 12, LifeEventKind::Ate);
+        app.update();
+        send(&mut app, organism, 15, LifeEventKind::Ate);
+        send(&mut app, organism, 20, LifeEventKind::Conceived);
+        app.update();
+        send(&mut app, organism, 30, LifeEventKind::GaveBirth(3));
+        send(&mut app, organism, 31, LifeEventKind::Ate);
+        app.update();
+
+        assert_eq!(
+            history(&app),
+            [
+                (5, LifeEventKind::Born, 1),
+                (10, LifeEventKind::Ate, 3),
+                (20, LifeEventKind::Conceived, 1),
+                (30, LifeEventKind::GaveBirth(3), 1),
+                (31, LifeEventKind::Ate, 1),
+            ]
+        );
+        assert!(!app.world.resource::<Timeline>().frozen);
+    }
+
+    #[test]
+    fn events_of_other_organisms_are_left_out() {
+        let (mut app, organism) = app_with_selected();
+        let other = app.world.spawn(Organism).id();
+        send(&mut app, other, 10, LifeEventKind::Ate);
+        send(&mut app, organism, 11, LifeEventKind::Mated);
+        app.update();
+
+        assert_eq!(
+            history(&app),
+            [(5, LifeEventKind::Born, 1), (11, LifeEventKind::Mated, 1)]
+        );
+    }
+
+    #[test]
+    fn timeline_freezes_when_the_organism_dies() {
+        let (mut app, organism) = app_with_selected();
+        send(&mut app, organism, 10, LifeEventKind::Ate);
+        app.update();
+
+        app.world.resource_mut::<SimTick>().0 = 40;
+        app.world.despawn(organism);
+        app.update();
+        // late events and later ticks change nothing
+        send(&mut app, organism, 41, LifeEventKind::Ate);
+        app.world.resource_mut::<SimTick>().0 = 50;
+        app.update();
+
+        let timeline = app.world.resource::<Timeline>();
+        assert!(timeline.frozen);
+        assert_eq!(timeline.organism, Some(organism));
+        assert_eq!(
+            history(&app),
+            [
+                (5, LifeEventKind::Born, 1),
+                (10, LifeEventKind::Ate, 1),
+                (40, LifeEventKind::Died, 1),
+            ]
+        );
+    }
+}
@@ -0,0 +1,66 @@
+//! Torus mode: the arena has no walls and its opposite edges are glued
+//! together, so distances and neighborhoods are measured through the wrap.
+
+use bevy::prelude::*;
+
+use crate::config::{SimulationConfig, WorldTopology};
+use crate::spatial::SpatialGrid;
+
+pub fn is_torus(config: &SimulationConfig) -> bool {
+    config.world_topology == WorldTopology::Torus
+}
+
+fn size(config: &SimulationConfig) -> Vec2 {
+    Vec2::new(
+        config.right_boundary - config.left_boundary,
+        config.top_boundary - config.bottom_boundary,
+    )
+}
+
+/// Brings `position` back into the arena across the edges it left through.
+pub fn wrap(config: &SimulationConfig, position: Vec3) -> Vec3 {
+    let size = size(config);
+    let x = config.left_boundary + (position.x - config.left_boundary).rem_euclid(size.x);
+    let y = config.bottom_boundary + (position.y - config.bottom_boundary).rem_euclid(size.y);
+    Vec3::new(x, y, position.z)
+}
+
+/// The shortest vector from `from` to `to`, through the wrap in torus mode.
+pub fn displacement(config: &SimulationConfig, from: Vec3, to: Vec3) -> Vec3 {
+    let delta = to - from;
+    if !is_torus(config) {
+        return delta;
+    }
+    let size = size(config);
+    // the closest of the periodic images of `to`
+    let wrapped = delta.truncate() - size * (delta.truncate() / size).round();
+    wrapped.extend(delta.z)
+}
+
+/// Every entity of `grid` within `radius` of `center`, including the ones
+/// across the edges in torus mode.
+pub fn query_circle(
+    config: &SimulationConfig,
+    grid: &SpatialGrid,
+    center: Vec2,
+    radius: f32,
+) -> Vec<Entity> {
+    if !is_torus(config) {
+        return grid.query_circle(center, radius);
+    }
+    let size = size(config);
+    let min = Vec2::new(config.left_boundary, config.bottom_boundary) - radius;
+    let max = Vec2::new(config.right_boundary, config.top_boundary) + radius;
+    let mut entities = Vec::new();
+    for x in -1..=1 {
+        for y in -1..=1 {
+            let image = center + size * Vec2::new(x as f32, y as f32);
+            // only the images near the arena can reach into it
+            if image.cmpge(min).all() && image.cmple(max).all() {
+                entities.extend(grid.query_circle(image, radius));
+            }
+        }
+    }
+    grid.sort(&mut entities);
+    entities
+}
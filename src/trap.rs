@@ -0,0 +1,50 @@
+//! Death traps, zones set in the config that kill any organism entering them.
+
+use bevy::prelude::*;
+
+use crate::config::SimulationConfig;
+use crate::organism::Organism;
+use crate::{Dead, DeathCause};
+
+pub const TRAP_COLOR: Color = Color::rgb(0.35, 0.0, 0.0);
+
+/// A circular zone of `radius` around `position` that organisms die in.
+#[derive(Component)]
+pub struct DeathTrap {
+    pub position: Vec3,
+    pub radius: f32,
+}
+
+pub(crate) fn spawn_traps(mut commands: Commands, config: Res<SimulationConfig>) {
+    for trap in &config.death_traps {
+        // drawn under everything else
+        let position = Vec3::new(trap.position[0], trap.position[1], -1.0);
+        commands.spawn((
+            DeathTrap {
+                position,
+                radius: trap.radius,
+            },
+            Transform::from_translation(position).with_scale(Vec3::splat(trap.radius * 2.0)),
+        ));
+    }
+}
+
+/// Kills the organisms whose center is inside a trap.
+pub(crate) fn spring_traps(
+    mut commands: Commands,
+    trap_query: Query<&DeathTrap>,
+    organism_query: Query<(Entity, &Transform), With<Organism>>,
+) {
+    for trap in &trap_query {
+        for (organism, transform) in &organism_query {
+            if transform
+                .translation
+                .truncate()
+                .distance(trap.position.truncate())
+                < trap.radius
+            {
+                commands.entity(organism).insert(Dead(DeathCause::Trap));
+            }
+        }
+    }
+}
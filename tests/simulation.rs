@@ -0,0 +1,517 @@
+//! Whole simulation runs without a window.
+
+use std::fmt::Debug;
+use std::sync::{Mutex, Once};
+
+use bevy::ecs::event::ManualEventReader;
+use bevy::prelude::*;
+use bevy::utils::tracing::field::{Field, Visit};
+use bevy::utils::tracing::{span, Event, Level, Metadata, Subscriber};
+use bevy_game_rs::census::CENSUS_QUANTITIES;
+use bevy_game_rs::config::{FoodSupply, NutrientCyclingConfig, ObstacleConfig, TrapConfig};
+use bevy_game_rs::food::Food;
+use bevy_game_rs::mirror::{center_x, mirror_position};
+use bevy_game_rs::organism::CollisionEvent;
+use bevy_game_rs::save::SaveRequested;
+use bevy_game_rs::{
+    headless_app, Census, DeathEvent, Direction, Energy, FounderLayout, Organism, OrganismId,
+    Poison, Pregnant, SavedWorld, SimStats, SimTick, SimulationConfig, SimulationSpeed,
+    WorldTopology, TIME_STEP,
+};
+
+/// A headless app set up and ready to run ticks. The logs of all the runs
+/// go to a scratch directory instead of the working tree.
+fn app(config: SimulationConfig) -> App {
+    static SCRATCH: Once = Once::new();
+    SCRATCH.call_once(|| {
+        let dir = std::env::temp_dir().join("evolution-game-tests");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::env::set_current_dir(dir).unwrap();
+    });
+    let mut app = headless_app(config, None, false);
+    app.setup();
+    app
+}
+
+/// An app resuming from the save at `config.saves.path`.
+fn loaded_app(config: SimulationConfig) -> App {
+    let saved = SavedWorld::load(config.saves.path.to_str().unwrap()).unwrap();
+    let mut app = headless_app(config, Some(saved), false);
+    app.setup();
+    app
+}
+
+/// Messages of the warnings and errors logged while the tests run.
+static WARNINGS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Keeps the messages of the warnings and errors in [`WARNINGS`].
+struct WarningLog;
+
+impl Subscriber for WarningLog {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        *metadata.level() <= Level::WARN
+    }
+
+    fn new_span(&self, _: &span::Attributes) -> span::Id {
+        span::Id::from_u64(1)
+    }
+
+    fn record(&self, _: &span::Id, _: &span::Record) {}
+
+    fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+
+    fn event(&self, event: &Event) {
+        struct Message(String);
+        impl Visit for Message {
+            fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+                if field.name() == "message" {
+                    self.0 = format!("{value:?}");
+                }
+            }
+        }
+        let mut message = Message(String::new());
+        event.record(&mut message);
+        WARNINGS.lock().unwrap().push(message.0);
+    }
+
+    fn enter(&self, _: &span::Id) {}
+
+    fn exit(&self, _: &span::Id) {}
+}
+
+/// Starts keeping the warnings of all the threads in [`WARNINGS`].
+fn log_warnings() {
+    static SUBSCRIBER: Once = Once::new();
+    SUBSCRIBER.call_once(|| {
+        bevy::utils::tracing::subscriber::set_global_default(WarningLog).unwrap();
+    });
+}
+
+/// Updates the app until `ticks` fixed ticks have run in total.
+fn run(app: &mut App, ticks: u64) {
+    while app.world.resource::<SimTick>().0 < ticks {
+        app.update();
+    }
+}
+
+/// Config of an arena without organisms where food never goes away.
+fn empty_arena() -> SimulationConfig {
+    SimulationConfig {
+        seed: Some(1),
+        initial_population: 0,
+        food_lifetime: 1_000_000,
+        ..SimulationConfig::default()
+    }
+}
+
+#[test]
+fn fixed_food_supply_spawns_food_per_timestep_every_food_tick() {
+    let config = empty_arena();
+    let mut app = app(config.clone());
+    run(&mut app, 600);
+
+    // the food timer as it was before the controller existed
+    let mut timer = Timer::from_seconds(0.2 / config.simulation_speed, TimerMode::Repeating);
+    let period = std::time::Duration::from_secs_f32(TIME_STEP);
+    let food_ticks = (0..600)
+        .filter(|_| timer.tick(period).just_finished())
+        .count();
+    let food = app
+        .world
+        .query_filtered::<(), With<Food>>()
+        .iter(&app.world)
+        .count();
+    assert!(food_ticks > 0);
+    assert_eq!(food, food_ticks * config.food_per_timestep);
+}
+
+#[test]
+fn seeded_runs_are_identical() {
+    let config = SimulationConfig {
+        seed: Some(7),
+        ..SimulationConfig::default()
+    };
+    let mut first = app(config.clone());
+    let mut second = app(config);
+    for ticks in (100..=1000).step_by(100) {
+        run(&mut first, ticks);
+        run(&mut second, ticks);
+        assert_eq!(
+            first.world.resource::<SimStats>(),
+            second.world.resource::<SimStats>(),
+            "diverged by tick {ticks}"
+        );
+    }
+}
+
+#[test]
+fn mirror_arena_halves_get_mirrored_food() {
+    let config = SimulationConfig {
+        mirror_arena: true,
+        poison_probability: 0.3,
+        ..empty_arena()
+    };
+    let mut app = app(config.clone());
+    run(&mut app, 300);
+
+    let center = center_x(&config);
+    let mut left = Vec::new();
+    let mut right = Vec::new();
+    for (transform, poison) in app
+        .world
+        .query_filtered::<(&Transform, Option<&Poison>), Or<(With<Food>, With<Poison>)>>()
+        .iter(&app.world)
+    {
+        let position = transform.translation;
+        if position.x < center {
+            left.push((position.x.to_bits(), position.y.to_bits(), poison.is_some()));
+        } else {
+            let mirrored = mirror_position(&config, position);
+            right.push((mirrored.x.to_bits(), mirrored.y.to_bits(), poison.is_some()));
+        }
+    }
+    left.sort_unstable();
+    right.sort_unstable();
+    assert!(!left.is_empty());
+    assert_eq!(left, right);
+}
+
+/// Positions of the organisms, in the order they were spawned.
+fn organism_positions(app: &mut App) -> Vec<Vec3> {
+    let mut organisms = app
+        .world
+        .query_filtered::<(Entity, &Transform), With<Organism>>()
+        .iter(&app.world)
+        .map(|(entity, transform)| (entity, transform.translation))
+        .collect::<Vec<_>>();
+    organisms.sort_unstable_by_key(|(entity, _)| *entity);
+    organisms
+        .into_iter()
+        .map(|(_, position)| position)
+        .collect()
+}
+
+#[test]
+fn trajectories_do_not_depend_on_the_runtime_speed() {
+    let config = SimulationConfig {
+        seed: Some(3),
+        ..SimulationConfig::default()
+    };
+    let mut fast = app(config.clone());
+    fast.insert_resource(SimulationSpeed(10.0));
+    run(&mut fast, 500);
+    // several ticks run per update at 10x, so stop the 1x run at the same one
+    let ticks = fast.world.resource::<SimTick>().0;
+    let mut slow = app(config);
+    run(&mut slow, ticks);
+    assert_eq!(slow.world.resource::<SimTick>().0, ticks);
+
+    let positions = organism_positions(&mut fast);
+    assert!(!positions.is_empty());
+    assert_eq!(organism_positions(&mut slow), positions);
+}
+
+/// Config of a run with most of the optional features turned on. Eggs are
+/// left out, the founders of this seed don't survive laying them.
+fn all_features() -> SimulationConfig {
+    SimulationConfig {
+        seed: Some(42),
+        poison_probability: 0.1,
+        food_supply: FoodSupply::Controller {
+            target_ratio: 10.0,
+            kp: 0.5,
+            ki: 0.05,
+            min_food_per_timestep: 2,
+            max_food_per_timestep: 4,
+        },
+        nutrient_cycling: Some(NutrientCyclingConfig::default()),
+        death_traps: vec![TrapConfig {
+            position: [300.0, 0.0],
+            radius: 20.0,
+        }],
+        obstacles: vec![ObstacleConfig {
+            center: [-300.0, 0.0],
+            size: [40.0, 200.0],
+        }],
+        competition_cost: 0.001,
+        competition_strength: 0.5,
+        rare_advantage: 0.1,
+        swarm_sensing: true,
+        steering_smoothing: 0.5,
+        log_mutations: true,
+        ..SimulationConfig::default()
+    }
+}
+
+/// Runs `ticks` ticks and returns a census sample every 1000 ticks,
+/// checking that none of them found a leak.
+fn census_samples(app: &mut App, ticks: u64) -> Vec<Vec<usize>> {
+    (1..=ticks / 1000)
+        .map(|k| {
+            run(app, k * 1000);
+            let census = app.world.resource::<Census>();
+            assert!(census.leaks.is_empty(), "leaks: {:?}", census.leaks);
+            census.values.clone()
+        })
+        .collect()
+}
+
+/// Asserts that no census quantity got past 1.5 times its peak over the
+/// first half of the run during the second half, past some slack for small
+/// counts.
+fn assert_plateaus(samples: &[Vec<usize>]) {
+    let (first, second) = samples.split_at(samples.len() / 2);
+    for (i, name) in CENSUS_QUANTITIES.iter().enumerate() {
+        let peak = |samples: &[Vec<usize>]| samples.iter().map(|s| s[i]).max().unwrap();
+        let (before, after) = (peak(first), peak(second));
+        assert!(
+            after <= before * 3 / 2 + 10,
+            "{name} grew from {before} to {after}"
+        );
+    }
+}
+
+#[test]
+#[ignore = "runs 50k ticks, takes minutes"]
+fn census_quantities_plateau_in_a_long_run() {
+    let mut app = app(all_features());
+    let samples = census_samples(&mut app, 50_000);
+    assert!(samples.last().unwrap()[0] > 0, "the population died out");
+    assert_plateaus(&samples);
+}
+
+#[test]
+fn planned_organism_turns_before_reaching_a_wall() {
+    let config = SimulationConfig {
+        seed: Some(5),
+        initial_population: 1,
+        founder_layout: FounderLayout::Custom {
+            positions: vec![[400.0, 0.0]],
+        },
+        // too little food to steer by
+        food_per_timestep: 1,
+        food_lifetime: 1,
+        ..SimulationConfig::default()
+    };
+    let mut app = app(config.clone());
+    // the founders are spawned on the first update
+    app.update();
+    let organism = app
+        .world
+        .query_filtered::<Entity, With<Organism>>()
+        .single(&app.world);
+    app.world.get_mut::<Direction>(organism).unwrap().0 = Vec2::X;
+
+    let mut collisions = ManualEventReader::<CollisionEvent>::default();
+    let mut turned = false;
+    for tick in 1..=300 {
+        run(&mut app, tick);
+        let events = app.world.resource::<Events<CollisionEvent>>();
+        assert!(
+            !collisions
+                .iter(events)
+                .any(|event| matches!(event, CollisionEvent::Wall)),
+            "hit the wall on tick {tick}"
+        );
+        turned |= app.world.get::<Direction>(organism).unwrap().x <= 0.0;
+    }
+    assert!(turned);
+}
+
+#[test]
+fn organisms_wrap_across_every_edge_of_a_torus() {
+    let config = SimulationConfig {
+        seed: Some(9),
+        initial_population: 4,
+        world_topology: WorldTopology::Torus,
+        founder_layout: FounderLayout::Custom {
+            positions: vec![[0.0, 0.0]; 4],
+        },
+        ..empty_arena()
+    };
+    let mut app = app(config.clone());
+    // the founders are spawned on the first update
+    app.update();
+    let organisms = app
+        .world
+        .query_filtered::<Entity, With<Organism>>()
+        .iter(&app.world)
+        .collect::<Vec<_>>();
+    // just past the left, right, bottom and top edges, heading out
+    let outside = [
+        (Vec2::new(config.left_boundary - 1.0, 0.0), Vec2::NEG_X),
+        (Vec2::new(config.right_boundary + 1.0, 0.0), Vec2::X),
+        (Vec2::new(0.0, config.bottom_boundary - 1.0), Vec2::NEG_Y),
+        (Vec2::new(0.0, config.top_boundary + 1.0), Vec2::Y),
+    ];
+    for (&organism, (position, direction)) in organisms.iter().zip(outside) {
+        app.world
+            .get_mut::<Transform>(organism)
+            .unwrap()
+            .translation = position.extend(0.0);
+        app.world.get_mut::<Direction>(organism).unwrap().0 = direction;
+    }
+
+    let tick = app.world.resource::<SimTick>().0;
+    run(&mut app, tick + 1);
+    for (organism, (position, _)) in organisms.into_iter().zip(outside) {
+        let transform = app
+            .world
+            .get::<Transform>(organism)
+            .unwrap_or_else(|| panic!("the organism past {position} died"));
+        let wrapped = transform.translation;
+        assert!(
+            (config.left_boundary..=config.right_boundary).contains(&wrapped.x)
+                && (config.bottom_boundary..=config.top_boundary).contains(&wrapped.y),
+            "the organism past {position} is at {wrapped}"
+        );
+    }
+}
+
+/// Positions and sizes of the organisms by their id.
+fn positions_by_id(app: &mut App) -> Vec<(u64, Vec3, Vec3)> {
+    let mut organisms = app
+        .world
+        .query_filtered::<(&OrganismId, &Transform), With<Organism>>()
+        .iter(&app.world)
+        .map(|(id, transform)| (id.0, transform.translation, transform.scale))
+        .collect::<Vec<_>>();
+    organisms.sort_unstable_by_key(|(id, ..)| *id);
+    organisms
+}
+
+#[test]
+fn a_loaded_save_goes_on_like_the_run_it_was_taken_from() {
+    let mut config = all_features();
+    config.saves.path = "round_trip.json".into();
+    let mut uninterrupted = app(config.clone());
+    run(&mut uninterrupted, 600);
+    uninterrupted.insert_resource(SaveRequested(true));
+    run(&mut uninterrupted, 601);
+
+    let mut loaded = loaded_app(config);
+    run(&mut loaded, 601);
+    assert_eq!(
+        positions_by_id(&mut loaded),
+        positions_by_id(&mut uninterrupted),
+        "differs once loaded"
+    );
+    for ticks in (700..=1600).step_by(100) {
+        run(&mut uninterrupted, ticks);
+        run(&mut loaded, ticks);
+        assert_eq!(
+            positions_by_id(&mut loaded),
+            positions_by_id(&mut uninterrupted),
+            "diverged by tick {ticks}"
+        );
+        assert_eq!(
+            loaded.world.resource::<SimStats>(),
+            uninterrupted.world.resource::<SimStats>(),
+            "diverged by tick {ticks}"
+        );
+    }
+    assert!(!positions_by_id(&mut loaded).is_empty());
+}
+
+#[test]
+fn an_organism_past_a_wall_dies_once() {
+    log_warnings();
+    let config = SimulationConfig {
+        seed: Some(11),
+        initial_population: 1,
+        founder_layout: FounderLayout::Custom {
+            positions: vec![[0.0, 0.0]],
+        },
+        ..empty_arena()
+    };
+    let mut app = app(config.clone());
+    // the founders are spawned on the first update
+    app.update();
+    let organism = app
+        .world
+        .query_filtered::<Entity, With<Organism>>()
+        .single(&app.world);
+    app.world
+        .get_mut::<Transform>(organism)
+        .unwrap()
+        .translation
+        .x = config.right_boundary + 5.0;
+    // starving too, so that more than one system finds it dead
+    app.world.get_mut::<Energy>(organism).unwrap().0 = 0.0;
+
+    let mut deaths = ManualEventReader::<DeathEvent>::default();
+    let tick = app.world.resource::<SimTick>().0;
+    run(&mut app, tick + 1);
+    let events = app.world.resource::<Events<DeathEvent>>();
+    let died = deaths
+        .iter(events)
+        .filter(|event| event.organism == organism)
+        .count();
+    assert_eq!(died, 1);
+    assert!(app.world.get_entity(organism).is_none());
+    let warnings = WARNINGS.lock().unwrap();
+    let despawns: Vec<_> = warnings
+        .iter()
+        .filter(|warning| warning.contains("despawn"))
+        .collect();
+    assert!(despawns.is_empty(), "{despawns:?}");
+}
+
+/// An app with one organism, made pregnant before the first tick after the
+/// founders are spawned.
+fn pregnant_app(config: SimulationConfig) -> (App, Entity) {
+    let config = SimulationConfig {
+        seed: Some(11),
+        initial_population: 1,
+        founder_layout: FounderLayout::Custom {
+            positions: vec![[0.0, 0.0]],
+        },
+        ..config
+    };
+    let mut app = app(config);
+    app.update();
+    let organism = app
+        .world
+        .query_filtered::<Entity, With<Organism>>()
+        .single(&app.world);
+    app.world.entity_mut(organism).insert(Pregnant(0));
+    (app, organism)
+}
+
+fn population(app: &mut App) -> usize {
+    app.world
+        .query_filtered::<(), With<Organism>>()
+        .iter(&app.world)
+        .count()
+}
+
+#[test]
+fn a_pregnant_organism_gives_birth_after_the_gestation() {
+    let (mut app, organism) = pregnant_app(SimulationConfig {
+        gestation_ticks: 5,
+        ..empty_arena()
+    });
+    let tick = app.world.resource::<SimTick>().0;
+    for elapsed in 1..=5 {
+        run(&mut app, tick + elapsed);
+        assert_eq!(app.world.get::<Pregnant>(organism).unwrap().0, elapsed);
+        assert_eq!(population(&mut app), 1);
+        assert_eq!(app.world.resource::<SimStats>().pregnant, 1);
+    }
+    run(&mut app, tick + 6);
+    assert!(app.world.get::<Pregnant>(organism).is_none());
+    assert!(population(&mut app) > 1);
+    // the marker is removed at the end of the tick
+    run(&mut app, tick + 7);
+    assert_eq!(app.world.resource::<SimStats>().pregnant, 0);
+}
+
+#[test]
+fn without_a_gestation_birth_comes_on_the_next_tick() {
+    let (mut app, organism) = pregnant_app(empty_arena());
+    assert_eq!(SimulationConfig::default().gestation_ticks, 0);
+    let tick = app.world.resource::<SimTick>().0;
+    run(&mut app, tick + 1);
+    assert!(app.world.get::<Pregnant>(organism).is_none());
+    assert!(population(&mut app) > 1);
+}